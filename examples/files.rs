@@ -0,0 +1,45 @@
+//! Compresses a file into another file with a filemagic, and decompresses it back, using the
+//! library directly instead of `jan`.
+//!
+//! ```console
+//! $ cargo run --example files
+//! ```
+
+use splaycompress::{compress_with_magic, decompress_with_magic, DecompressOptions, Flavor};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+
+fn main() {
+    let dir = env::temp_dir().join(format!("splaycompress-example-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let original = dir.join("readme.md");
+    let compressed = dir.join("readme.md.jan");
+    let restored = dir.join("readme.restored.md");
+    fs::write(&original, include_bytes!("../README.md")).unwrap();
+
+    let r = BufReader::new(File::open(&original).unwrap());
+    let mut w = BufWriter::new(File::create(&compressed).unwrap());
+    let stats = compress_with_magic(Flavor::Symbol8, r, &mut w).unwrap();
+    w.flush().unwrap();
+    println!(
+        "{} -> {} bytes",
+        fs::metadata(&original).unwrap().len(),
+        stats.bytes_out
+    );
+
+    // The flavor comes from the filemagic, it doesn't need to be passed again.
+    let r = BufReader::new(File::open(&compressed).unwrap());
+    let mut w = BufWriter::new(File::create(&restored).unwrap());
+    decompress_with_magic(r, &mut w, &DecompressOptions::default()).unwrap();
+    w.flush().unwrap();
+
+    assert_eq!(fs::metadata(&compressed).unwrap().len(), stats.bytes_out);
+    assert_eq!(fs::read(&restored).unwrap(), fs::read(&original).unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_main() {
+    main();
+}
@@ -0,0 +1,36 @@
+//! Compresses a short message in memory, and decompresses it again.
+//!
+//! ```console
+//! $ cargo run --example roundtrip
+//! ```
+
+use splaycompress::{compress, decompress_into, Flavor};
+
+fn main() {
+    let message = b"However, this compression scheme is actually surprisingly good for short snippets of data.";
+
+    let mut compressed = Vec::new();
+    compress(Flavor::Symbol8, &message[..], &mut compressed).unwrap();
+    println!(
+        "{} bytes compressed to {} bytes",
+        message.len(),
+        compressed.len()
+    );
+
+    // The length is known here, so a fixed buffer is enough. Padding may decode to one extra
+    // symbol, hence the extra byte.
+    let mut buf = [0; 128];
+    let len = decompress_into(
+        Flavor::Symbol8,
+        &compressed[..],
+        &mut buf[..message.len() + 1],
+    )
+    .unwrap();
+    assert_eq!(&buf[..message.len()], message);
+    assert!(len <= message.len() + 1);
+}
+
+#[test]
+fn test_main() {
+    main();
+}
@@ -0,0 +1,91 @@
+//! Streams a large input through `write::Compressor` and back through `read::Decompressor`,
+//! without ever holding the plaintext in memory.
+//!
+//! ```console
+//! $ cargo run --example streaming
+//! ```
+
+use splaycompress::read::Decompressor;
+use splaycompress::write::Compressor;
+use splaycompress::Flavor;
+use std::io::{self, Read};
+
+/// An endless, somewhat compressible stream of log-like lines.
+struct Lines {
+    n: u64,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Lines {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() {
+            self.n += 1;
+            self.line = format!(
+                "{:08} INFO request {} took {}ms\n",
+                self.n,
+                self.n * 7,
+                self.n % 97
+            )
+            .into_bytes();
+            self.pos = 0;
+        }
+        let len = buf.len().min(self.line.len() - self.pos);
+        buf[..len].copy_from_slice(&self.line[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+fn lines() -> Lines {
+    Lines {
+        n: 0,
+        line: Vec::new(),
+        pos: 0,
+    }
+}
+
+const LEN: u64 = 256 << 10;
+
+fn main() {
+    let mut compressor = Compressor::new(Flavor::Symbol8, Vec::new());
+    let copied = io::copy(&mut lines().take(LEN), &mut compressor).unwrap();
+    let compressed = compressor.finish().unwrap();
+    assert_eq!(copied, LEN);
+    println!("{LEN} bytes compressed to {} bytes", compressed.len());
+
+    // Comparing against a fresh copy of the input keeps this in constant memory, too.
+    let mut decompressor = Decompressor::new(Flavor::Symbol8, &compressed[..]);
+    let mut checker = Checker {
+        expected: lines().take(LEN),
+        seen: 0,
+    };
+    let copied = io::copy(&mut decompressor, &mut checker).unwrap();
+    assert_eq!(copied, LEN);
+    assert_eq!(checker.seen, LEN);
+}
+
+/// Compares everything written to it against `expected`.
+struct Checker<R: Read> {
+    expected: R,
+    seen: u64,
+}
+
+impl<R: Read> io::Write for Checker<R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut expected = vec![0; buf.len()];
+        self.expected.read_exact(&mut expected)?;
+        assert_eq!(buf, expected, "mismatch after {} bytes", self.seen);
+        self.seen += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_main() {
+    main();
+}
@@ -0,0 +1,23 @@
+//! Feeds arbitrary bytes to the decompressors, which may fail, but must never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use splaycompress::{decompress, decompress_with_magic, Flavor};
+use std::io::sink;
+
+const FLAVORS: [Flavor; 5] = [
+    Flavor::Symbol8,
+    Flavor::Symbol16BE,
+    Flavor::Symbol16LE,
+    Flavor::SymbolI16BE,
+    Flavor::SymbolI16LE,
+];
+
+fuzz_target!(|data: &[u8]| {
+    for flavor in FLAVORS {
+        let _ = decompress(flavor, data, sink());
+    }
+    // Covers the block format, too.
+    let _ = decompress_with_magic(data, sink(), &Default::default());
+});
@@ -0,0 +1,29 @@
+//! Compresses arbitrary input with each flavor, and checks that it decompresses to the same.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use splaycompress::{compress, decompress, Flavor};
+
+const FLAVORS: [Flavor; 5] = [
+    Flavor::Symbol8,
+    Flavor::Symbol16BE,
+    Flavor::Symbol16LE,
+    Flavor::SymbolI16BE,
+    Flavor::SymbolI16LE,
+];
+
+fuzz_target!(|data: &[u8]| {
+    for flavor in FLAVORS {
+        // The 16-bit flavors reject an odd number of bytes, which isn't interesting here.
+        let input = match flavor {
+            Flavor::Symbol8 => data,
+            _ => &data[..data.len() & !1],
+        };
+        let mut compressed = Vec::new();
+        compress(flavor, input, &mut compressed).unwrap();
+        let mut decompressed = Vec::new();
+        decompress(flavor, &compressed[..], &mut decompressed).unwrap();
+        assert_eq!(decompressed, input, "{flavor:?}");
+    }
+});
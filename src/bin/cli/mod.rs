@@ -0,0 +1,87 @@
+//! Plumbing that `jan` and `jancat` share.
+
+use splaycompress::dict::{decompress_with_dictionary, Dictionary};
+use splaycompress::{decompress_with_magic, DecompressOptions, Error, Magic, Stats};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// A short message for `e`, without the "(os error 2)" that `io::Error` appends.
+pub fn describe(e: &io::Error) -> String {
+    let message = e.to_string();
+    match message.rfind(" (os error ") {
+        Some(i) if message.ends_with(')') => message[..i].to_owned(),
+        _ => message,
+    }
+}
+
+/// If whoever reads stdout went away, like `jan -dc foo.jan | head` does, there's nobody left to
+/// tell about anything, so exit quietly. This isn't a failure, just like for gzip.
+pub fn exit_on_broken_pipe(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        std::process::exit(0);
+    }
+    e
+}
+
+/// Parses a size with an optional binary suffix `K`, `M`, or `G`, e.g. `256K`.
+pub fn parse_size(size: &str) -> Result<usize, String> {
+    let (digits, unit) = match size.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&size[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&size[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size {size:?}, expected e.g. 65536, 256K, or 4M"))
+}
+
+pub fn load_dictionary(path: &Path) -> io::Result<Dictionary> {
+    Dictionary::from_bytes(&fs::read(path)?)
+}
+
+/// Decompresses input that starts with `magic`, as found by `peek_magic`. Streams compressed with
+/// a preset dictionary need `dict`. `max_output` is the most bytes that may be written, see
+/// `--max-output`.
+pub fn decompress_detected<R: Read, W: Write>(
+    magic: Magic,
+    dict: Option<&Dictionary>,
+    max_output: Option<usize>,
+    r: R,
+    w: W,
+) -> io::Result<Stats> {
+    let options = decompress_options(max_output);
+    match magic {
+        Magic::Single(_) | Magic::Blocks => decompress_with_magic(r, w, &options),
+        Magic::Dictionary => match dict {
+            Some(dict) => decompress_with_dictionary(dict, r, w, &options),
+            None => Err(Error::DictionaryRequired.into()),
+        },
+    }
+}
+
+/// The options for decompressing with `--max-output`, if given.
+pub fn decompress_options(max_output: Option<usize>) -> DecompressOptions {
+    DecompressOptions {
+        max_output_bytes: max_output.map(|max| max as u64),
+        ..DecompressOptions::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let e = io::Error::from_raw_os_error(2);
+        assert!(e.to_string().contains("os error"));
+        assert!(!describe(&e).contains("os error"));
+        assert!(!describe(&e).is_empty());
+        let e: io::Error = Error::UnknownMagic.into();
+        assert_eq!(describe(&e), e.to_string());
+    }
+}
@@ -0,0 +1,121 @@
+// Only some of the plumbing that `jan` and `jancat` share is needed here.
+#[allow(dead_code)]
+mod cli;
+
+use cli::{describe, exit_on_broken_pipe};
+use splaycompress::{peek_magic, DecompressOptions, DecompressState, Error, Magic, MAGIC_LEN};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, stdin, stdout, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+
+/// Decompresses a file without writing the result, and prints how many bits each symbol took
+/// instead, to see which parts of the data compress badly.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Instead of a line per decoded symbol, print a line per symbol value, with how often it
+    /// occurs, and how many bits it took in total and on average.
+    #[arg(long)]
+    summary: bool,
+
+    /// A single stream with a filemagic, as written by `jan`. The block format and preset
+    /// dictionaries aren't supported. Without a file, reads stdin.
+    file: Option<PathBuf>,
+}
+
+/// Per symbol value: how often it occurred, and how many bits that took.
+type Summary = BTreeMap<i64, (u64, u64)>;
+
+fn inspect<R: Read, W: Write>(args: &Args, r: R, mut w: W) -> io::Result<()> {
+    let (magic, mut r) = peek_magic(r)?;
+    let flavor = match magic.ok_or(Error::UnknownMagic)? {
+        Magic::Single(flavor) => flavor,
+        Magic::Blocks | Magic::Dictionary => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "only single streams can be inspected, not the block format or preset dictionaries",
+            ));
+        }
+    };
+    r.read_exact(&mut [0; MAGIC_LEN])?;
+    let mut state = DecompressState::new(flavor, &DecompressOptions::default());
+    state.trace();
+    let mut summary = Summary::new();
+    let (mut symbols, mut bits) = (0u64, 0u64);
+    if !args.summary {
+        writeln!(
+            w,
+            "{:>10} {:>6} {:>4} {:>11}",
+            "index", "symbol", "bits", "bits/symbol"
+        )?;
+    }
+    let mut buf = [0; 8192];
+    let mut out = Vec::new();
+    loop {
+        let n = match r.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        state.push(&buf[..n], &mut out)?;
+        out.clear();
+        for (symbol, code_length) in state.take_trace() {
+            symbols += 1;
+            bits += u64::from(code_length);
+            if args.summary {
+                let entry = summary.entry(symbol).or_default();
+                entry.0 += 1;
+                entry.1 += u64::from(code_length);
+            } else {
+                let average = bits as f64 / symbols as f64;
+                writeln!(
+                    w,
+                    "{:>10} {symbol:>6} {code_length:>4} {average:>11.3}",
+                    symbols - 1
+                )?;
+            }
+        }
+    }
+    let stats = state.finish()?;
+    if args.summary {
+        writeln!(
+            w,
+            "{:>6} {:>10} {:>10} {:>11}",
+            "symbol", "count", "bits", "bits/symbol"
+        )?;
+        for (symbol, (count, bits)) in summary {
+            let average = bits as f64 / count as f64;
+            writeln!(w, "{symbol:>6} {count:>10} {bits:>10} {average:>11.3}")?;
+        }
+    }
+    writeln!(w, "symbols: {symbols}")?;
+    writeln!(w, "padding bits: {}", stats.trailing_bits)?;
+    writeln!(w, "total bits: {}", bits + stats.trailing_bits as u64)?;
+    w.flush()
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let w = BufWriter::new(stdout().lock());
+    let result = match &args.file {
+        None => inspect(&args, stdin().lock(), w),
+        Some(path) => File::open(path)
+            .and_then(|file| inspect(&args, BufReader::new(file), w))
+            .map_err(|e| {
+                let message = format!("{}: {}", path.display(), describe(&e));
+                io::Error::new(e.kind(), message)
+            }),
+    };
+    match result.map_err(exit_on_broken_pipe) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("jan-inspect: {}", describe(&e));
+            ExitCode::FAILURE
+        }
+    }
+}
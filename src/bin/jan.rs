@@ -1,40 +1,2339 @@
-use splaycompress::{compress, decompress, Flavor};
-use std::io::{stdin, stdout};
+mod cli;
 
-use clap::Parser;
+use cli::{
+    decompress_detected, decompress_options, describe, exit_on_broken_pipe, load_dictionary,
+    parse_size,
+};
+use splaycompress::dict::{compress_keep_tree, compress_with_dictionary, Dictionary};
+use splaycompress::model::{entropy, histogram16, histogram8};
+use splaycompress::vectors::KNOWN_ANSWERS;
+use splaycompress::{
+    choose_flavor, compress_blocks, compress_framed, compress_with_magic, compress_with_stats,
+    decompress_framed, decompress_with_options, estimate_compressed_size, inspect, inspect_members,
+    peek_magic, verify, BlockOptions, Error, Flavor, Magic, Member, Peeked, Stats, Summary,
+    MAX_BLOCK_SIZE,
+};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, stdin, stdout, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+
+/// Default suffix of compressed files.
+const SUFFIX: &str = "jan";
+
+/// Range of the block size that `auto_block_size` picks.
+const AUTO_BLOCK_SIZES: RangeInclusive<usize> = (256 << 10)..=(16 << 20);
+
+/// Smallest `--block-size`. Smaller blocks hardly compress at all, since each starts with a
+/// fresh tree, and adds a header of 9 bytes.
+const MIN_BLOCK_SIZE: usize = 1 << 10;
+
+/// The flavors that `--compare` tries, in the order of preference on a tie.
+const COMPARE_FLAVORS: [Flavor; 3] = [Flavor::Symbol8, Flavor::Symbol16BE, Flavor::Symbol16LE];
+
+/// Inputs smaller than this finish too quickly for a progress bar to be of any use.
+const PROGRESS_MIN_LEN: u64 = 16 << 20;
+
+/// Width of the progress bar itself, without the name and the percentage.
+const PROGRESS_WIDTH: usize = 40;
+
+/// Under these names, `jan` decompresses by default, like gzip does as `gunzip`.
+const DECOMPRESSOR_NAMES: [&str; 2] = ["unjan", "jandec"];
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    after_help = "Packagers can get a man page with `jan --dump-manpage > jan.1`, and shell \
+                  completions with `jan --completions bash`, or zsh, fish, powershell, or elvish."
+)]
 struct Args {
-    /// Whether to decompress instead of compress.
+    /// Whether to decompress instead of compress. Usually not needed, since compressed input is
+    /// recognized by its filemagic. This is the default when invoked as `unjan` or `jandec`.
     #[arg(short, long)]
     decompress: bool,
 
+    /// Compress, even when invoked as `unjan` or `jandec`.
+    #[arg(short = 'z', long, conflicts_with = "decompress")]
+    compress: bool,
+
     /// Flavor of the algorithm to use. Defaults to bit8 which is many times faster but slightly worse at compressing.
+    /// When decompressing, the flavor is taken from the filemagic instead, if there is one.
+    /// `auto` looks at the beginning of each input, and picks a 16-bit flavor only if that
+    /// compresses much better, e.g. for UTF-16 text.
+    #[clap(value_enum)]
+    #[arg(long)]
+    flavor: Option<CLIFlavor>,
+
+    /// For those used to gzip: 1 to 9. This is not a real trade-off between speed and ratio,
+    /// since the algorithm has nothing to tune, it only picks a flavor: 1 is bit8, the fastest,
+    /// 2 to 8 are `--flavor auto`, which uses a 16-bit flavor only where that compresses much
+    /// better, and 9 is `--compare`, which tries bit8, bit16-be, and bit16-le, and keeps the
+    /// smallest result.
+    #[arg(
+        long = "level",
+        value_parser = clap::value_parser!(u8).range(1..=9),
+        conflicts_with_all = ["flavor", "compare", "compare_only", "benchmark", "recommend"]
+    )]
+    compression_level: Option<u8>,
+
+    /// How much of the input `--flavor auto` looks at, e.g. `64K`. This much is held in memory
+    /// before compressing, so that it also works for pipes.
+    #[arg(long, default_value = "64K", value_parser = parse_size)]
+    auto_sample: usize,
+
+    /// Compress with bit8, bit16-be, and bit16-le in parallel, and keep the smallest result. With
+    /// `-v`, prints the size of each. The whole input is read first, see `--compare-memory`.
+    #[arg(long, conflicts_with = "flavor")]
+    compare: bool,
+
+    /// Like `--compare`, but only print the flavors ranked by compressed size to stderr, with
+    /// the ratio of compressed to original size, and write nothing. Reads stdin, or each of the
+    /// files, which are left alone.
+    #[arg(long, conflicts_with_all = ["flavor", "compare", "benchmark", "recommend", "list"])]
+    compare_only: bool,
+
+    /// How much of the input `--compare` and `--compare-only` hold in memory, e.g. `64M`, and
+    /// `--flavor auto` when it needs the length of a pipe. Anything beyond that is spilled to a
+    /// temporary file, so that it can be read again.
+    #[arg(long, default_value = "64M", value_parser = parse_size)]
+    compare_memory: usize,
+
+    /// Refuse to compress with a flavor whose tree needs more memory than this, e.g. `256K`.
+    /// `--flavor auto` and `--compare` fall back to bit8 instead. The 16-bit flavors need about
+    /// 512K per tree, i.e. per thread of `--threads`, and bit8 about 1K, which is always allowed.
+    #[arg(long, value_parser = parse_size)]
+    memory_limit: Option<usize>,
+
+    /// When decompressing, stop with an error once the output would grow beyond this many bytes,
+    /// e.g. `1G`, as a guard against decompression bombs in untrusted input.
+    #[arg(long, value_parser = parse_size)]
+    max_output: Option<usize>,
+
+    /// Instead of compressing the files, compress each of them with every flavor without keeping
+    /// the output, and print a table of the compressed size, the ratio, and the throughput to
+    /// stdout. Other options like `--block-size` apply to each run.
+    #[arg(long)]
+    benchmark: bool,
+
+    /// Instead of compressing the files, print an analysis of each to stdout, one `key: value` per
+    /// line: the entropy of its bytes and of its 16-bit words, how many distinct ones there are,
+    /// and the flavor that `--flavor auto` would pick for all of it, or `none` if nothing would
+    /// make it smaller. Each file is read into memory as a whole.
+    #[arg(long, conflicts_with = "benchmark")]
+    recommend: bool,
+
+    /// Instead of decompressing the files, list their members like `gzip -l`, each with the
+    /// compressed and the uncompressed size, the space saved, the flavor, and the name that the
+    /// file decompresses to. Only the block format records the uncompressed size, so it's `?`
+    /// otherwise. With more than one row, a row with the totals follows. For a file with a
+    /// checkpoint index, the name is followed by the number of checkpoints.
+    #[arg(short, long, conflicts_with_all = ["benchmark", "recommend"])]
+    list: bool,
+
+    /// Compress input even if it already looks compressed, overwrite existing output files, and
+    /// write compressed data to a terminal. Without it, these are errors, like in gzip.
+    #[arg(short, long)]
+    force: bool,
+
+    /// Short for `--format raw`.
+    #[arg(long, conflicts_with = "format")]
+    no_magic: bool,
+
+    /// The same as `--format raw`, i.e. only the bitstream, exactly as the library's `compress`
+    /// writes it, but also insists on an explicit `--flavor` when compressing: The output is not
+    /// self-describing, so the flavor must be given again to decompress it.
+    #[arg(long, conflicts_with_all = ["format", "no_magic"])]
+    raw: bool,
+
+    /// How the compressed data is wrapped. `raw` and `framed` have no filemagic, so decompressing
+    /// them needs `-d` and the `--flavor` they were compressed with.
+    #[clap(value_enum)]
+    #[arg(long)]
+    format: Option<CLIFormat>,
+
+    /// Compress in the block format, with this many blocks in parallel (default: the number of
+    /// CPUs). The block format compresses slightly worse. For a given `--block-size`, its output
+    /// is the same for any number of threads. `1` keeps the single stream, unless `--block-size`
+    /// is given, too. Decompression detects the block format automatically.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Compress in the block format, with blocks of this many bytes, e.g. `256K` or `4M`. Each
+    /// block starts over with a fresh tree. `0` keeps the single stream. By default, a file is
+    /// split evenly across the threads, within 256K to 16M per block, and stdin uses 1M.
+    #[arg(long, value_parser = parse_block_size)]
+    block_size: Option<usize>,
+
+    /// Like `--block-size`, e.g. `1M`, but also append an index of where each block starts. Each
+    /// block is a checkpoint from which the library's `SeekableDecompressor` can start, so that
+    /// reading from the middle doesn't need to decompress everything before it. `--list` shows
+    /// the number of checkpoints.
+    #[arg(long, value_parser = parse_checkpoint_interval, conflicts_with = "block_size")]
+    checkpoint_interval: Option<usize>,
+
+    /// Make the compressed output depend on nothing but the input bytes and the options, e.g. for
+    /// build systems that hash it: Without `--block-size`, the block format always uses 1M blocks,
+    /// instead of a size that depends on the length of the file and the number of CPUs. Nothing
+    /// else varies to begin with: The formats store no file name or time, and the padding is
+    /// always the same.
+    #[arg(long)]
+    reproducible: bool,
+
+    /// Process this many files at the same time, each on its own thread. Independent of
+    /// `--threads`, which splits a single file.
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+
+    /// Keep the input files, instead of deleting them after a successful run.
+    #[arg(short, long)]
+    keep: bool,
+
+    /// Don't carry the modification time of the input over to the output file, which then gets
+    /// the current time. The permissions are carried over either way. The format doesn't store
+    /// the time, so this is all that gzip's `--no-name` would do.
+    #[arg(long)]
+    no_timestamp: bool,
+
+    /// Descend into directories, and (de)compress the files in them. Only regular files are
+    /// touched, symlinks and special files are skipped with a warning. Without `-d`, files that
+    /// already have the suffix are skipped, and with `-d`, files that don't have it.
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Write to stdout instead of next to the input files. Implies `--keep`. Several files are
+    /// compressed into one stream with one member per file, which `-d` expands to their
+    /// concatenation.
+    #[arg(short = 'c', long)]
+    stdout: bool,
+
+    /// Write to PATH instead of next to the input, for stdin or a single file, which is kept as
+    /// with `--keep`. The name doesn't matter, so `-d` doesn't need the suffix either. `-o -`
+    /// writes to stdout, like `-c`.
+    #[arg(short, long, value_name = "PATH", conflicts_with = "stdout")]
+    output: Option<PathBuf>,
+
+    /// Check that the input decompresses cleanly, discarding the output. Prints nothing on success.
+    /// Since there is no checksum, this only catches damage that makes the stream malformed.
+    #[arg(short, long)]
+    test: bool,
+
+    /// Print the sizes, the compression ratio, and the throughput of each file to stderr, in the
+    /// style of `gzip -v`. Twice, also prints details like the size of the blocks.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// After all files, print one line to stderr with the totals over all of them: the number of
+    /// files, the bytes read and written, their ratio, the wall time, and the throughput in MB/s
+    /// of the input. The fields are `key=value` pairs, for scripts.
+    #[arg(long)]
+    stats: bool,
+
+    /// Only print errors, but no warnings, e.g. about skipped files, and never a progress bar,
+    /// which is only shown when built with the `progress` feature. The exit code stays the same.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Suffix of compressed files, with or without the leading dot. Applies to both the name of
+    /// new files when compressing, and the suffix that is removed when decompressing.
+    #[arg(short = 'S', long, default_value = SUFFIX, value_parser = parse_suffix)]
+    suffix: String,
+
+    /// Compress with the preset dictionary from this file, as written by `jan train`. Helps a lot
+    /// with many small, similar files. Decompressing needs the same dictionary again.
+    #[arg(long)]
+    dictionary: Option<PathBuf>,
+
+    /// After compressing, write the tree as it ended up to this file, for looking into what it
+    /// adapted to. A problem with writing it is reported, but leaves the compressed output alone.
+    /// Only for a single input compressed with bit8 and a filemagic.
+    #[arg(long)]
+    dump_tree: Option<PathBuf>,
+
+    /// Format of `--dump-tree`.
     #[clap(value_enum)]
-    #[arg(short, long, default_value = "bit8")]
-    flavor: CLIFlavor,
+    #[arg(long, default_value = "binary")]
+    dump_tree_format: DumpFormat,
+
+    /// Start out with the tree from this file, as written by `--dump-tree`, instead of the uniform
+    /// one. Like `--dictionary`, which takes the same files, decompressing needs the same tree
+    /// again. Trees are only dumped for 8-bit symbols, so this only works with bit8.
+    #[arg(long, conflicts_with = "dictionary")]
+    prime_tree: Option<PathBuf>,
+
+    /// Read the files to (de)compress from LIST, one per line, or `-` for stdin. Empty lines are
+    /// ignored. Works like listing the files on the command line, e.g. for `--recursive`.
+    #[arg(short = 'T', long, value_name = "LIST", conflicts_with = "files")]
+    files_from: Option<PathBuf>,
+
+    /// Separate the files in `--files-from` by NUL instead of newlines, as with `find -print0`,
+    /// which works for any filename.
+    #[arg(long)]
+    null: bool,
+
+    /// Print the completion script for this shell to stdout, and do nothing else. For packagers,
+    /// so it's not in `--help`.
+    #[arg(long, hide = true, value_name = "SHELL")]
+    completions: Option<Shell>,
+
+    /// Print the man page to stdout as troff, and do nothing else. It's generated from the same
+    /// definitions as `--help`, so it always matches the options of this version.
+    #[arg(long, hide = true)]
+    dump_manpage: bool,
+
+    /// Compress and decompress the built-in known-answer vectors in memory, print PASS or FAIL
+    /// for each to stdout, and do nothing else. Fails if any of them does, which means that this
+    /// build can't be trusted.
+    #[arg(long)]
+    self_test: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files to (de)compress. `foo` is compressed to `foo.jan`, and `foo.jan` is decompressed to
+    /// `foo`. Without any files, reads stdin and writes stdout.
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Builds a preset dictionary for `--dictionary` from sample files, which should look like the
+    /// data that is going to be compressed.
+    Train {
+        /// Sample files. Later ones have more influence on the dictionary.
+        #[arg(required = true)]
+        samples: Vec<PathBuf>,
+
+        /// Where to write the dictionary.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Shows the format, the flavor, and the sizes of compressed files, without decompressing
+    /// them. The original size is only known for the block format, which records it.
+    Stats {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CLIFormat {
+    /// Starts with a filemagic, which records the flavor. The default.
+    Magic,
+    /// Only the bitstream, exactly as `splaycompress::compress` writes it.
+    Raw,
+    /// The bitstream, prefixed with its length as a LEB128 varint, see
+    /// `splaycompress::compress_framed`.
+    Framed,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DumpFormat {
+    /// A dictionary file, like `jan train` writes, so it also works with `--dictionary`.
+    Binary,
+    /// The DOT language of Graphviz, e.g. for `dot -Tsvg`.
+    Dot,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
 enum CLIFlavor {
+    Auto,
     Bit8,
     Bit16BE,
     Bit16LE,
+    #[value(name = "i16-be")]
+    I16BE,
+    #[value(name = "i16-le")]
+    I16LE,
+}
+
+impl Args {
+    fn flavor(&self) -> Flavor {
+        match self.flavor {
+            // `auto` is resolved separately for each input, see `sample_flavor`.
+            None | Some(CLIFlavor::Auto | CLIFlavor::Bit8) => Flavor::Symbol8,
+            Some(CLIFlavor::Bit16BE) => Flavor::Symbol16BE,
+            Some(CLIFlavor::Bit16LE) => Flavor::Symbol16LE,
+            Some(CLIFlavor::I16BE) => Flavor::SymbolI16BE,
+            Some(CLIFlavor::I16LE) => Flavor::SymbolI16LE,
+        }
+    }
+
+    /// The preset tree to start out with, from either `--dictionary` or `--prime-tree`.
+    fn preset(&self) -> Option<&Path> {
+        self.dictionary.as_deref().or(self.prime_tree.as_deref())
+    }
+
+    fn format(&self) -> CLIFormat {
+        match self.format {
+            _ if self.no_magic || self.raw => CLIFormat::Raw,
+            None => CLIFormat::Magic,
+            Some(format) => format,
+        }
+    }
+
+    /// The most detailed messages to print, from `-q` and `-v`.
+    fn level(&self) -> Level {
+        match self.verbose {
+            _ if self.quiet => Level::Error,
+            0 => Level::Warning,
+            1 => Level::Info,
+            _ => Level::Detail,
+        }
+    }
+}
+
+/// How important a message on stderr is. Errors are always printed, the rest depends on
+/// `Args::level`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Error,
+    /// Something was skipped or left out, e.g. a symlink with `--recursive`. Hidden by `-q`.
+    Warning,
+    /// The lines of `-v`, one per file.
+    Info,
+    /// More details with `-vv`.
+    Detail,
+}
+
+/// Peeks at the input to decide what to do: Input with a filemagic is decompressed even without
+/// `-d`, unless `--force` or a format without filemagic was given.
+fn detect<R: Read>(args: &Args, r: R) -> io::Result<(bool, Option<Magic>, Peeked<R>)> {
+    let (input_magic, r) = peek_magic(r)?;
+    let decompress = args.decompress
+        || (input_magic.is_some() && !args.force && args.format() == CLIFormat::Magic);
+    Ok((decompress, input_magic, r))
+}
+
+/// Like gzip, refuse to dump compressed data onto an interactive terminal, where it is useless at
+/// best. Decompressed data is fine, and `--force` overrides this.
+fn refuse_terminal(decompress_mode: bool, force: bool, stdout_is_terminal: bool) -> bool {
+    !decompress_mode && !force && stdout_is_terminal
+}
+
+/// Whether to show a progress bar for an input of `len` bytes, if known. It goes to stderr, so it
+/// only makes sense on a terminal, and several jobs would draw over each other's bars.
+fn shows_progress(quiet: bool, jobs: usize, stderr_is_terminal: bool, len: Option<u64>) -> bool {
+    !quiet && jobs == 1 && stderr_is_terminal && len.is_some_and(|len| len >= PROGRESS_MIN_LEN)
+}
+
+fn check_terminal(args: &Args, decompress_mode: bool) -> io::Result<()> {
+    if refuse_terminal(decompress_mode, args.force, stdout().is_terminal()) {
+        return Err(io::Error::other(
+            "compressed data not written to a terminal, use --force or redirect",
+        ));
+    }
+    Ok(())
 }
 
-fn main() {
-    let r = stdin().lock();
-    let w = stdout().lock();
-    let args = Args::parse();
-    let flavor = match args.flavor {
-        CLIFlavor::Bit8 => Flavor::Symbol8,
-        CLIFlavor::Bit16BE => Flavor::Symbol16BE,
-        CLIFlavor::Bit16LE => Flavor::Symbol16LE,
+/// (De)compresses `r` into `w`. `name` is the input file, if any, for messages.
+fn execute<R: Read, W: Write>(
+    args: &Args,
+    dict: Option<&Dictionary>,
+    name: Option<&Path>,
+    decompress_mode: bool,
+    input_magic: Option<Magic>,
+    r: R,
+    w: W,
+) -> io::Result<Stats> {
+    let flavor = args.flavor();
+    if decompress_mode {
+        if args.dump_tree.is_some() {
+            log(
+                args,
+                Level::Warning,
+                format_args!("jan: warning: not dumping the tree, since the input is decompressed"),
+            );
+        }
+        match (args.format(), input_magic) {
+            (CLIFormat::Raw, Some(Magic::Single(_)) | None) => {
+                decompress_with_options(flavor, r, w, &decompress_options(args.max_output))
+            }
+            (CLIFormat::Framed, Some(Magic::Single(_)) | None) => {
+                decompress_frame(args, flavor, r, w)
+            }
+            (_, Some(magic)) => {
+                if let Magic::Single(input_flavor) = magic {
+                    let explicit = !matches!(args.flavor, None | Some(CLIFlavor::Auto));
+                    if explicit && input_flavor != flavor {
+                        log(
+                            args,
+                            Level::Warning,
+                            format_args!(
+                                "jan: warning: input was compressed as {}, ignoring --flavor",
+                                input_flavor.name()
+                            ),
+                        );
+                    }
+                }
+                // The block format and dictionaries always have a filemagic.
+                decompress_detected(magic, dict, args.max_output, r, w)
+            }
+            (_, None) => Err(Error::UnknownMagic.into()),
+        }
+    } else {
+        // Only reachable with --format raw or framed, since such input is decompressed otherwise.
+        if input_magic.is_some() && !args.force {
+            return Err(io::Error::other(
+                "input already looks compressed, use --force to compress it again",
+            ));
+        }
+        let (flavor, r) = sample_flavor(args, dict, name, r)?;
+        let flavor = check_memory_limit(args, name, flavor)?;
+        warn_single_checkpoint(args, name);
+        compress_as(args, flavor, dict, name, r, w)
+    }
+}
+
+/// Whether `--memory-limit` allows compressing with `flavor`. bit8 is always allowed, since
+/// there is nothing smaller to fall back to.
+fn fits_memory_limit(args: &Args, flavor: Flavor) -> bool {
+    flavor == Flavor::Symbol8
+        || args
+            .memory_limit
+            .is_none_or(|limit| flavor.memory_usage() <= limit)
+}
+
+/// Implements `--memory-limit` before compressing with `flavor`: Fails if it doesn't fit, except
+/// that a 16-bit flavor picked by `--flavor auto` falls back to bit8.
+fn check_memory_limit(args: &Args, name: Option<&Path>, flavor: Flavor) -> io::Result<Flavor> {
+    if fits_memory_limit(args, flavor) {
+        return Ok(flavor);
+    }
+    let needed = flavor.memory_usage().div_ceil(1 << 10);
+    if args.flavor == Some(CLIFlavor::Auto) {
+        let prefix = name.map_or(String::new(), |name| format!("{}:\t", name.display()));
+        log(
+            args,
+            Level::Info,
+            format_args!(
+                "{prefix}{} would need {needed}K, more than --memory-limit, using bit8",
+                flavor.name()
+            ),
+        );
+        return Ok(Flavor::Symbol8);
+    }
+    Err(io::Error::other(format!(
+        "{} needs {needed}K of memory, more than --memory-limit allows, use --flavor bit8 or auto",
+        flavor.name()
+    )))
+}
+
+/// Warns if the file `name` is smaller than `--checkpoint-interval`, since the index is pointless
+/// with only one checkpoint. The size of stdin isn't known in advance, so it never warns.
+fn warn_single_checkpoint(args: &Args, name: Option<&Path>) {
+    let (Some(interval), Some(name)) = (args.checkpoint_interval, name) else {
+        return;
     };
-    if args.decompress {
-        decompress(flavor, r, w).unwrap()
+    if fs::metadata(name).is_ok_and(|metadata| metadata.len() < interval as u64) {
+        log(
+            args,
+            Level::Warning,
+            format_args!(
+                "jan: warning: {}: smaller than --checkpoint-interval, so there is only one checkpoint",
+                name.display()
+            ),
+        );
+    }
+}
+
+/// The input after `sample_flavor`, i.e. whatever was read ahead followed by the rest.
+type Sampled<R> = io::Chain<Spool, R>;
+
+/// Resolves `--flavor auto` by reading up to `--auto-sample` bytes from `r`, or all of it if a
+/// 16-bit flavor fits the sample but the length of a pipe is needed, and `--compare` by reading
+/// all of it. Either way, what was read is put back in front of the rest of the input.
+/// Any other flavor reads nothing.
+fn sample_flavor<R: Read>(
+    args: &Args,
+    dict: Option<&Dictionary>,
+    name: Option<&Path>,
+    mut r: R,
+) -> io::Result<(Flavor, Sampled<R>)> {
+    if args.compare {
+        let spool = Spool::read_all(&mut r, args.compare_memory)?;
+        let flavor = compare(args, dict, name, &spool)?;
+        return Ok((flavor, spool.chain(r)));
+    }
+    let mut sample = Vec::new();
+    if args.flavor != Some(CLIFlavor::Auto) {
+        return Ok((
+            args.flavor(),
+            Spool::Memory(io::Cursor::new(sample)).chain(r),
+        ));
+    }
+    (&mut r)
+        .take(args.auto_sample as u64)
+        .read_to_end(&mut sample)?;
+    let flavor = choose_flavor(&sample);
+    // A sample shorter than `--auto-sample` is all of the input, so `choose_flavor` already knew
+    // whether its length is odd.
+    if flavor == Flavor::Symbol8 || sample.len() < args.auto_sample {
+        return Ok((flavor, Spool::Memory(io::Cursor::new(sample)).chain(r)));
+    }
+    // The 16-bit flavors can't compress an odd number of bytes, so the length of the rest
+    // matters, too. For a regular file, that's known in advance, but a pipe has to be read to the
+    // end first, like for `--compare`.
+    let file_len = name
+        .and_then(|name| fs::metadata(name).ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len());
+    let (len, spool) = match file_len {
+        Some(len) => (len, Spool::Memory(io::Cursor::new(sample))),
+        None => {
+            let spool = Spool::read_all(
+                &mut io::Cursor::new(sample).chain(&mut r),
+                args.compare_memory,
+            )?;
+            (spool.len(), spool)
+        }
+    };
+    if len.is_multiple_of(2) {
+        return Ok((flavor, spool.chain(r)));
+    }
+    let prefix = name.map_or(String::new(), |name| format!("{}:\t", name.display()));
+    log(
+        args,
+        Level::Info,
+        format_args!(
+            "{prefix}{} would fit, but the input has an odd length, using bit8",
+            flavor.name()
+        ),
+    );
+    Ok((Flavor::Symbol8, spool.chain(r)))
+}
+
+/// Implements `--compare`: Compresses all of `spool` with each of the `COMPARE_FLAVORS` in
+/// parallel, without keeping the output, and returns the flavor with the smallest result.
+fn compare(
+    args: &Args,
+    dict: Option<&Dictionary>,
+    name: Option<&Path>,
+    spool: &Spool,
+) -> io::Result<Flavor> {
+    let sizes = compare_sizes(args, dict, name, spool)?;
+    let (best, _) = *sizes.iter().min_by_key(|&&(_, size)| size).unwrap();
+    if args.level() >= Level::Info {
+        let prefix = name.map_or(String::new(), |name| format!("{}:\t", name.display()));
+        let sizes: Vec<String> = sizes
+            .iter()
+            .map(|&(flavor, size)| format!("{} {size} bytes", flavor.name()))
+            .collect();
+        note(format_args!(
+            "{prefix}{}, keeping {}",
+            sizes.join(", "),
+            best.name()
+        ));
+    }
+    Ok(best)
+}
+
+/// The compressed size of `spool` with each of the `COMPARE_FLAVORS` that can compress it within
+/// `--memory-limit`, in that order.
+fn compare_sizes(
+    args: &Args,
+    dict: Option<&Dictionary>,
+    name: Option<&Path>,
+    spool: &Spool,
+) -> io::Result<Vec<(Flavor, u64)>> {
+    // The 16-bit flavors can't compress an odd number of bytes at all.
+    let candidates = if spool.len().is_multiple_of(2) {
+        &COMPARE_FLAVORS[..]
+    } else {
+        &COMPARE_FLAVORS[..1]
+    };
+    let candidates: Vec<Flavor> = candidates
+        .iter()
+        .copied()
+        .filter(|&flavor| fits_memory_limit(args, flavor))
+        .collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = candidates
+            .iter()
+            .map(|&flavor| {
+                scope.spawn(move || {
+                    let stats = match spool {
+                        Spool::Memory(data) => {
+                            let data = &data.get_ref()[..];
+                            compress_as(args, flavor, dict, name, data, io::sink())?
+                        }
+                        Spool::File(_, path) => {
+                            let r = BufReader::new(File::open(path)?);
+                            compress_as(args, flavor, dict, name, r, io::sink())?
+                        }
+                    };
+                    Ok::<_, io::Error>((flavor, stats.bytes_out))
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("compressing thread panicked"))
+            .collect::<io::Result<Vec<_>>>()
+    })
+}
+
+/// Distinguishes the temporary files of `Spool`, since `--jobs` may need several at once.
+static SPOOL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Input that was read ahead, see `sample_flavor`. Reading from it starts over at the beginning.
+enum Spool {
+    Memory(io::Cursor<Vec<u8>>),
+    /// Spilled to a temporary file, which is removed again on drop.
+    File(BufReader<File>, PathBuf),
+}
+
+impl Spool {
+    /// Reads all of `r`, holding up to `memory` bytes in memory, or spilling all of it to a
+    /// temporary file if there is more.
+    fn read_all<R: Read>(r: &mut R, memory: usize) -> io::Result<Spool> {
+        let mut data = Vec::new();
+        r.take(memory as u64).read_to_end(&mut data)?;
+        let mut next = [0];
+        if r.read(&mut next)? == 0 {
+            return Ok(Spool::Memory(io::Cursor::new(data)));
+        }
+        let path = env::temp_dir().join(format!(
+            "jan-spool-{}-{}.tmp",
+            std::process::id(),
+            SPOOL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // From here on, dropping the spool removes the file again, also on errors.
+        let spool = Spool::File(BufReader::new(File::open(&path)?), path);
+        let mut w = BufWriter::new(file);
+        w.write_all(&data)?;
+        w.write_all(&next)?;
+        io::copy(r, &mut w)?;
+        w.flush()?;
+        Ok(spool)
+    }
+
+    fn len(&self) -> u64 {
+        match self {
+            Spool::Memory(data) => data.get_ref().len() as u64,
+            Spool::File(file, _) => file.get_ref().metadata().map_or(0, |m| m.len()),
+        }
+    }
+}
+
+impl Read for Spool {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Spool::Memory(data) => data.read(buf),
+            Spool::File(file, _) => file.read(buf),
+        }
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if let Spool::File(_, path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Picks the block size if there is no `--block-size`: The input is split evenly across the
+/// threads, so that each of them has something to do, but into blocks as large as possible, since
+/// each block starts over with a fresh tree. That's `ceil(len / threads)`, rounded up to a
+/// multiple of 4K, and clamped to `AUTO_BLOCK_SIZES`, so that tiny blocks don't ruin the ratio,
+/// and huge ones don't take up too much memory. If the length is unknown, e.g. for stdin, this is
+/// the default of the library.
+fn auto_block_size(input_len: Option<u64>, threads: usize) -> usize {
+    let Some(len) = input_len else {
+        return BlockOptions::default().block_size;
+    };
+    let per_thread = len.div_ceil(threads.max(1) as u64);
+    let per_thread = per_thread
+        .checked_next_multiple_of(4 << 10)
+        .unwrap_or(u64::MAX);
+    let per_thread = usize::try_from(per_thread).unwrap_or(usize::MAX);
+    per_thread.clamp(*AUTO_BLOCK_SIZES.start(), *AUTO_BLOCK_SIZES.end())
+}
+
+/// Compresses `r` as `flavor`, in the format that the options ask for. `name` is the input file,
+/// if any, whose size decides the block size, see `auto_block_size`.
+fn compress_as<R: Read, W: Write>(
+    args: &Args,
+    flavor: Flavor,
+    dict: Option<&Dictionary>,
+    name: Option<&Path>,
+    r: R,
+    w: W,
+) -> io::Result<Stats> {
+    // A single stream extends to the end of the input, so anything that is concatenated with
+    // other members must be in the block format, which knows where it ends.
+    if uses_blocks(args) {
+        let threads = args
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get));
+        let block_size = args
+            .checkpoint_interval
+            .or(args.block_size)
+            .unwrap_or_else(|| {
+                // Unknown, as for stdin, gives the same size for every input.
+                let input_len = name
+                    .filter(|_| !args.reproducible)
+                    .and_then(|name| fs::metadata(name).ok())
+                    .filter(|metadata| metadata.is_file())
+                    .map(|metadata| metadata.len());
+                auto_block_size(input_len, threads)
+            });
+        let prefix = name.map_or(String::new(), |name| format!("{}:\t", name.display()));
+        log(
+            args,
+            Level::Detail,
+            format_args!(
+                "{prefix}{}, blocks of {block_size} bytes on {threads} threads",
+                flavor.name()
+            ),
+        );
+        let options = BlockOptions {
+            block_size,
+            threads,
+            index: args.checkpoint_interval.is_some(),
+            ..BlockOptions::default()
+        };
+        compress_blocks(flavor, r, w, &options)
+    } else if let Some(path) = &args.dump_tree {
+        let (stats, tree) = compress_keep_tree(dict, r, w)?;
+        dump_tree(args, path, &tree);
+        Ok(stats)
+    } else if let Some(dict) = dict {
+        compress_with_dictionary(dict, r, w)
+    } else {
+        match args.format() {
+            CLIFormat::Magic => compress_with_magic(flavor, r, w),
+            CLIFormat::Raw => compress_with_stats(flavor, r, w),
+            CLIFormat::Framed => compress_framed(flavor, r, w),
+        }
+    }
+}
+
+/// Implements `--dump-tree`. Failing to write the dump doesn't affect the compressed output,
+/// which is complete by now, so it only gets a warning.
+fn dump_tree(args: &Args, path: &Path, tree: &Dictionary) {
+    let bytes = match args.dump_tree_format {
+        DumpFormat::Binary => tree.to_bytes(),
+        DumpFormat::Dot => tree.to_dot().into_bytes(),
+    };
+    if let Err(e) = fs::write(path, bytes) {
+        log(
+            args,
+            Level::Warning,
+            format_args!(
+                "jan: warning: cannot dump the tree to {}: {}",
+                path.display(),
+                describe(&e)
+            ),
+        );
+    }
+}
+
+/// Decompresses a single frame, see `--format framed`. Unlike in a larger container, anything
+/// after the frame is an error here, since it would be silently lost otherwise.
+fn decompress_frame<R: Read, W: Write>(
+    args: &Args,
+    flavor: Flavor,
+    mut r: R,
+    w: W,
+) -> io::Result<Stats> {
+    let options = decompress_options(args.max_output);
+    let stats = decompress_framed(flavor, &mut r, w, &options)?;
+    if r.read(&mut [0])? != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "trailing data after the frame",
+        ));
+    }
+    Ok(stats)
+}
+
+/// Whether to compress in the block format instead of a single stream.
+fn uses_blocks(args: &Args) -> bool {
+    args.checkpoint_interval.is_some()
+        || args.block_size != Some(0)
+            && (parallel(args) || args.block_size.is_some() || concatenating(args))
+}
+
+/// Whether `--threads` asks for more than one thread. `--threads 1` alone is just the single
+/// stream, which one thread compresses best.
+fn parallel(args: &Args) -> bool {
+    args.threads.is_some_and(|threads| threads > 1)
+}
+
+/// Whether several compressed files are written to stdout one after another, see `-c`.
+fn concatenating(args: &Args) -> bool {
+    args.stdout && args.files.len() > 1
+}
+
+/// Counts the bytes read, so that `--verbose` works for streams, too. This also drives the
+/// progress bar, if any.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+    progress: Option<Progress>,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            count: 0,
+            progress: None,
+        }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        if let Some(progress) = &mut self.progress {
+            progress.update(self.count);
+        }
+        Ok(n)
+    }
+}
+
+/// An input file. With the `mmap` feature, a non-empty regular file is mapped into memory, which
+/// spares the read calls. The compressor still reads the bytes through `Read`, i.e. copies them
+/// out of the map like out of any buffer. Everything else, and any file that can't be mapped, is
+/// read as a stream. Either way, the same bytes come out.
+enum Input {
+    Streamed(BufReader<File>),
+    #[cfg(feature = "mmap")]
+    Mapped(io::Cursor<memmap2::Mmap>),
+}
+
+impl Input {
+    /// `len` is the length of the file, if it is a regular file.
+    fn new(file: File, len: Option<u64>) -> Self {
+        #[cfg(feature = "mmap")]
+        if len.is_some_and(|len| len > 0) {
+            // SAFETY: The map is only ever read, and jan never writes to its inputs. Another
+            // process still could, which nothing can prevent: the output is then as garbled as
+            // when reading a file that is being written, or jan gets SIGBUS if the file shrinks.
+            if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                return Input::Mapped(io::Cursor::new(map));
+            }
+        }
+        #[cfg(not(feature = "mmap"))]
+        let _ = len;
+        Input::Streamed(BufReader::new(file))
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Streamed(r) => r.read(buf),
+            #[cfg(feature = "mmap")]
+            Input::Mapped(r) => r.read(buf),
+        }
+    }
+}
+
+/// The progress bar that is currently on stderr, or empty if there is none.
+static PROGRESS_LINE: Mutex<String> = Mutex::new(String::new());
+
+/// Prints `message` to stderr like `eprintln!`, but first moves the progress bar out of the way,
+/// if there is one, and then draws it again below the message.
+fn note(message: fmt::Arguments) {
+    let line = PROGRESS_LINE.lock().unwrap();
+    if line.is_empty() {
+        eprintln!("{message}");
+    } else {
+        // Carriage return, then erase to the end of the line.
+        eprint!("\r\x1b[K{message}\n{line}");
+    }
+}
+
+/// Prints `message` through `note` if `args` asks for messages of this `level`.
+fn log(args: &Args, level: Level, message: fmt::Arguments) {
+    if level <= args.level() {
+        note(message);
+    }
+}
+
+/// A progress bar on stderr, see `shows_progress`. It draws over itself with `\r`, and clears the
+/// line again when dropped, so that whatever is printed next starts on an empty line. Messages
+/// in the meantime must go through `note`.
+struct Progress {
+    name: String,
+    len: u64,
+    /// The percentage that is currently shown, to only redraw when it changes.
+    shown: Option<u64>,
+}
+
+impl Progress {
+    fn new(name: &Path, len: u64) -> Self {
+        Self {
+            name: name.display().to_string(),
+            len,
+            shown: None,
+        }
+    }
+
+    fn update(&mut self, count: u64) {
+        let percent = count.min(self.len) * 100 / self.len.max(1);
+        if self.shown == Some(percent) {
+            return;
+        }
+        self.shown = Some(percent);
+        let filled = percent as usize * PROGRESS_WIDTH / 100;
+        let mut line = PROGRESS_LINE.lock().unwrap();
+        *line = format!(
+            "{}: [{}{}] {percent:3}%",
+            self.name,
+            "#".repeat(filled),
+            "-".repeat(PROGRESS_WIDTH - filled)
+        );
+        eprint!("\r{line}");
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.shown.is_some() {
+            PROGRESS_LINE.lock().unwrap().clear();
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// Prints one line like `gzip -v` does, e.g. `foo:\t 61.9% -- replaced with foo.jan`, followed
+/// by the sizes and the throughput. `name` is `None` for stdin, which gzip prints without a name.
+fn report(
+    args: &Args,
+    name: Option<&Path>,
+    decompress_mode: bool,
+    bytes_in: u64,
+    bytes_out: u64,
+    elapsed: Duration,
+    outcome: &str,
+) {
+    let (plain, compressed) = if decompress_mode {
+        (bytes_out, bytes_in)
+    } else {
+        (bytes_in, bytes_out)
+    };
+    // Like gzip, show how much space is saved, which is negative for incompressible input.
+    let ratio = if plain == 0 {
+        0.0
     } else {
-        compress(flavor, r, w).unwrap()
+        100.0 * (1.0 - compressed as f64 / plain as f64)
+    };
+    let throughput = plain as f64 / elapsed.as_secs_f64().max(1e-9) / 1e6;
+    let prefix = name.map_or(String::new(), |name| format!("{}:\t", name.display()));
+    log(
+        args,
+        Level::Info,
+        format_args!(
+            "{prefix}{ratio:5.1}%{outcome} ({plain} bytes uncompressed, {compressed} bytes compressed, {throughput:.1} MB/s)"
+        ),
+    );
+}
+
+/// The sums over all files for `--stats`. Several jobs may add to it at the same time.
+#[derive(Default)]
+struct Totals {
+    files: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl Totals {
+    fn add(&self, bytes_in: u64, bytes_out: u64) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes_in, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes_out, Ordering::Relaxed);
+    }
+
+    /// Prints the line for `--stats`, e.g. `jan: stats: files=2 bytes_in=1000 bytes_out=620
+    /// ratio=0.6200 seconds=0.001000 mb_per_s=1.00`. Unlike `report`, the ratio is simply the
+    /// bytes written over the bytes read, whichever direction that was.
+    fn print(&self, elapsed: Duration) {
+        let files = self.files.load(Ordering::Relaxed);
+        let bytes_in = self.bytes_in.load(Ordering::Relaxed);
+        let bytes_out = self.bytes_out.load(Ordering::Relaxed);
+        let ratio = if bytes_in == 0 {
+            0.0
+        } else {
+            bytes_out as f64 / bytes_in as f64
+        };
+        let seconds = elapsed.as_secs_f64();
+        let throughput = bytes_in as f64 / seconds.max(1e-9) / 1e6;
+        eprintln!(
+            "jan: stats: files={files} bytes_in={bytes_in} bytes_out={bytes_out} ratio={ratio:.4} seconds={seconds:.6} mb_per_s={throughput:.2}"
+        );
+    }
+}
+
+/// Validates `--suffix`, and removes the leading dot, if any.
+fn parse_suffix(suffix: &str) -> Result<String, String> {
+    let suffix = suffix.strip_prefix('.').unwrap_or(suffix);
+    if suffix.is_empty() {
+        return Err("suffix must not be empty".to_owned());
+    }
+    if suffix.chars().any(std::path::is_separator) {
+        return Err("suffix must not contain path separators".to_owned());
+    }
+    // The suffix is handled as the extension of the file name, which can't contain dots.
+    if suffix.contains('.') {
+        return Err("suffix must be a single extension, without further dots".to_owned());
+    }
+    Ok(suffix.to_owned())
+}
+
+/// Parses `--block-size`, see `parse_size`. 0 is allowed, and means no blocks at all.
+fn parse_block_size(size: &str) -> Result<usize, String> {
+    let size = parse_size(size)?;
+    if size != 0 && !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&size) {
+        return Err(format!(
+            "must be between {}K and {}M, or 0 for no blocks",
+            MIN_BLOCK_SIZE >> 10,
+            MAX_BLOCK_SIZE >> 20
+        ));
+    }
+    Ok(size)
+}
+
+/// Parses `--checkpoint-interval`, like `parse_block_size`, except that there's no 0.
+fn parse_checkpoint_interval(size: &str) -> Result<usize, String> {
+    let size = parse_size(size)?;
+    if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&size) {
+        return Err(format!(
+            "must be positive, between {}K and {}M",
+            MIN_BLOCK_SIZE >> 10,
+            MAX_BLOCK_SIZE >> 20
+        ));
+    }
+    Ok(size)
+}
+
+/// Where the result for `input` goes: `foo` becomes `foo.jan`, and vice versa.
+fn output_path(input: &Path, decompress: bool, suffix: &str) -> Result<PathBuf, Problem> {
+    if decompress {
+        if input.extension() != Some(suffix.as_ref()) {
+            return Err(Problem::from(format!(
+                "unknown suffix, expected .{suffix} (use -c to decompress to stdout)"
+            )));
+        }
+        Ok(input.with_extension(""))
+    } else {
+        if input.extension() == Some(suffix.as_ref()) {
+            return Err(Problem {
+                level: Level::Warning,
+                message: format!("already has .{suffix} suffix, skipping"),
+            });
+        }
+        let mut name = input.as_os_str().to_owned();
+        name.push(".");
+        name.push(suffix);
+        Ok(PathBuf::from(name))
+    }
+}
+
+/// A temporary file next to `output`, so that renaming it into place is atomic.
+fn temp_path(output: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(output.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", std::process::id()));
+    output.with_file_name(name)
+}
+
+/// Moves the finished `temp` into place. Without `force`, this never replaces `output`, even if
+/// it appeared only after the check in `run_file`.
+fn persist(temp: &Path, output: &Path, force: bool) -> io::Result<()> {
+    if force {
+        return fs::rename(temp, output);
+    }
+    // Unlike a rename, a hard link fails if the target exists.
+    match fs::hard_link(temp, output) {
+        Ok(()) => fs::remove_file(temp),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(io::Error::new(
+            e.kind(),
+            format!(
+                "{} already exists, use --force to overwrite",
+                output.display()
+            ),
+        )),
+        // Not every filesystem supports hard links, so settle for the check in `run_file`.
+        Err(_) => fs::rename(temp, output),
+    }
+}
+
+/// Writes `output` through a temporary file next to it, which is only moved into place once
+/// `write` is done, so that `output` is never half-written. Existing files are only replaced with
+/// `--force`, which is checked before anything is written, and again by `persist`.
+fn write_output(
+    args: &Args,
+    output: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<Stats>,
+) -> io::Result<(Stats, File)> {
+    if !args.force && output.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already exists, use --force to overwrite",
+                output.display()
+            ),
+        ));
+    }
+    let temp = temp_path(output);
+    let w = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", temp.display(), describe(&e))))?;
+    let result = (|| {
+        let mut w = BufWriter::new(w);
+        let stats = write(&mut w)?;
+        let w = w.into_inner().map_err(|e| e.into_error())?;
+        w.sync_all()?;
+        persist(&temp, output, args.force)?;
+        Ok((stats, w))
+    })();
+    if result.is_err() {
+        // Never leave a half-written file behind, neither under the final nor the temporary name.
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Like gzip, gives `output` the permissions and the modification time of the input, unless
+/// `--no-timestamp`. This happens through the handle that wrote it, once it is in place, so it
+/// also works if the permissions make it read-only. It's not worth failing over, so a problem is
+/// only a warning, and none at all if the platform doesn't support it.
+fn keep_metadata(args: &Args, metadata: &fs::Metadata, w: &File, output: &Path) {
+    let mut result = Ok(());
+    if !args.no_timestamp {
+        result = metadata.modified().and_then(|mtime| w.set_modified(mtime));
+    }
+    // The permissions last, since they may take away the right to change the time.
+    result = result.and(w.set_permissions(metadata.permissions()));
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::Unsupported => log(
+            args,
+            Level::Warning,
+            format_args!(
+                "jan: warning: {}: cannot keep the permissions and time of the input: {}",
+                output.display(),
+                describe(&e)
+            ),
+        ),
+        _ => {}
+    }
+}
+
+/// Whether both paths refer to the same file, e.g. through a symlink.
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (fs::metadata(a), fs::metadata(b)) {
+            return a.dev() == b.dev() && a.ino() == b.ino();
+        }
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        // Can't tell, so better assume the worst.
+        _ => true,
+    }
+}
+
+/// Why `run_file` gave up on a file, which counts as a failure either way. Most problems are
+/// errors, but a file that is merely skipped only gets a warning, which `-q` hides.
+struct Problem {
+    level: Level,
+    message: String,
+}
+
+impl From<String> for Problem {
+    fn from(message: String) -> Self {
+        Problem {
+            level: Level::Error,
+            message,
+        }
+    }
+}
+
+fn run_file(
+    args: &Args,
+    dict: Option<&Dictionary>,
+    totals: &Totals,
+    input: &Path,
+) -> Result<(), Problem> {
+    let r = File::open(input).map_err(|e| describe(&e))?;
+    if args.test {
+        let mut r = CountingReader::new(BufReader::new(r));
+        let stats = verify(&mut r).map_err(|e| describe(&e))?;
+        totals.add(r.count, stats.bytes_out);
+        log(args, Level::Info, format_args!("{}:\t OK", input.display()));
+        return Ok(());
+    }
+    let start = Instant::now();
+    let metadata = r.metadata().ok();
+    let len = metadata.as_ref().filter(|m| m.is_file()).map(|m| m.len());
+    let mut r = CountingReader::new(Input::new(r, len));
+    if cfg!(feature = "progress")
+        && shows_progress(args.quiet, args.jobs, io::stderr().is_terminal(), len)
+    {
+        r.progress = len.map(|len| Progress::new(input, len));
+    }
+    let (decompress_mode, input_magic, peeked) = detect(args, &mut r).map_err(|e| describe(&e))?;
+    if args.stdout {
+        check_terminal(args, decompress_mode).map_err(|e| describe(&e))?;
+        let stats = execute(
+            args,
+            dict,
+            Some(input),
+            decompress_mode,
+            input_magic,
+            peeked,
+            stdout().lock(),
+        )
+        .map_err(|e| describe(&exit_on_broken_pipe(e)))?;
+        // Out of the way before anything else goes to stderr.
+        r.progress = None;
+        totals.add(r.count, stats.bytes_out);
+        report(
+            args,
+            Some(input),
+            decompress_mode,
+            r.count,
+            stats.bytes_out,
+            start.elapsed(),
+            "",
+        );
+        return Ok(());
+    }
+    let output = match &args.output {
+        Some(output) => output.clone(),
+        None => output_path(input, decompress_mode, &args.suffix)?,
+    };
+    let result = write_output(args, &output, |w| {
+        execute(
+            args,
+            dict,
+            Some(input),
+            decompress_mode,
+            input_magic,
+            peeked,
+            w,
+        )
+    });
+    r.progress = None;
+    let (stats, w) = result.map_err(|e| describe(&e))?;
+    if let Some(metadata) = &metadata {
+        keep_metadata(args, metadata, &w, &output);
+    }
+    let elapsed = start.elapsed();
+    totals.add(r.count, stats.bytes_out);
+    let outcome = if args.keep || args.output.is_some() {
+        format!(" -- created {}", output.display())
+    } else {
+        if same_file(input, &output) {
+            return Err(Problem::from(format!(
+                "not deleting input, it is the same file as {}",
+                output.display()
+            )));
+        }
+        fs::remove_file(input).map_err(|e| format!("cannot delete input: {}", describe(&e)))?;
+        format!(" -- replaced with {}", output.display())
+    };
+    report(
+        args,
+        Some(input),
+        decompress_mode,
+        r.count,
+        stats.bytes_out,
+        elapsed,
+        &outcome,
+    );
+    Ok(())
+}
+
+fn run_stdin(args: &Args, dict: Option<&Dictionary>, totals: &Totals) -> io::Result<()> {
+    if args.test {
+        let mut r = CountingReader::new(stdin().lock());
+        let stats = verify(&mut r)?;
+        totals.add(r.count, stats.bytes_out);
+        log(args, Level::Info, format_args!(" OK"));
+        return Ok(());
+    }
+    let start = Instant::now();
+    let mut r = CountingReader::new(stdin().lock());
+    let (decompress_mode, input_magic, peeked) = detect(args, &mut r)?;
+    let stats = if let Some(output) = &args.output {
+        let write = |w: &mut _| execute(args, dict, None, decompress_mode, input_magic, peeked, w);
+        write_output(args, output, write)?.0
+    } else {
+        check_terminal(args, decompress_mode)?;
+        execute(
+            args,
+            dict,
+            None,
+            decompress_mode,
+            input_magic,
+            peeked,
+            stdout().lock(),
+        )
+        .map_err(exit_on_broken_pipe)?
+    };
+    totals.add(r.count, stats.bytes_out);
+    let elapsed = start.elapsed();
+    report(
+        args,
+        None,
+        decompress_mode,
+        r.count,
+        stats.bytes_out,
+        elapsed,
+        "",
+    );
+    Ok(())
+}
+
+/// Reads the list of files for `--files-from`, where `-` is stdin.
+fn read_file_list(list: &Path, null: bool) -> io::Result<Vec<PathBuf>> {
+    let data = if list == Path::new("-") {
+        let mut data = Vec::new();
+        stdin().lock().read_to_end(&mut data)?;
+        data
+    } else {
+        fs::read(list)?
+    };
+    parse_file_list(&data, null)
+}
+
+/// Splits the contents of a `--files-from` list into paths, skipping empty entries.
+fn parse_file_list(data: &[u8], null: bool) -> io::Result<Vec<PathBuf>> {
+    let separator = if null { b'\0' } else { b'\n' };
+    data.split(|&byte| byte == separator)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                Ok(PathBuf::from(OsStr::from_bytes(entry)))
+            }
+            #[cfg(not(unix))]
+            {
+                std::str::from_utf8(entry).map(PathBuf::from).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "file name is not valid UTF-8")
+                })
+            }
+        })
+        .collect()
+}
+
+/// Collects the files below `dir` for `--recursive`, sorted by name so that the order doesn't
+/// depend on the filesystem. Problems are reported right away, without stopping the walk, and
+/// the return value says whether there were any.
+fn walk(args: &Args, dir: &Path, files: &mut Vec<PathBuf>) -> bool {
+    let entries = fs::read_dir(dir).and_then(|entries| {
+        entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()
+    });
+    let mut entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            log(
+                args,
+                Level::Error,
+                format_args!("jan: {}: {}", dir.display(), describe(&e)),
+            );
+            return false;
+        }
+    };
+    entries.sort();
+    let mut ok = true;
+    for path in entries {
+        // Doesn't follow symlinks, so neither loops nor files outside the tree are a concern.
+        let file_type = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata.file_type(),
+            Err(e) => {
+                log(
+                    args,
+                    Level::Error,
+                    format_args!("jan: {}: {}", path.display(), describe(&e)),
+                );
+                ok = false;
+                continue;
+            }
+        };
+        if file_type.is_dir() {
+            ok &= walk(args, &path, files);
+        } else if file_type.is_symlink() {
+            log(
+                args,
+                Level::Warning,
+                format_args!(
+                    "jan: warning: {}: is a symbolic link, skipping",
+                    path.display()
+                ),
+            );
+        } else if !file_type.is_file() {
+            log(
+                args,
+                Level::Warning,
+                format_args!(
+                    "jan: warning: {}: not a regular file, skipping",
+                    path.display()
+                ),
+            );
+        } else if (path.extension() == Some(args.suffix.as_ref())) == args.decompress {
+            files.push(path);
+        }
+    }
+    ok
+}
+
+/// Rejects combinations of options that can't work together.
+fn check_args(args: &Args) -> Result<(), &'static str> {
+    if args.jobs == 0 {
+        return Err("--jobs must be at least 1");
+    }
+    if args.threads == Some(0) {
+        return Err("--threads must be at least 1");
+    }
+    if args.null && args.files_from.is_none() {
+        return Err("--null only applies to the list of --files-from");
+    }
+    if args.raw && matches!(args.flavor, None | Some(CLIFlavor::Auto)) {
+        return Err("--raw needs an explicit --flavor, since the output doesn't record it");
+    }
+    if args.files.len() > 1 && args.files.iter().any(|path| path == Path::new("-")) {
+        return Err("- stands for stdin, which cannot be mixed with files");
+    }
+    if args.output.is_some() {
+        if args.files.len() > 1 {
+            return Err("-o names a single output, so it cannot be used with several files");
+        }
+        if args.test || args.list || args.benchmark || args.compare_only || args.recommend {
+            return Err("-o cannot be used with -t, -l, --benchmark, --compare-only, or --recommend, which write no output");
+        }
+    }
+    if args.stdout && args.jobs > 1 {
+        return Err("--jobs cannot be used with -c, since the output must be written in order");
+    }
+    if args.block_size == Some(0) && !args.decompress {
+        if parallel(args) {
+            return Err("--threads needs the block format, which --block-size 0 disables");
+        }
+        if concatenating(args) {
+            return Err(
+                "several files with -c need the block format, which --block-size 0 disables",
+            );
+        }
+    }
+    let has_magic = args.format() == CLIFormat::Magic;
+    let auto = args.flavor == Some(CLIFlavor::Auto);
+    if args.decompress && !has_magic && (args.flavor.is_none() || auto) {
+        return Err("-d with --no-magic or --format raw|framed needs --flavor, since the input doesn't record it");
+    }
+    if auto && !has_magic && !args.decompress {
+        return Err("--flavor auto needs the filemagic to record its choice, so it cannot be used with --no-magic or --format raw|framed");
+    }
+    if auto && args.dictionary.is_some() && !args.decompress {
+        return Err("--flavor auto cannot be used with --dictionary, which only supports bit8");
+    }
+    if args.compare && !args.decompress {
+        if !has_magic {
+            return Err("--compare needs the filemagic to record its choice, so it cannot be used with --no-magic or --format raw|framed");
+        }
+        if args.dictionary.is_some() {
+            return Err("--compare cannot be used with --dictionary, which only supports bit8");
+        }
+    }
+    if concatenating(args) && !has_magic && !args.decompress {
+        return Err("--no-magic and --format raw|framed cannot compress several files to stdout, since the members could not be told apart");
+    }
+    if parallel(args) && !has_magic && !args.decompress {
+        return Err("the block format always has a filemagic, so --threads cannot be used with --no-magic or --format raw|framed");
+    }
+    if args.block_size.is_some_and(|size| size != 0) && !has_magic && !args.decompress {
+        return Err("the block format always has a filemagic, so --block-size cannot be used with --no-magic or --format raw|framed");
+    }
+    if args.checkpoint_interval.is_some() && !has_magic && !args.decompress {
+        return Err("the block format always has a filemagic, so --checkpoint-interval cannot be used with --no-magic or --format raw|framed");
+    }
+    if args.benchmark {
+        if args.decompress || args.test || args.stdout {
+            return Err("--benchmark only compresses, and writes no output, so it cannot be used with -d, -t, or -c");
+        }
+        if args.flavor.is_some() || args.compare || args.preset().is_some() {
+            return Err("--benchmark tries every flavor, so it cannot be used with --flavor, --compare, --dictionary, or --prime-tree");
+        }
+        if args.files.is_empty() {
+            return Err("--benchmark needs files, since it reads each of them once per flavor");
+        }
+    }
+    if args.compare_only {
+        if args.decompress || args.test || args.stdout {
+            return Err("--compare-only only compresses, and writes no output, so it cannot be used with -d, -t, or -c");
+        }
+        if args.preset().is_some() {
+            return Err("--compare-only cannot be used with --dictionary or --prime-tree, which only support bit8");
+        }
+    }
+    if args.recommend {
+        if args.decompress || args.test || args.stdout {
+            return Err("--recommend only analyzes, and writes no output, so it cannot be used with -d, -t, or -c");
+        }
+        if args.flavor.is_some() || args.compare {
+            return Err(
+                "--recommend picks the flavor, so it cannot be used with --flavor or --compare",
+            );
+        }
+        if args.files.is_empty() {
+            return Err("--recommend needs files");
+        }
+    }
+    if args.list {
+        if args.test || args.stdout {
+            return Err("--list only reads the files, so it cannot be used with -t or -c");
+        }
+        if args.files.is_empty() {
+            return Err("--list needs files");
+        }
+    }
+    if args.dump_tree.is_some() {
+        if args.decompress || args.test || args.benchmark {
+            return Err("--dump-tree only works when compressing, so it cannot be used with -d, -t, or --benchmark");
+        }
+        if args.files.len() > 1 {
+            return Err(
+                "--dump-tree writes a single tree, so it cannot be used with several files",
+            );
+        }
+        if !has_magic || uses_blocks(args) {
+            return Err("--dump-tree needs a single stream with a filemagic, so it cannot be used with --no-magic, --format raw|framed, --threads, or --block-size");
+        }
+        if !matches!(args.flavor, None | Some(CLIFlavor::Bit8)) || args.compare {
+            return Err("--dump-tree only supports --flavor bit8");
+        }
+    }
+    if args.dictionary.is_some() && !args.decompress {
+        if !has_magic {
+            return Err(
+                "--dictionary needs the filemagic, it cannot be used with --no-magic or --format raw|framed",
+            );
+        }
+        if uses_blocks(args) {
+            return Err("--dictionary does not support the block format, so neither --threads, --block-size, nor several files with -c");
+        }
+        if args.flavor() != Flavor::Symbol8 {
+            return Err("--dictionary only supports --flavor bit8");
+        }
+    }
+    if args.prime_tree.is_some() {
+        let other_flavor = match args.flavor {
+            None | Some(CLIFlavor::Bit8) => args.compare && !args.decompress,
+            // Decompression takes the flavor from the filemagic anyway.
+            Some(CLIFlavor::Auto) => !args.decompress,
+            Some(_) => true,
+        };
+        if other_flavor {
+            return Err("--prime-tree takes a tree of 8-bit symbols, like --dump-tree writes, so it only works with --flavor bit8");
+        }
+        if !args.decompress && !has_magic {
+            return Err(
+                "--prime-tree needs the filemagic, it cannot be used with --no-magic or --format raw|framed",
+            );
+        }
+        if !args.decompress && uses_blocks(args) {
+            return Err("--prime-tree does not support the block format, so neither --threads, --block-size, nor several files with -c");
+        }
+    }
+    Ok(())
+}
+
+/// Implements `jan train`.
+fn train(args: &Args, samples: &[PathBuf], output: &Path) -> Result<(), String> {
+    let samples = samples
+        .iter()
+        .map(|path| fs::read(path).map_err(|e| format!("{}: {}", path.display(), describe(&e))))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dict = Dictionary::train(samples.iter().map(Vec::as_slice));
+    if !args.force && output.exists() {
+        return Err(format!(
+            "{} already exists, use --force to overwrite",
+            output.display()
+        ));
+    }
+    fs::write(output, dict.to_bytes())
+        .map_err(|e| format!("{}: {}", output.display(), describe(&e)))
+}
+
+/// One line of `jan stats`, e.g. `foo.jan: block format, bit8, 3 blocks, 1234 bytes compressed,
+/// 5678 bytes uncompressed, 78.3% saved`.
+fn describe_summary(name: &Path, summary: &Summary) -> String {
+    let name = name.display();
+    let format = match summary.magic {
+        None => return format!("{name}: unknown format, {} bytes", summary.compressed_bytes),
+        Some(Magic::Single(_)) => "single stream",
+        Some(Magic::Blocks) => "block format",
+        Some(Magic::Dictionary) => "preset dictionary",
+    };
+    let mut line = format!("{name}: {format}");
+    if let Some(flavor) = summary.flavor {
+        line += &format!(", {}", flavor.name());
+    }
+    if summary.members > 1 {
+        line += &format!(", {} members", summary.members);
+    }
+    if summary.magic == Some(Magic::Blocks) {
+        line += &format!(", {} blocks", summary.blocks);
+    }
+    line += &format!(", {} bytes compressed", summary.compressed_bytes);
+    match summary.original_bytes {
+        Some(original) => {
+            // Like gzip, show how much space is saved, see `report`.
+            let ratio = if original == 0 {
+                0.0
+            } else {
+                100.0 * (1.0 - summary.compressed_bytes as f64 / original as f64)
+            };
+            line += &format!(", {original} bytes uncompressed, {ratio:.1}% saved");
+        }
+        None => line += ", uncompressed size unknown",
+    }
+    line
+}
+
+/// Implements `jan stats`. Like `-r`, a failure on one file doesn't stop the others.
+fn stats(files: &[PathBuf]) -> Result<(), Failure> {
+    let mut result = Ok(());
+    for path in files {
+        match File::open(path).and_then(|file| inspect(BufReader::new(file))) {
+            Ok(summary) => println!("{}", describe_summary(path, &summary)),
+            Err(e) => {
+                eprintln!("jan: {}: {}", path.display(), describe(&e));
+                result = Err(Failure::Error);
+            }
+        }
+    }
+    result
+}
+
+/// Implements `--list`, which reports problems with a file without stopping. Prints a table like
+/// this, where the ratio is the space saved, like in `report`:
+///
+/// ```text
+///   compressed uncompressed  ratio flavor    name
+///          816         1000  18.4% bit8      foo
+///          805            ?      ? bit16-le  foo
+///         1621            ?      ?           (totals)
+/// ```
+///
+/// The format stores neither the original name nor the time, so the name is always derived from
+/// the name of the file, like `-d` would.
+fn list(args: &Args) -> Result<(), Failure> {
+    let mut result = Ok(());
+    let mut rows = 0;
+    let mut totals = Member {
+        original_bytes: Some(0),
+        ..Member::default()
+    };
+    println!(
+        "{:>12} {:>12} {:>6} {:<9} name",
+        "compressed", "uncompressed", "ratio", "flavor"
+    );
+    for path in &args.files {
+        let members = File::open(path).and_then(|file| inspect_members(BufReader::new(file)));
+        let members = match members {
+            Ok(members) if members[0].magic.is_none() => Err(Error::UnknownMagic.into()),
+            members => members,
+        };
+        let members = match members {
+            Ok(members) => members,
+            Err(e) => {
+                log(
+                    args,
+                    Level::Error,
+                    format_args!("jan: {}: {}", path.display(), describe(&e)),
+                );
+                result = Err(Failure::Error);
+                continue;
+            }
+        };
+        let name = output_path(path, true, &args.suffix).unwrap_or_else(|_| path.clone());
+        for member in &members {
+            let flavor = member.flavor.map_or("?", Flavor::name);
+            let name = match member.checkpoints {
+                Some(1) => format!("{} (1 checkpoint)", name.display()),
+                Some(checkpoints) => format!("{} ({checkpoints} checkpoints)", name.display()),
+                None => name.display().to_string(),
+            };
+            println!("{}", list_row(member, flavor, &name));
+            totals.compressed_bytes += member.compressed_bytes;
+            totals.original_bytes = totals
+                .original_bytes
+                .zip(member.original_bytes)
+                .map(|(a, b)| a + b);
+            rows += 1;
+        }
+    }
+    if rows > 1 {
+        println!("{}", list_row(&totals, "", "(totals)"));
+    }
+    result
+}
+
+/// One row of `--list`.
+fn list_row(member: &Member, flavor: &str, name: &str) -> String {
+    let (original, ratio) = match member.original_bytes {
+        Some(original) => {
+            let ratio = if original == 0 {
+                0.0
+            } else {
+                100.0 * (1.0 - member.compressed_bytes as f64 / original as f64)
+            };
+            (original.to_string(), format!("{ratio:.1}%"))
+        }
+        None => ("?".to_owned(), "?".to_owned()),
+    };
+    format!(
+        "{:>12} {original:>12} {ratio:>6} {flavor:<9} {name}",
+        member.compressed_bytes
+    )
+}
+
+/// Implements `--benchmark`, which reports problems with a file without stopping.
+fn benchmark(args: &Args) -> Result<(), Failure> {
+    let mut result = Ok(());
+    for path in &args.files {
+        if let Err(e) = benchmark_file(args, path) {
+            log(
+                args,
+                Level::Error,
+                format_args!("jan: {}: {}", path.display(), describe(&e)),
+            );
+            result = Err(Failure::Error);
+        }
+    }
+    result
+}
+
+/// Prints a table like this, where the ratio is the compressed over the original size, and the
+/// throughput is in MB/s of the original:
+///
+/// ```text
+/// foo:
+/// flavor    compressed   ratio    MB/s
+/// bit8            6190  0.6190   12.34
+/// ```
+///
+/// Each flavor reads the file again, so the output can go straight into a sink. The 16-bit
+/// flavors can't compress an odd number of bytes at all, so they get a row of dashes then.
+fn benchmark_file(args: &Args, path: &Path) -> io::Result<()> {
+    let len = fs::metadata(path)?.len();
+    let mut rows = Vec::new();
+    for flavor in Flavor::ALL {
+        let name = flavor.name();
+        if flavor != Flavor::Symbol8 && !len.is_multiple_of(2) {
+            rows.push(format!("{name:<9} {:>10} {:>7} {:>7}", "-", "-", "-"));
+            continue;
+        }
+        let start = Instant::now();
+        let r = BufReader::new(File::open(path)?);
+        let stats = compress_as(args, flavor, None, Some(path), r, io::sink())?;
+        let seconds = start.elapsed().as_secs_f64();
+        let ratio = if len == 0 {
+            0.0
+        } else {
+            stats.bytes_out as f64 / len as f64
+        };
+        let throughput = len as f64 / seconds.max(1e-9) / 1e6;
+        rows.push(format!(
+            "{name:<9} {:>10} {ratio:>7.4} {throughput:>7.2}",
+            stats.bytes_out
+        ));
+    }
+    // All at once, so that the table of one file is never interrupted by an error.
+    println!(
+        "{}:\n{:<9} {:>10} {:>7} {:>7}\n{}",
+        path.display(),
+        "flavor",
+        "compressed",
+        "ratio",
+        "MB/s",
+        rows.join("\n")
+    );
+    Ok(())
+}
+
+/// Implements `--compare-only` for stdin, or for each of the files, which reports problems with a
+/// file without stopping.
+fn compare_only(args: &Args) -> Result<(), Failure> {
+    if args.files.is_empty() {
+        return compare_only_input(args, None, stdin().lock()).map_err(|e| {
+            log(args, Level::Error, format_args!("jan: {}", describe(&e)));
+            Failure::Error
+        });
+    }
+    let mut result = Ok(());
+    for path in &args.files {
+        let ranked = File::open(path)
+            .and_then(|file| compare_only_input(args, Some(path), BufReader::new(file)));
+        if let Err(e) = ranked {
+            log(
+                args,
+                Level::Error,
+                format_args!("jan: {}: {}", path.display(), describe(&e)),
+            );
+            result = Err(Failure::Error);
+        }
+    }
+    result
+}
+
+/// Prints a table like this to stderr, smallest first, where the ratio is the compressed over the
+/// original size:
+///
+/// ```text
+/// foo:
+/// flavor    compressed   ratio
+/// bit8            6190  0.6190
+/// bit16-le        6342  0.6342
+/// bit16-be        6401  0.6401
+/// ```
+///
+/// The 16-bit flavors can't compress an odd number of bytes at all, so they come last with
+/// `n/a (odd length)` then. Stdin has no name, so it gets no first line.
+fn compare_only_input<R: Read>(args: &Args, name: Option<&Path>, mut r: R) -> io::Result<()> {
+    let spool = Spool::read_all(&mut r, args.compare_memory)?;
+    let len = spool.len();
+    let mut sizes = compare_sizes(args, None, name, &spool)?;
+    // Stable, so that ties stay in the order of preference.
+    sizes.sort_by_key(|&(_, size)| size);
+    let mut rows = Vec::new();
+    if let Some(name) = name {
+        rows.push(format!("{}:", name.display()));
+    }
+    rows.push(format!(
+        "{:<9} {:>10} {:>7}",
+        "flavor", "compressed", "ratio"
+    ));
+    for (flavor, size) in &sizes {
+        let ratio = if len == 0 {
+            0.0
+        } else {
+            *size as f64 / len as f64
+        };
+        rows.push(format!("{:<9} {size:>10} {ratio:>7.4}", flavor.name()));
+    }
+    for flavor in COMPARE_FLAVORS {
+        if !sizes.iter().any(|&(candidate, _)| candidate == flavor) {
+            let reason = if fits_memory_limit(args, flavor) {
+                "odd length"
+            } else {
+                "over --memory-limit"
+            };
+            rows.push(format!("{:<9} n/a ({reason})", flavor.name()));
+        }
+    }
+    // All at once, so that the tables of several files never interleave with an error.
+    note(format_args!("{}", rows.join("\n")));
+    Ok(())
+}
+
+/// Implements `--recommend`, which reports problems with a file without stopping.
+fn recommend(args: &Args) -> Result<(), Failure> {
+    let mut result = Ok(());
+    for path in &args.files {
+        match fs::read(path) {
+            Ok(data) => println!("{}", analyze(path, &data)),
+            Err(e) => {
+                log(
+                    args,
+                    Level::Error,
+                    format_args!("jan: {}: {}", path.display(), describe(&e)),
+                );
+                result = Err(Failure::Error);
+            }
+        }
+    }
+    result
+}
+
+/// The analysis of `--recommend`, e.g. for a short text:
+///
+/// ```text
+/// file: foo
+/// bytes: 1000
+/// distinct_bytes: 53
+/// byte_entropy: 4.7265
+/// distinct_words: 218
+/// word_entropy: 7.3822
+/// recommended_flavor: bit8
+/// ```
+///
+/// The entropy is in bits per byte and per 16-bit word, respectively. Words are counted big-endian,
+/// which doesn't matter for either number, and a trailing odd byte is ignored.
+fn analyze(path: &Path, data: &[u8]) -> String {
+    let bytes = histogram8(data);
+    let words: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    let words = histogram16(&words);
+    let distinct = |counts: &[u64]| counts.iter().filter(|&&count| count > 0).count();
+    let flavor = choose_flavor(data);
+    let recommended = if estimate_compressed_size(flavor, data) < data.len() {
+        flavor.name()
+    } else {
+        "none"
+    };
+    format!(
+        "file: {}\nbytes: {}\ndistinct_bytes: {}\nbyte_entropy: {:.4}\ndistinct_words: {}\nword_entropy: {:.4}\nrecommended_flavor: {recommended}",
+        path.display(),
+        data.len(),
+        distinct(&bytes),
+        entropy(&bytes),
+        distinct(&words),
+        entropy(&words),
+    )
+}
+
+/// Whether `argv0` is one of the `DECOMPRESSOR_NAMES`, e.g. `/usr/bin/unjan` or `unjan.exe`.
+fn invoked_as_decompressor(argv0: Option<&OsStr>) -> bool {
+    argv0
+        .and_then(|argv0| Path::new(argv0).file_stem())
+        .is_some_and(|stem| DECOMPRESSOR_NAMES.iter().any(|name| stem == *name))
+}
+
+/// Why `jan` failed, which decides the exit code. Like gzip, 1 means that something went wrong,
+/// and 2 that the options were wrong, which is also what clap uses.
+#[derive(Debug)]
+enum Failure {
+    /// At least one input failed, and has already been reported.
+    Error,
+    /// The options don't work together.
+    Usage(&'static str),
+}
+
+impl Failure {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Failure::Error => ExitCode::from(1),
+            Failure::Usage(_) => ExitCode::from(2),
+        }
+    }
+}
+
+/// Implements `--self-test`.
+fn self_test() -> Result<(), Failure> {
+    let mut result = Ok(());
+    for vector in KNOWN_ANSWERS {
+        if vector.check() {
+            println!("PASS {}", vector.name);
+        } else {
+            println!("FAIL {}", vector.name);
+            result = Err(Failure::Error);
+        }
+    }
+    result
+}
+
+fn run(mut args: Args) -> Result<(), Failure> {
+    if let Some(shell) = args.completions {
+        clap_complete::generate(shell, &mut Args::command(), "jan", &mut stdout());
+        return Ok(());
+    }
+    if args.dump_manpage {
+        return clap_mangen::Man::new(Args::command().name("jan"))
+            .render(&mut stdout())
+            .map_err(exit_on_broken_pipe)
+            .map_err(|e| {
+                log(&args, Level::Error, format_args!("jan: {}", describe(&e)));
+                Failure::Error
+            });
+    }
+    if args.self_test {
+        return self_test();
+    }
+    match &args.command {
+        Some(Command::Train { samples, output }) => {
+            return train(&args, samples, output).map_err(|e| {
+                log(&args, Level::Error, format_args!("jan: {e}"));
+                Failure::Error
+            });
+        }
+        Some(Command::Stats { files }) => return stats(files),
+        None => {}
+    }
+    if let Some(list) = &args.files_from {
+        args.files = read_file_list(list, args.null).map_err(|e| {
+            log(
+                &args,
+                Level::Error,
+                format_args!("jan: {}: {}", list.display(), describe(&e)),
+            );
+            Failure::Error
+        })?;
+        if args.files.is_empty() {
+            // An empty list means no files, not stdin.
+            return Ok(());
+        }
+    }
+    // Like for most tools, `-` is stdin as the input, and stdout as the output, see below.
+    if args.files == [Path::new("-")] {
+        args.files.clear();
+    }
+    let mut result = Ok(());
+    if args.recursive && !args.files.is_empty() {
+        let mut files = Vec::new();
+        for path in &args.files {
+            if path.is_dir() {
+                if !walk(&args, path, &mut files) {
+                    result = Err(Failure::Error);
+                }
+            } else {
+                files.push(path.clone());
+            }
+        }
+        if files.is_empty() {
+            // Nothing to do, which must not fall back to stdin.
+            return result;
+        }
+        args.files = files;
+    }
+    // `--level` is merely a shorthand, see there. Decompression takes the flavor from the
+    // filemagic anyway.
+    match args.compression_level {
+        _ if args.decompress => {}
+        Some(1) => args.flavor = Some(CLIFlavor::Bit8),
+        Some(9) => args.compare = true,
+        Some(_) => args.flavor = Some(CLIFlavor::Auto),
+        None => {}
+    }
+    check_args(&args).map_err(Failure::Usage)?;
+    if args.output.as_deref() == Some(Path::new("-")) {
+        args.output = None;
+        args.stdout = true;
+    }
+    if args.benchmark {
+        return benchmark(&args);
+    }
+    if args.compare_only {
+        return compare_only(&args);
+    }
+    if args.recommend {
+        return recommend(&args);
+    }
+    if args.list {
+        return list(&args);
+    }
+    let dict = match args.preset() {
+        None => None,
+        Some(path) => Some(load_dictionary(path).map_err(|e| {
+            log(
+                &args,
+                Level::Error,
+                format_args!("jan: {}: {}", path.display(), describe(&e)),
+            );
+            Failure::Error
+        })?),
+    };
+    let dict = dict.as_ref();
+    let start = Instant::now();
+    let totals = Totals::default();
+    if args.files.is_empty() {
+        if let Err(e) = run_stdin(&args, dict, &totals) {
+            log(&args, Level::Error, format_args!("jan: {}", describe(&e)));
+            result = Err(Failure::Error);
+        }
+    } else if !run_files(&args, dict, &totals) {
+        result = Err(Failure::Error);
+    }
+    if args.stats {
+        totals.print(start.elapsed());
+    }
+    result
+}
+
+/// Runs `run_file` on each of `args.files`, up to `--jobs` of them at the same time, and returns
+/// whether all of them succeeded. Each message goes to stderr with a single `eprintln!` in
+/// `note`, which holds the lock on stderr for the whole line, so the lines of different jobs never
+/// mix.
+fn run_files(args: &Args, dict: Option<&Dictionary>, totals: &Totals) -> bool {
+    let next = AtomicUsize::new(0);
+    let ok = AtomicBool::new(true);
+    thread::scope(|s| {
+        for _ in 0..args.jobs.min(args.files.len()) {
+            s.spawn(|| {
+                while let Some(input) = args.files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(problem) = run_file(args, dict, totals, input) {
+                        let kind = match problem.level {
+                            Level::Error => "",
+                            _ => "warning: ",
+                        };
+                        log(
+                            args,
+                            problem.level,
+                            format_args!("jan: {kind}{}: {}", input.display(), problem.message),
+                        );
+                        ok.store(false, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    ok.into_inner()
+}
+
+fn main() -> ExitCode {
+    let invoked_as_decompressor = invoked_as_decompressor(std::env::args_os().next().as_deref());
+    let mut args = Args::parse();
+    if invoked_as_decompressor && !args.compress {
+        args.decompress = true;
+    }
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            if let Failure::Usage(message) = &failure {
+                eprintln!("jan: {message}");
+            }
+            failure.exit_code()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_suffix() {
+        assert_eq!(parse_suffix("spc").unwrap(), "spc");
+        assert_eq!(parse_suffix(".sz").unwrap(), "sz");
+        assert!(parse_suffix("").is_err());
+        assert!(parse_suffix(".").is_err());
+        assert!(parse_suffix("a/b").is_err());
+        assert!(parse_suffix("tar.jan").is_err());
+    }
+
+    #[test]
+    fn test_parse_block_size() {
+        assert_eq!(parse_block_size("0").unwrap(), 0);
+        assert_eq!(parse_block_size("65536").unwrap(), 65536);
+        assert_eq!(parse_block_size("256K").unwrap(), 256 << 10);
+        assert_eq!(parse_block_size("256k").unwrap(), 256 << 10);
+        assert_eq!(parse_block_size("4M").unwrap(), 4 << 20);
+        assert_eq!(parse_block_size("1K").unwrap(), MIN_BLOCK_SIZE);
+        assert_eq!(parse_block_size("64M").unwrap(), MAX_BLOCK_SIZE);
+        for invalid in ["", "K", "-1", "1.5M", "4MB", "4 M", "0x100"] {
+            let e = parse_block_size(invalid).unwrap_err();
+            assert!(e.starts_with("invalid size"), "{invalid}: {e}");
+        }
+        for out_of_range in ["1", "1023", "65M", "1G", "99999999999G"] {
+            assert!(parse_block_size(out_of_range).is_err(), "{out_of_range}");
+        }
+    }
+
+    #[test]
+    fn test_auto_block_size() {
+        let blocks = |len: u64, threads| len.div_ceil(auto_block_size(Some(len), threads) as u64);
+        // One block per thread, as long as that is within bounds.
+        assert_eq!(blocks(3 << 20, 4), 4);
+        assert_eq!(blocks((3 << 20) + 1, 4), 4);
+        assert_eq!(blocks(100 << 20, 8), 8);
+        assert_eq!(blocks(10 << 20, 1), 1);
+        // Small inputs get fewer blocks than threads rather than tiny blocks.
+        assert_eq!(blocks(300 << 10, 16), 2);
+        assert_eq!(blocks(1, 16), 1);
+        assert_eq!(auto_block_size(Some(0), 4), 256 << 10);
+        // Huge inputs get more blocks than threads rather than huge blocks.
+        assert_eq!(blocks(1 << 30, 8), 64);
+        assert_eq!(auto_block_size(Some(u64::MAX), 1), 16 << 20);
+        // Always a multiple of 4K, so even for the 16-bit flavors.
+        assert_eq!(auto_block_size(Some(1_000_001), 3), 335872);
+        assert_eq!(auto_block_size(None, 4), BlockOptions::default().block_size);
+        assert_eq!(auto_block_size(Some(100 << 20), 0), 16 << 20);
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        let paths = |data: &[u8], null| parse_file_list(data, null).unwrap();
+        assert_eq!(
+            paths(b"a\nb c\n\nd/e", false),
+            [Path::new("a"), "b c".as_ref(), "d/e".as_ref()]
+        );
+        assert_eq!(
+            paths(b"a\nb\0c\0\0", true),
+            [Path::new("a\nb"), "c".as_ref()]
+        );
+        assert!(paths(b"", false).is_empty());
+        assert!(paths(b"\0", true).is_empty());
+    }
+
+    #[test]
+    fn test_level() {
+        let level = |args: &[&str]| Args::try_parse_from(args).unwrap().level();
+        assert_eq!(level(&["jan"]), Level::Warning);
+        assert_eq!(level(&["jan", "-q"]), Level::Error);
+        assert_eq!(level(&["jan", "-v"]), Level::Info);
+        assert_eq!(level(&["jan", "-vv"]), Level::Detail);
+        assert_eq!(level(&["jan", "-v", "-v", "-v"]), Level::Detail);
+        assert!(Args::try_parse_from(["jan", "-q", "-v"]).is_err());
+    }
+
+    #[test]
+    fn test_refuse_terminal() {
+        assert!(refuse_terminal(false, false, true));
+        // Decompressed data, --force, and anything that isn't a terminal are fine.
+        assert!(!refuse_terminal(true, false, true));
+        assert!(!refuse_terminal(false, true, true));
+        assert!(!refuse_terminal(false, false, false));
+        assert!(!refuse_terminal(true, true, false));
+    }
+
+    #[test]
+    fn test_shows_progress() {
+        let large = Some(PROGRESS_MIN_LEN);
+        assert!(shows_progress(false, 1, true, large));
+        assert!(shows_progress(false, 1, true, Some(u64::MAX)));
+        // Not with --quiet, several jobs, or anything that isn't a terminal.
+        assert!(!shows_progress(true, 1, true, large));
+        assert!(!shows_progress(false, 2, true, large));
+        assert!(!shows_progress(false, 1, false, large));
+        assert!(!shows_progress(true, 2, false, large));
+        // Nor for small inputs, or when the size is unknown, e.g. for a pipe.
+        assert!(!shows_progress(false, 1, true, Some(PROGRESS_MIN_LEN - 1)));
+        assert!(!shows_progress(false, 1, true, Some(0)));
+        assert!(!shows_progress(false, 1, true, None));
+    }
+
+    #[test]
+    fn test_invoked_as_decompressor() {
+        for name in ["unjan", "jandec", "/usr/bin/unjan", "./jandec", "unjan.exe"] {
+            assert!(invoked_as_decompressor(Some(name.as_ref())), "{name}");
+        }
+        for name in ["jan", "/usr/bin/jan", "unjan-old", "/opt/unjan/jan"] {
+            assert!(!invoked_as_decompressor(Some(name.as_ref())), "{name}");
+        }
+        assert!(!invoked_as_decompressor(None));
     }
 }
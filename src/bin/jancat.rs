@@ -0,0 +1,74 @@
+mod cli;
+
+use cli::{decompress_detected, describe, exit_on_broken_pipe, load_dictionary, parse_size};
+use splaycompress::dict::Dictionary;
+use splaycompress::{peek_magic, Error};
+use std::fs::File;
+use std::io::{self, stdin, stdout, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+
+/// Decompresses to stdout, like `zcat`. Same as `jan -dc`, except that it never compresses.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Preset dictionary to decompress with, for data that was compressed with `jan --dictionary`.
+    #[arg(long)]
+    dictionary: Option<PathBuf>,
+
+    /// Stop with an error once the output would grow beyond this many bytes, e.g. `1G`, as a
+    /// guard against decompression bombs in untrusted input. The limit is per file.
+    #[arg(long, value_parser = parse_size)]
+    max_output: Option<usize>,
+
+    /// Files to decompress, one after another. The flavor is taken from the filemagic. Without
+    /// any files, reads stdin. The files are never modified.
+    files: Vec<PathBuf>,
+}
+
+fn cat<R: Read>(args: &Args, dict: Option<&Dictionary>, r: R) -> io::Result<()> {
+    let (magic, r) = peek_magic(r)?;
+    let magic = magic.ok_or(Error::UnknownMagic)?;
+    decompress_detected(magic, dict, args.max_output, r, stdout().lock())
+        .map_err(exit_on_broken_pipe)?;
+    Ok(())
+}
+
+fn cat_file(args: &Args, dict: Option<&Dictionary>, input: &Path) -> io::Result<()> {
+    cat(args, dict, BufReader::new(File::open(input)?))
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let dict = match &args.dictionary {
+        None => None,
+        Some(path) => match load_dictionary(path) {
+            Ok(dict) => Some(dict),
+            Err(e) => {
+                eprintln!("jancat: {}: {}", path.display(), describe(&e));
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let dict = dict.as_ref();
+    if args.files.is_empty() {
+        return match cat(&args, dict, stdin().lock()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("jancat: {}", describe(&e));
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut exit_code = ExitCode::SUCCESS;
+    for input in &args.files {
+        // Like zcat, keep going, so that one bad file doesn't hide the rest.
+        if let Err(e) = cat_file(&args, dict, input) {
+            eprintln!("jancat: {}: {}", input.display(), describe(&e));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+    exit_code
+}
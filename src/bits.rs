@@ -1,4 +1,4 @@
-use std::io::*;
+use crate::io::{Read, Result, Write};
 
 pub struct BitWriter<W: Write> {
     backing: W,
@@ -37,6 +37,13 @@ impl<W: Write> BitWriter<W> {
         }
     }
 
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.backing
+    }
+
+    /// How many more bits are needed to complete the current byte. This is 0 (and never 8) when
+    /// the output is byte-aligned, including right after a byte was completed. Callers must keep
+    /// writing padding bits until this returns 0, as `flush` insists on byte alignment.
     pub fn padding_needed(&self) -> usize {
         if self.nbits > 0 {
             8 - self.nbits
@@ -74,6 +81,12 @@ impl<R: Read> BitReader<R> {
         self.nbits -= 1;
         Ok(bit)
     }
+
+    /// How many bits of the current byte have not been read yet. This is 0 when the input is
+    /// byte-aligned, i.e. the next `read_bit` reads a new byte.
+    pub fn buffered_bits(&self) -> usize {
+        self.nbits
+    }
 }
 
 #[cfg(test)]
@@ -144,4 +157,49 @@ mod tests {
         assert!(reader.read_bit().unwrap());
         assert!(!reader.read_bit().unwrap());
     }
+
+    #[test]
+    fn test_buffered_bits() {
+        let mut reader = BitReader::new(&[0b1010_0000, 0xff][..]);
+        assert_eq!(reader.buffered_bits(), 0);
+        for _ in 0..3 {
+            reader.read_bit().unwrap();
+        }
+        assert_eq!(reader.buffered_bits(), 5);
+        for _ in 0..5 {
+            reader.read_bit().unwrap();
+        }
+        assert_eq!(reader.buffered_bits(), 0);
+        reader.read_bit().unwrap();
+        assert_eq!(reader.buffered_bits(), 7);
+    }
+
+    #[test]
+    fn test_padding_needed_at_alignment() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        for byte in 0..3 {
+            for bit in 0..8 {
+                assert_eq!(
+                    writer.padding_needed(),
+                    (8 - bit) % 8,
+                    "byte {byte}, bit {bit}"
+                );
+                writer.write_bit(bit % 3 == 0).unwrap();
+            }
+            // Exactly aligned: no padding, as opposed to a full byte of padding.
+            assert_eq!(writer.padding_needed(), 0);
+        }
+        writer.flush().unwrap();
+        assert_eq!(buffer, [0b1001_0010; 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_flush_unaligned() {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_bit(true).unwrap();
+        assert_eq!(writer.padding_needed(), 7);
+        let _ = writer.flush();
+    }
 }
@@ -0,0 +1,750 @@
+//! The block container: The input is cut into blocks of a fixed size, and each block is compressed
+//! independently, starting from a fresh tree. This costs a bit of compression ratio, because the
+//! tree has to adapt again for each block, but it means that blocks can be compressed in parallel.
+//!
+//! Layout:
+//! - `MAGIC_FORMAT_BLOCKS`
+//! - Any number of blocks, each consisting of:
+//!   - The flavor tag (1 byte): 1 for Symbol8, 2 for Symbol16BE, 3 for Symbol16LE, 4 for
+//!     SymbolI16BE, 5 for SymbolI16LE. Blocks may differ, see `BlockOptions::adaptive`.
+//!   - The length of the uncompressed block in bytes (u32, big-endian).
+//!   - The length of the compressed block in bytes (u32, big-endian).
+//!   - The compressed block, as written by `compress`.
+//! - Optionally, with `BlockOptions::index`, the checkpoint index, see `SeekableDecompressor`:
+//!   - The index tag (1 byte): 255.
+//!   - The length of the entries in bytes (u32, big-endian).
+//!   - For each block, where its flavor tag is, counted from the start of the filemagic, and
+//!     where its data starts in the uncompressed output (each u64, big-endian). A final entry
+//!     holds where the index tag is, and the total uncompressed length.
+//!   - The length of the entries again (u32, big-endian), so the index can be found from the end.
+//! - The end tag (1 byte): 0.
+
+use crate::{
+    compress_with_stats, decompress_with_options, estimate_compressed_size, DecompressOptions,
+    Error, Flavor, Stats, MAGIC_LEN,
+};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Result, Write};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+/// Filemagic for "splaycompress block container".
+/// I generated this by taking 6 random bytes, the NUL byte, and the '\\r' byte, and re-shuffling
+/// them until neither of the two "special" bytes are at either end. This should provide a good
+/// balance between global uniqueness and built-in error detection.
+///
+/// Alternate representations: b"p\x00\x81\xcf\x85\rS\xda" or cACBz4UNU9o=
+pub const MAGIC_FORMAT_BLOCKS: &[u8] = b"\x70\x00\x81\xcf\x85\x0d\x53\xda";
+
+pub(crate) const TAG_END: u8 = 0;
+pub(crate) const TAG_INDEX: u8 = 255;
+
+/// Largest permitted block size. Even in the worst case, the compressed size of such a block
+/// comfortably fits into the u32 length field.
+pub const MAX_BLOCK_SIZE: usize = 64 << 20;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockOptions {
+    /// Number of uncompressed bytes per block. Must be even for the 16-bit flavors, so that no
+    /// symbol is split across blocks.
+    pub block_size: usize,
+    /// Number of blocks to compress at the same time. 1 means no additional threads are used.
+    /// Regardless of this value, the output is always the same.
+    pub threads: usize,
+    /// Append an index of where each block starts, so that `SeekableDecompressor` can start
+    /// decompressing at any block. Plain decompression skips it.
+    pub index: bool,
+    /// Compress each block with either `Flavor::Symbol8` or the given flavor, whichever
+    /// `estimate_compressed_size` predicts to be smaller, so that mixed data gets the better
+    /// symbol width for each part. This costs an estimate per flavor and block. An odd-length
+    /// final block always uses `Flavor::Symbol8`. No effect if the given flavor is `Symbol8`.
+    pub adaptive: bool,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 1 << 20,
+            threads: 1,
+            index: false,
+            adaptive: false,
+        }
+    }
+}
+
+fn flavor_tag(flavor: Flavor) -> u8 {
+    match flavor {
+        Flavor::Symbol8 => 1,
+        Flavor::Symbol16BE => 2,
+        Flavor::Symbol16LE => 3,
+        Flavor::SymbolI16BE => 4,
+        Flavor::SymbolI16LE => 5,
+    }
+}
+
+pub(crate) fn flavor_from_tag(tag: u8) -> Option<Flavor> {
+    match tag {
+        1 => Some(Flavor::Symbol8),
+        2 => Some(Flavor::Symbol16BE),
+        3 => Some(Flavor::Symbol16LE),
+        4 => Some(Flavor::SymbolI16BE),
+        5 => Some(Flavor::SymbolI16LE),
+        _ => None,
+    }
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+/// Reads up to `block_size` bytes. Only returns fewer bytes if EOF was reached.
+fn read_block<R: Read>(r: &mut R, block_size: usize) -> Result<Vec<u8>> {
+    let mut block = Vec::with_capacity(block_size);
+    r.take(block_size as u64).read_to_end(&mut block)?;
+    Ok(block)
+}
+
+/// Compresses the input into the block container, see the module documentation.
+pub fn compress_blocks<R: Read, W: Write>(
+    flavor: Flavor,
+    mut r: R,
+    mut w: W,
+    options: &BlockOptions,
+) -> Result<Stats> {
+    if options.block_size == 0 || options.block_size > MAX_BLOCK_SIZE {
+        return Err(invalid_input("block size out of range"));
+    }
+    if flavor != Flavor::Symbol8 && !options.block_size.is_multiple_of(2) {
+        return Err(invalid_input("block size must be even for 16-bit symbols"));
+    }
+    if options.threads == 0 {
+        return Err(invalid_input("need at least one thread"));
+    }
+
+    w.write_all(MAGIC_FORMAT_BLOCKS)?;
+    let mut stats = Stats {
+        bytes_out: MAGIC_LEN as u64,
+        ..Stats::default()
+    };
+    // Where each block starts, and its uncompressed length, for the index.
+    let mut blocks = Vec::new();
+    if options.threads == 1 {
+        loop {
+            let block = read_block(&mut r, options.block_size)?;
+            if !block.is_empty() {
+                let result = compress_block(flavor, &block, options.adaptive);
+                blocks.push((stats.bytes_out, block.len() as u64));
+                write_block(block.len(), result, &mut w, &mut stats)?;
+            }
+            if block.len() < options.block_size {
+                break;
+            }
+        }
+    } else {
+        let (job_tx, job_rx) = mpsc::sync_channel::<Job>(options.threads);
+        let job_rx = Mutex::new(job_rx);
+        thread::scope(|s| {
+            for _ in 0..options.threads {
+                s.spawn(|| loop {
+                    // Only hold the lock while waiting for the next job, not while working on it.
+                    let job = job_rx.lock().unwrap().recv();
+                    let Ok((block, result_tx)) = job else {
+                        break;
+                    };
+                    // The receiver is gone if writing an earlier block failed.
+                    let _ = result_tx.send(compress_block(flavor, &block, options.adaptive));
+                });
+            }
+            // Dropping the sender, also on error, lets the workers run out of jobs and stop.
+            let job_tx = job_tx;
+            pipeline(&mut r, &mut w, options, &job_tx, &mut stats, &mut blocks)
+        })?;
+    }
+    if options.index {
+        write_index(&blocks, &mut w, &mut stats)?;
+    }
+    w.write_all(&[TAG_END])?;
+    stats.bytes_out += 1;
+    w.flush()?;
+    Ok(stats)
+}
+
+/// The flavor that was chosen for the block, the compressed data, and its stats.
+type CompressedBlock = Result<(Flavor, Vec<u8>, Stats)>;
+
+/// A block to compress, and where to send the result.
+type Job = (Vec<u8>, mpsc::Sender<CompressedBlock>);
+
+/// Hands out blocks to the workers, and writes the results in order, even if later blocks finish
+/// first. To bound the memory usage, at most twice as many blocks as there are threads are in
+/// flight, so that the next round of blocks is ready while the current one is being written.
+fn pipeline<R: Read, W: Write>(
+    r: &mut R,
+    w: &mut W,
+    options: &BlockOptions,
+    job_tx: &mpsc::SyncSender<Job>,
+    stats: &mut Stats,
+    blocks: &mut Vec<(u64, u64)>,
+) -> Result<()> {
+    let mut in_flight = VecDeque::with_capacity(2 * options.threads);
+    let mut reached_eof = false;
+    while !reached_eof || !in_flight.is_empty() {
+        while !reached_eof && in_flight.len() < 2 * options.threads {
+            let block = read_block(r, options.block_size)?;
+            reached_eof = block.len() < options.block_size;
+            if !block.is_empty() {
+                let (result_tx, result_rx) = mpsc::channel();
+                in_flight.push_back((block.len(), result_rx));
+                job_tx
+                    .send((block, result_tx))
+                    .expect("compression threads stopped early");
+            }
+        }
+        if let Some((len, result_rx)) = in_flight.pop_front() {
+            let result = result_rx.recv().expect("compression thread panicked");
+            blocks.push((stats.bytes_out, len as u64));
+            write_block(len, result, w, stats)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_block<W: Write>(
+    len: usize,
+    result: CompressedBlock,
+    w: &mut W,
+    stats: &mut Stats,
+) -> Result<()> {
+    let (flavor, compressed, block_stats) = result?;
+    let compressed_len =
+        u32::try_from(compressed.len()).map_err(|_| invalid_input("compressed block too large"))?;
+    w.write_all(&[flavor_tag(flavor)])?;
+    w.write_all(&(len as u32).to_be_bytes())?;
+    w.write_all(&compressed_len.to_be_bytes())?;
+    w.write_all(&compressed)?;
+    stats.accumulate(&Stats {
+        bytes_out: 9 + block_stats.bytes_out,
+        ..block_stats
+    });
+    Ok(())
+}
+
+/// Writes the checkpoint index for `blocks`, each given by where it starts and its uncompressed
+/// length, see the module documentation.
+fn write_index<W: Write>(blocks: &[(u64, u64)], w: &mut W, stats: &mut Stats) -> Result<()> {
+    let len = u32::try_from(16 * (blocks.len() + 1))
+        .map_err(|_| invalid_input("too many blocks for the index"))?;
+    let mut index = Vec::with_capacity(len as usize + 9);
+    index.push(TAG_INDEX);
+    index.extend_from_slice(&len.to_be_bytes());
+    let mut plain_offset: u64 = 0;
+    for &(offset, plain_len) in blocks {
+        index.extend_from_slice(&offset.to_be_bytes());
+        index.extend_from_slice(&plain_offset.to_be_bytes());
+        plain_offset += plain_len;
+    }
+    index.extend_from_slice(&stats.bytes_out.to_be_bytes());
+    index.extend_from_slice(&plain_offset.to_be_bytes());
+    index.extend_from_slice(&len.to_be_bytes());
+    w.write_all(&index)?;
+    stats.bytes_out += index.len() as u64;
+    Ok(())
+}
+
+/// Reads the checkpoint index after its tag, see the module documentation, and checks that it is
+/// in order. Fails with `ErrorKind::UnexpectedEof` if the input ends early.
+pub(crate) fn read_index<R: Read>(mut r: R) -> Result<Vec<(u64, u64)>> {
+    let mut len = [0; 4];
+    r.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    // Read through `take`, so that a corrupt length can't make us allocate gigabytes up front.
+    let mut bytes = Vec::new();
+    r.take(len as u64 + 4).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize + 4 {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    let (entries, trailer) = bytes.split_at(len as usize);
+    if trailer != len.to_be_bytes() || entries.is_empty() || entries.len() % 16 != 0 {
+        return Err(Error::InvalidIndex.into());
+    }
+    let index: Vec<(u64, u64)> = entries
+        .chunks_exact(16)
+        .map(|entry| {
+            let offset = u64::from_be_bytes(entry[..8].try_into().unwrap());
+            let plain_offset = u64::from_be_bytes(entry[8..].try_into().unwrap());
+            (offset, plain_offset)
+        })
+        .collect();
+    // Empty blocks are never written, so both offsets must strictly increase.
+    let in_order = index
+        .windows(2)
+        .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1);
+    if index[0] != (MAGIC_LEN as u64, 0) || !in_order {
+        return Err(Error::InvalidIndex.into());
+    }
+    Ok(index)
+}
+
+fn compress_block(flavor: Flavor, block: &[u8], adaptive: bool) -> CompressedBlock {
+    let flavor = if !adaptive || flavor == Flavor::Symbol8 {
+        flavor
+    } else if !block.len().is_multiple_of(2)
+        || estimate_compressed_size(Flavor::Symbol8, block)
+            <= estimate_compressed_size(flavor, block)
+    {
+        Flavor::Symbol8
+    } else {
+        flavor
+    };
+    let mut compressed = Vec::new();
+    let stats = compress_with_stats(flavor, block, &mut compressed)?;
+    Ok((flavor, compressed, stats))
+}
+
+/// Reverses `compress_blocks`. Fails with `Error::UnknownMagic` if the input is not a block
+/// container.
+pub fn decompress_blocks<R: Read, W: Write>(
+    mut r: R,
+    w: W,
+    options: &DecompressOptions,
+) -> Result<Stats> {
+    let mut magic = [0; MAGIC_LEN];
+    match r.read_exact(&mut magic) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(Error::UnknownMagic.into()),
+        Err(e) => return Err(e),
+    }
+    if magic != MAGIC_FORMAT_BLOCKS {
+        return Err(Error::UnknownMagic.into());
+    }
+    decompress_block_body(r, w, options)
+}
+
+/// Decompresses everything after `MAGIC_FORMAT_BLOCKS`.
+pub(crate) fn decompress_block_body<R: Read, W: Write>(
+    mut r: R,
+    mut w: W,
+    options: &DecompressOptions,
+) -> Result<Stats> {
+    let mut stats = Stats::default();
+    let truncated = |bytes_out| -> io::Error {
+        Error::Truncated {
+            bytes_out,
+            cause: None,
+        }
+        .into()
+    };
+    loop {
+        let mut header = [0; 9];
+        match r.read_exact(&mut header[..1]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(truncated(stats.bytes_out));
+            }
+            Err(e) => return Err(e),
+        }
+        if header[0] == TAG_END {
+            w.flush()?;
+            return Ok(stats);
+        }
+        if header[0] == TAG_INDEX {
+            match read_index(r.by_ref()) {
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return Err(truncated(stats.bytes_out));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let flavor = flavor_from_tag(header[0]).ok_or(Error::UnknownMagic)?;
+        match r.read_exact(&mut header[1..]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(truncated(stats.bytes_out));
+            }
+            Err(e) => return Err(e),
+        }
+        let plain_len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as u64;
+        let compressed_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as u64;
+
+        let block_options = DecompressOptions {
+            max_output_bytes: options
+                .max_output_bytes
+                .map(|max| max.saturating_sub(stats.bytes_out)),
+            expected_output_bytes: Some(plain_len),
+            // The block header tells us exactly what to expect, so we can always be strict.
+            strict: true,
+            max_stored_nodes: options.max_stored_nodes,
+        };
+        let mut block_reader = r.by_ref().take(compressed_len);
+        let block_stats =
+            match decompress_with_options(flavor, &mut block_reader, &mut w, &block_options) {
+                Ok(block_stats) => block_stats,
+                Err(e) => {
+                    // Report the limit in terms of the whole stream, not just this block.
+                    if let Some(Error::LimitExceeded { written }) = Error::from_io_error(&e) {
+                        return Err(Error::LimitExceeded {
+                            written: stats.bytes_out + written,
+                        }
+                        .into());
+                    }
+                    // If the block ended early, the length mismatch is only a symptom. Errors of
+                    // the writer are passed on as they are, though.
+                    if block_reader.limit() != 0 && Error::from_io_error(&e).is_some() {
+                        return Err(truncated(stats.bytes_out));
+                    }
+                    return Err(e);
+                }
+            };
+        if block_reader.limit() != 0 {
+            return Err(truncated(stats.bytes_out + block_stats.bytes_out));
+        }
+        stats.accumulate(&block_stats);
+    }
+}
+
+/// What the headers of a block container say, see `scan_block_body`.
+#[derive(Debug, Default)]
+pub(crate) struct BlockScan {
+    pub blocks: u64,
+    /// Sum of the uncompressed lengths.
+    pub plain_bytes: u64,
+    /// Number of bytes consumed, i.e. of everything after the filemagic.
+    pub bytes_in: u64,
+    /// The flavor of the first block, if any. `compress_blocks` uses the same for all of them,
+    /// unless `BlockOptions::adaptive` is set.
+    pub flavor: Option<Flavor>,
+    /// Number of checkpoints in the index, if there is one.
+    pub checkpoints: Option<u64>,
+}
+
+/// Reads the block headers of everything after `MAGIC_FORMAT_BLOCKS`, and skips the compressed
+/// data in between, without decompressing it.
+pub(crate) fn scan_block_body<R: Read>(mut r: R) -> Result<BlockScan> {
+    let mut scan = BlockScan::default();
+    let truncated = |plain_bytes| -> io::Error {
+        Error::Truncated {
+            bytes_out: plain_bytes,
+            cause: None,
+        }
+        .into()
+    };
+    loop {
+        let mut header = [0; 9];
+        match r.read_exact(&mut header[..1]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(truncated(scan.plain_bytes));
+            }
+            Err(e) => return Err(e),
+        }
+        scan.bytes_in += 1;
+        if header[0] == TAG_END {
+            return Ok(scan);
+        }
+        if header[0] == TAG_INDEX {
+            match read_index(r.by_ref()) {
+                Ok(index) => {
+                    scan.bytes_in += 8 + 16 * index.len() as u64;
+                    scan.checkpoints = Some(index.len() as u64 - 1);
+                    continue;
+                }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return Err(truncated(scan.plain_bytes));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let flavor = flavor_from_tag(header[0]).ok_or(Error::UnknownMagic)?;
+        match r.read_exact(&mut header[1..]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(truncated(scan.plain_bytes));
+            }
+            Err(e) => return Err(e),
+        }
+        let plain_len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as u64;
+        let compressed_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as u64;
+        let skipped = io::copy(&mut r.by_ref().take(compressed_len), &mut io::sink())?;
+        if skipped != compressed_len {
+            return Err(truncated(scan.plain_bytes));
+        }
+        scan.blocks += 1;
+        scan.plain_bytes += plain_len;
+        scan.bytes_in += 8 + compressed_len;
+        scan.flavor.get_or_insert(flavor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress_with_magic};
+
+    fn sample(len: usize) -> Vec<u8> {
+        let text = b"However, this compression scheme is actually surprisingly good for short snippets of data, often beating zstd, lz4, and others.\n";
+        text.iter().copied().cycle().take(len).collect()
+    }
+
+    fn roundtrip(flavor: Flavor, input: &[u8], options: &BlockOptions) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        let stats = compress_blocks(flavor, input, &mut compressed, options).unwrap();
+        assert_eq!(stats.bytes_out, compressed.len() as u64);
+        let mut decompressed = Vec::new();
+        let stats =
+            decompress_blocks(&compressed[..], &mut decompressed, &Default::default()).unwrap();
+        assert_eq!(decompressed, input);
+        assert_eq!(stats.bytes_out, input.len() as u64);
+        compressed
+    }
+
+    #[test]
+    fn test_empty() {
+        let compressed = roundtrip(Flavor::Symbol8, b"", &BlockOptions::default());
+        assert_eq!(&compressed[..MAGIC_LEN], MAGIC_FORMAT_BLOCKS);
+        assert_eq!(&compressed[MAGIC_LEN..], &[TAG_END]);
+    }
+
+    #[test]
+    fn test_single_block() {
+        let compressed = roundtrip(Flavor::Symbol8, b"short", &BlockOptions::default());
+        assert_eq!(
+            &compressed[MAGIC_LEN..],
+            b"\x01\x00\x00\x00\x05\x00\x00\x00\x05\x73\x51\x3e\xf2\x00\x00"
+        );
+    }
+
+    #[test]
+    fn test_blocks_are_independent() {
+        let input = sample(1000);
+        let options = BlockOptions {
+            block_size: 100,
+            threads: 1,
+            ..BlockOptions::default()
+        };
+        let compressed = roundtrip(Flavor::Symbol8, &input, &options);
+        let mut first_block = Vec::new();
+        compress(Flavor::Symbol8, &input[..100], &mut first_block).unwrap();
+        assert_eq!(
+            &compressed[MAGIC_LEN + 9..][..first_block.len()],
+            first_block
+        );
+    }
+
+    #[test]
+    fn test_threads_same_output() {
+        let input = sample(10_000);
+        let sequential = roundtrip(
+            Flavor::Symbol8,
+            &input,
+            &BlockOptions {
+                block_size: 1000,
+                threads: 1,
+                ..BlockOptions::default()
+            },
+        );
+        for threads in [2, 3, 4, 16] {
+            let parallel = roundtrip(
+                Flavor::Symbol8,
+                &input,
+                &BlockOptions {
+                    block_size: 1000,
+                    threads,
+                    ..BlockOptions::default()
+                },
+            );
+            assert_eq!(sequential, parallel, "{threads} threads");
+        }
+    }
+
+    #[test]
+    #[ignore = "slow (building the 16-bit tree for every block adds up)"]
+    fn test_threads_same_output_16() {
+        let input = sample(1000);
+        for flavor in [Flavor::Symbol16BE, Flavor::Symbol16LE] {
+            let options = BlockOptions {
+                block_size: 250,
+                threads: 1,
+                ..BlockOptions::default()
+            };
+            let sequential = roundtrip(flavor, &input, &options);
+            let options = BlockOptions {
+                threads: 4,
+                ..options
+            };
+            assert_eq!(
+                sequential,
+                roundtrip(flavor, &input, &options),
+                "{flavor:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_exact_multiple() {
+        roundtrip(
+            Flavor::Symbol8,
+            &sample(300),
+            &BlockOptions {
+                block_size: 100,
+                threads: 2,
+                ..BlockOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_adaptive() {
+        // Text, where 8-bit wins, then a few u16 tokens whose bytes are all different, where
+        // 16-bit wins, and an odd byte at the end, which only 8-bit can take.
+        let mut input = include_bytes!("../README.md")[..200].to_vec();
+        for i in 0..100 {
+            let token: u16 = [0x1234, 0xabcd, 0x5678, 0xef01][i * 7 % 11 % 4];
+            input.extend_from_slice(&token.to_le_bytes());
+        }
+        input.push(b'!');
+        let options = BlockOptions {
+            block_size: 200,
+            adaptive: true,
+            ..BlockOptions::default()
+        };
+        let compressed = roundtrip(Flavor::Symbol16LE, &input, &options);
+        let mut tags = Vec::new();
+        let mut pos = MAGIC_LEN;
+        while compressed[pos] != TAG_END {
+            tags.push(compressed[pos]);
+            let len = u32::from_be_bytes(compressed[pos + 5..pos + 9].try_into().unwrap());
+            pos += 9 + len as usize;
+        }
+        assert_eq!(tags, [1, 3, 1]);
+        let threads = BlockOptions {
+            threads: 3,
+            ..options.clone()
+        };
+        assert_eq!(roundtrip(Flavor::Symbol16LE, &input, &threads), compressed);
+
+        // Without `adaptive`, the odd byte is an incomplete 16-bit symbol.
+        let result = compress_blocks(
+            Flavor::Symbol16LE,
+            &input[..],
+            &mut Vec::new(),
+            &BlockOptions {
+                adaptive: false,
+                ..options
+            },
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        for (flavor, block_size, threads) in [
+            (Flavor::Symbol8, 0, 1),
+            (Flavor::Symbol8, MAX_BLOCK_SIZE + 1, 1),
+            (Flavor::Symbol16BE, 99, 1),
+            (Flavor::Symbol8, 100, 0),
+        ] {
+            let options = BlockOptions {
+                block_size,
+                threads,
+                ..BlockOptions::default()
+            };
+            let err = compress_blocks(flavor, &b"abcd"[..], &mut Vec::new(), &options).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let input = sample(1000);
+        let options = BlockOptions {
+            block_size: 100,
+            threads: 1,
+            ..BlockOptions::default()
+        };
+        let mut compressed = Vec::new();
+        compress_blocks(Flavor::Symbol8, &input[..], &mut compressed, &options).unwrap();
+        for len in [
+            MAGIC_LEN,
+            MAGIC_LEN + 5,
+            MAGIC_LEN + 20,
+            compressed.len() - 1,
+        ] {
+            let err = decompress_blocks(&compressed[..len], &mut Vec::new(), &Default::default())
+                .unwrap_err();
+            assert!(
+                matches!(Error::from_io_error(&err), Some(Error::Truncated { .. })),
+                "{len}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_autodetect() {
+        let input = sample(1000);
+        let mut compressed = Vec::new();
+        compress_blocks(
+            Flavor::Symbol16LE,
+            &input[..],
+            &mut compressed,
+            &BlockOptions::default(),
+        )
+        .unwrap();
+        let mut decompressed = Vec::new();
+        decompress_with_magic(&compressed[..], &mut decompressed, &Default::default()).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_limit() {
+        let input = sample(1000);
+        let options = BlockOptions {
+            block_size: 100,
+            threads: 1,
+            ..BlockOptions::default()
+        };
+        let mut compressed = Vec::new();
+        compress_blocks(Flavor::Symbol8, &input[..], &mut compressed, &options).unwrap();
+        let options = DecompressOptions {
+            max_output_bytes: Some(250),
+            ..DecompressOptions::default()
+        };
+        let err = decompress_blocks(&compressed[..], &mut Vec::new(), &options).unwrap_err();
+        assert_eq!(
+            Error::from_io_error(&err),
+            Some(&Error::LimitExceeded { written: 250 })
+        );
+    }
+
+    #[test]
+    fn test_writer_error_is_not_truncation() {
+        let input = sample(1000);
+        let options = BlockOptions {
+            block_size: 100,
+            threads: 1,
+            ..BlockOptions::default()
+        };
+        let mut compressed = Vec::new();
+        compress_blocks(Flavor::Symbol8, &input[..], &mut compressed, &options).unwrap();
+        // Accepts 150 bytes, and then fails like a closed pipe.
+        struct ClosedPipe(usize);
+        impl Write for ClosedPipe {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                if self.0 == 0 {
+                    return Err(ErrorKind::BrokenPipe.into());
+                }
+                let n = buf.len().min(self.0);
+                self.0 -= n;
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+        let err =
+            decompress_blocks(&compressed[..], ClosedPipe(150), &Default::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert_eq!(Error::from_io_error(&err), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! An object-safe interface to compression, for when the flavor is only known at runtime and the
+//! codec has to be stored, e.g. as `Box<dyn Codec>` in a map of codecs by name. The free
+//! functions like `compress` are generic over the reader and writer, so they can't be called
+//! through a trait object.
+
+use crate::{compress, decompress, Flavor};
+use std::io::{Read, Result, Write};
+use std::str::FromStr;
+
+/// Something that compresses and decompresses whole streams.
+pub trait Codec {
+    /// Compresses all of `r` into `w`, like `compress`.
+    fn compress(&self, r: &mut dyn Read, w: &mut dyn Write) -> Result<()>;
+
+    /// Decompresses all of `r` into `w`, like `decompress`.
+    fn decompress(&self, r: &mut dyn Read, w: &mut dyn Write) -> Result<()>;
+}
+
+/// Raw splaycompress data of a fixed flavor, without filemagic. The same as calling `compress`
+/// and `decompress` with `flavor`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SplayCodec {
+    pub flavor: Flavor,
+}
+
+impl SplayCodec {
+    pub fn new(flavor: Flavor) -> Self {
+        Self { flavor }
+    }
+}
+
+impl Codec for SplayCodec {
+    fn compress(&self, r: &mut dyn Read, w: &mut dyn Write) -> Result<()> {
+        compress(self.flavor, r, w)
+    }
+
+    fn decompress(&self, r: &mut dyn Read, w: &mut dyn Write) -> Result<()> {
+        decompress(self.flavor, r, w)
+    }
+}
+
+/// Compresses with the flavor of the given `Flavor::name`, e.g. from a config file, through a
+/// `SplayCodec`. Fails with `Error::UnknownFlavor` before touching `r` or `w` if there is no such
+/// flavor.
+pub fn compress_named(flavor: &str, r: &mut dyn Read, w: &mut dyn Write) -> Result<()> {
+    SplayCodec::new(Flavor::from_str(flavor)?).compress(r, w)
+}
+
+/// Reverses `compress_named`.
+pub fn decompress_named(flavor: &str, r: &mut dyn Read, w: &mut dyn Write) -> Result<()> {
+    SplayCodec::new(Flavor::from_str(flavor)?).decompress(r, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_trait_object_roundtrip() {
+        let input = b"Hello, World! Hello, World! Hello, World!\n";
+        let flavors = Flavor::ALL;
+        let codecs: Vec<Box<dyn Codec>> = flavors
+            .into_iter()
+            .map(|flavor| Box::new(SplayCodec::new(flavor)) as Box<dyn Codec>)
+            .collect();
+        for (flavor, codec) in flavors.into_iter().zip(&codecs) {
+            let mut compressed = Vec::new();
+            codec.compress(&mut &input[..], &mut compressed).unwrap();
+            // Exactly what the generic function writes.
+            let mut expected = Vec::new();
+            compress(flavor, &input[..], &mut expected).unwrap();
+            assert_eq!(compressed, expected, "{flavor:?}");
+
+            let mut decompressed = Vec::new();
+            codec
+                .decompress(&mut &compressed[..], &mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, input, "{flavor:?}");
+        }
+    }
+
+    #[test]
+    fn test_named() {
+        let input = b"Hello, World! Hello, World! Hello, World!\n";
+        for name in ["bit8", "bit16-be", "bit16-le", "i16-be", "i16-le"] {
+            let flavor = Flavor::from_str(name).unwrap();
+            assert_eq!(flavor.name(), name);
+            let mut compressed = Vec::new();
+            compress_named(name, &mut &input[..], &mut compressed).unwrap();
+            let mut expected = Vec::new();
+            compress(flavor, &input[..], &mut expected).unwrap();
+            assert_eq!(compressed, expected, "{name}");
+
+            let mut decompressed = Vec::new();
+            decompress_named(name, &mut &compressed[..], &mut decompressed).unwrap();
+            assert_eq!(decompressed, input, "{name}");
+        }
+
+        let mut compressed = Vec::new();
+        let err = compress_named("bit16", &mut &input[..], &mut compressed).unwrap_err();
+        assert_eq!(Error::from_io_error(&err), Some(&Error::UnknownFlavor));
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(compressed.is_empty());
+        assert!(err.to_string().contains("bit16-be"));
+    }
+}
@@ -1,5 +1,6 @@
-use std::fmt::Debug;
+use core::fmt::Debug;
 
+/// An arm of an internal node: Either another internal node, or the leaf of a symbol.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum NodeRef<T: Clone + Copy + Debug + Eq + PartialEq> {
     Internal(T),
@@ -11,6 +12,8 @@ impl<T: Clone + Copy + Debug + Eq + PartialEq> NodeRef<T> {
         NodeRef::Leaf(v)
     }
 
+    /// Panics if `v` is `max`, the largest symbol, which can't be an internal node, see
+    /// `NodeArena::checked_incr`.
     pub fn new_internal(v: T, max: T) -> Self {
         assert!(v != max, "too large internal ID: {v:?}");
         NodeRef::Internal(v)
@@ -54,12 +57,14 @@ impl Direction {
     }
 
     #[allow(dead_code)]
-    pub fn to_bit(&self) -> bool {
-        self == &Direction::Right
+    pub fn to_bit(self) -> bool {
+        self == Direction::Right
     }
 }
 
-#[derive(Debug)]
+/// An internal node of a tree, see `NodeArena`. Internal node `v` separates the symbols up to and
+/// including `v`, on its left, from those above it, on its right.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Node<T: Clone + Copy + Debug + Eq + PartialEq> {
     pub left: NodeRef<T>,
     pub right: NodeRef<T>,
@@ -0,0 +1,266 @@
+//! Preset dictionaries: A tree that was warmed up on sample data, so that compression of similar
+//! data doesn't have to start from the uniform tree. This helps most for many small, similar
+//! inputs, e.g. log lines, where the tree otherwise never gets the chance to adapt.
+//!
+//! Only 8-bit symbols are supported. The compressed format is `MAGIC_FORMAT_DICTIONARY`, followed
+//! by the `id` of the dictionary (u32, big-endian), followed by the data as written by `compress`,
+//! except that the tree starts out as the one from the dictionary. Decompression needs the same
+//! dictionary, which is checked through its `id`.
+
+use crate::common::NodeRef;
+use crate::splay::{Arena8, NodeArena};
+use crate::symbol::{SymbolRead8, SymbolWrite8};
+use crate::util::crc32;
+use crate::{
+    compress_raw, decompress_raw, peek_magic, DecompressOptions, Error, Stats,
+    MAGIC_FORMAT_SYMBOL8, MAGIC_LEN,
+};
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Result, Write};
+
+/// Filemagic for "splaycompress data with 8-bit symbols, compressed with a preset dictionary".
+/// Generated the same way as `MAGIC_FORMAT_SYMBOL8`.
+///
+/// Alternate representations: b"?!\x17\x89\x00\xda\r\xa2" or PyEXiQDaDaI=
+pub const MAGIC_FORMAT_DICTIONARY: &[u8] = b"\x3f\x21\x17\x89\x00\xda\x0d\xa2";
+
+/// Filemagic of a dictionary file, as written by `Dictionary::to_bytes`. Generated the same way.
+///
+/// Alternate representations: b">\xd6\x99\x10\x00\rH\x18" or PtaZEAANSBg=
+pub const MAGIC_DICTIONARY_FILE: &[u8] = b"\x3e\xd6\x99\x10\x00\x0d\x48\x18";
+
+/// A warmed-up tree, see the module documentation.
+#[derive(Clone, Debug)]
+pub struct Dictionary {
+    arena: Box<Arena8>,
+}
+
+impl Dictionary {
+    /// Replays all samples through a fresh tree, exactly like compressing them one after another
+    /// would. Later samples have more influence on the result than earlier ones.
+    pub fn train<'a, I: IntoIterator<Item = &'a [u8]>>(samples: I) -> Self {
+        let mut arena = Box::new(Arena8::new_uniform());
+        let mut walker = arena.splayable_mut();
+        for sample in samples {
+            for &symbol in sample {
+                walker.splay_symbol(symbol);
+            }
+        }
+        Self { arena }
+    }
+
+    /// Serializes the dictionary, including `MAGIC_DICTIONARY_FILE`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC_DICTIONARY_FILE.to_vec();
+        bytes.extend_from_slice(&self.arena.to_bytes());
+        bytes
+    }
+
+    /// Reverses `to_bytes`. Fails with `Error::InvalidDictionary` if `bytes` is not a dictionary.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let arena = bytes
+            .strip_prefix(MAGIC_DICTIONARY_FILE)
+            .and_then(Arena8::from_bytes)
+            .ok_or(Error::InvalidDictionary)?;
+        Ok(Self {
+            arena: Box::new(arena),
+        })
+    }
+
+    /// Identifies the dictionary in compressed data, so that decompressing with a different one
+    /// fails instead of producing garbage. This is the CRC-32 of `to_bytes`.
+    pub fn id(&self) -> u32 {
+        crc32(&self.to_bytes())
+    }
+
+    /// Renders the tree in the DOT language of Graphviz, e.g. for `dot -Tsvg`. Internal nodes are
+    /// named `i0` to `i254`, and leaves `l0` to `l255`, each labelled with its value. Edges are
+    /// labelled with the bit that takes them.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph splaytree {\n");
+        for id in 0..u8::MAX {
+            writeln!(dot, "    i{id} [label=\"{id}\"];").unwrap();
+            let node = self.arena.node(id);
+            for (bit, arm) in [(0, node.left), (1, node.right)] {
+                let target = match arm {
+                    NodeRef::Internal(v) => format!("i{v}"),
+                    NodeRef::Leaf(v) => format!("l{v}"),
+                };
+                writeln!(dot, "    i{id} -> {target} [label=\"{bit}\"];").unwrap();
+            }
+        }
+        for symbol in 0..=u8::MAX {
+            writeln!(dot, "    l{symbol} [label=\"{symbol}\", shape=box];").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Like `compress_with_dictionary` if `dict` is given, and like `compress_with_magic` with
+/// `Flavor::Symbol8` otherwise, but also gives back the tree as it is after compressing. That's
+/// for inspecting what the tree adapted to, e.g. with `Dictionary::to_dot`, and it also works
+/// as a dictionary for similar data.
+pub fn compress_keep_tree<R: Read, W: Write>(
+    dict: Option<&Dictionary>,
+    r: R,
+    mut w: W,
+) -> Result<(Stats, Dictionary)> {
+    let (input_magic, r) = peek_magic(r)?;
+    let (mut arena, header_len) = match dict {
+        Some(dict) => {
+            w.write_all(MAGIC_FORMAT_DICTIONARY)?;
+            w.write_all(&dict.id().to_be_bytes())?;
+            (dict.arena.clone(), MAGIC_LEN + 4)
+        }
+        None => {
+            w.write_all(MAGIC_FORMAT_SYMBOL8)?;
+            (Box::default(), MAGIC_LEN)
+        }
+    };
+    let mut stats = compress_raw(arena.as_mut(), &mut SymbolRead8(r), w)?;
+    stats.bytes_out += header_len as u64;
+    stats.input_looks_compressed = input_magic.is_some();
+    Ok((stats, Dictionary { arena }))
+}
+
+/// Like `compress_with_magic`, but starts out with the tree from `dict`, see the module
+/// documentation.
+pub fn compress_with_dictionary<R: Read, W: Write>(dict: &Dictionary, r: R, w: W) -> Result<Stats> {
+    let (stats, _) = compress_keep_tree(Some(dict), r, w)?;
+    Ok(stats)
+}
+
+/// Reverses `compress_with_dictionary`. Fails with `Error::UnknownMagic` if the input was not
+/// compressed with a dictionary, and with `Error::DictionaryMismatch` if it was compressed with a
+/// different one.
+pub fn decompress_with_dictionary<R: Read, W: Write>(
+    dict: &Dictionary,
+    mut r: R,
+    w: W,
+    options: &DecompressOptions,
+) -> Result<Stats> {
+    let mut header = [0; MAGIC_LEN + 4];
+    match r.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(Error::UnknownMagic.into()),
+        Err(e) => return Err(e),
+    }
+    if &header[..MAGIC_LEN] != MAGIC_FORMAT_DICTIONARY {
+        return Err(Error::UnknownMagic.into());
+    }
+    let expected = u32::from_be_bytes(header[MAGIC_LEN..].try_into().unwrap());
+    if expected != dict.id() {
+        return Err(Error::DictionaryMismatch {
+            expected,
+            actual: dict.id(),
+        }
+        .into());
+    }
+    let mut arena = dict.arena.clone();
+    decompress_raw(arena.as_mut(), r, &mut SymbolWrite8(w), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, Flavor};
+
+    const SAMPLES: [&[u8]; 3] = [
+        b"2024-01-01 INFO request took 12ms\n",
+        b"2024-01-01 INFO request took 7ms\n",
+        b"2024-01-02 WARN request took 1234ms\n",
+    ];
+
+    #[test]
+    fn test_roundtrip() {
+        let dict = Dictionary::train(SAMPLES);
+        let input = b"2024-01-03 INFO request took 9ms\n";
+        let mut compressed = Vec::new();
+        let stats = compress_with_dictionary(&dict, &input[..], &mut compressed).unwrap();
+        assert_eq!(stats.bytes_out, compressed.len() as u64);
+        assert_eq!(&compressed[..MAGIC_LEN], MAGIC_FORMAT_DICTIONARY);
+
+        let mut plain = Vec::new();
+        compress(Flavor::Symbol8, &input[..], &mut plain).unwrap();
+        assert!(compressed.len() - MAGIC_LEN - 4 < plain.len());
+
+        let mut decompressed = Vec::new();
+        decompress_with_dictionary(
+            &dict,
+            &compressed[..],
+            &mut decompressed,
+            &DecompressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_compress_keep_tree() {
+        let input = SAMPLES.concat();
+        let (stats, tree) = compress_keep_tree(None, &input[..], Vec::new()).unwrap();
+        let mut expected = Vec::new();
+        crate::compress_with_magic(Flavor::Symbol8, &input[..], &mut expected).unwrap();
+        assert_eq!(stats.bytes_out, expected.len() as u64);
+        // Compressing splays the tree exactly like training does.
+        assert_eq!(tree.to_bytes(), Dictionary::train([&input[..]]).to_bytes());
+
+        let dict = Dictionary::train(SAMPLES);
+        let mut compressed = Vec::new();
+        let (_, tree) = compress_keep_tree(Some(&dict), &input[..], &mut compressed).unwrap();
+        let mut expected = Vec::new();
+        compress_with_dictionary(&dict, &input[..], &mut expected).unwrap();
+        assert_eq!(compressed, expected);
+        let retrained = Dictionary::train(SAMPLES.into_iter().chain([&input[..]]));
+        assert_eq!(tree.to_bytes(), retrained.to_bytes());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = Dictionary::train(SAMPLES).to_dot();
+        assert!(dot.starts_with("digraph splaytree {\n"));
+        assert!(dot.ends_with("}\n"));
+        let count = |f: fn(&str) -> bool| dot.lines().filter(|line| f(line)).count();
+        let internal = count(|line| line.starts_with("    i") && !line.contains(" -> "));
+        let leaves = count(|line| line.starts_with("    l"));
+        let edges = count(|line| line.contains(" -> "));
+        assert_eq!(internal, 255);
+        assert_eq!(leaves, 256);
+        // Every node except the root has exactly one parent.
+        assert_eq!(edges, internal + leaves - 1);
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let dict = Dictionary::train(SAMPLES);
+        let restored = Dictionary::from_bytes(&dict.to_bytes()).unwrap();
+        assert_eq!(restored.id(), dict.id());
+        assert_ne!(dict.id(), Dictionary::train([]).id());
+
+        let err = Dictionary::from_bytes(b"not a dictionary").unwrap_err();
+        assert_eq!(Error::from_io_error(&err), Some(&Error::InvalidDictionary));
+    }
+
+    #[test]
+    fn test_wrong_dictionary() {
+        let dict = Dictionary::train(SAMPLES);
+        let other = Dictionary::train([&b"something else entirely"[..]]);
+        let mut compressed = Vec::new();
+        compress_with_dictionary(&dict, &b"INFO"[..], &mut compressed).unwrap();
+        let err = decompress_with_dictionary(
+            &other,
+            &compressed[..],
+            &mut Vec::new(),
+            &DecompressOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            Error::from_io_error(&err),
+            Some(&Error::DictionaryMismatch {
+                expected: dict.id(),
+                actual: other.id(),
+            })
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::io;
+use crate::splay::WalkError;
+use core::fmt::{self, Display, Formatter};
+
+/// Errors that are specific to splaycompress, as opposed to plain IO errors of the underlying
+/// reader or writer.
+///
+/// All public functions return `std::io::Result`, so these are transported inside an
+/// `io::Error`. Use `Error::from_io_error` to get them back out. Without the `std` feature, the
+/// same goes for the stand-in `splaycompress::io::Error`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Decompression would have produced more than `DecompressOptions::max_output_bytes`, or
+    /// compression more than `CompressOptions::max_output_bytes`. `written` is the number of
+    /// bytes that were written before stopping.
+    LimitExceeded { written: u64 },
+    /// The decoded data does not have the length it was declared to have. If the data was too
+    /// long, `actual` is the length at which decoding was stopped.
+    LengthMismatch { expected: u64, actual: u64 },
+    /// The input ended before the data was complete. `bytes_out` is the number of bytes that were
+    /// written before noticing. If the reader failed, `cause` is the kind of that error;
+    /// otherwise the input simply stopped in the middle of a symbol.
+    Truncated {
+        bytes_out: u64,
+        cause: Option<io::ErrorKind>,
+    },
+    /// The input does not start with any of the `MAGIC_FORMAT_*` filemagics.
+    UnknownMagic,
+    /// The arena and the symbols disagree on the alphabet, e.g. a custom arena that covers fewer
+    /// symbols than the reader can produce. Both are given as the largest symbol.
+    Mismatch { arena_max: i64, symbols_max: i64 },
+    /// The tree walker was used incorrectly, which indicates malformed input or a bug.
+    Walk(WalkError),
+    /// The input was compressed with a preset dictionary, which must be given to decompress it.
+    DictionaryRequired,
+    /// The input was compressed with a different preset dictionary. Both are given as their
+    /// `Dictionary::id`.
+    DictionaryMismatch { expected: u32, actual: u32 },
+    /// The data is not a valid serialized `Dictionary`.
+    InvalidDictionary,
+    /// The length prefix of a framed stream is malformed, see `compress_framed`.
+    InvalidFrameLength,
+    /// The string is not the name of any flavor, see `Flavor::name`.
+    UnknownFlavor,
+    /// The block container has no checkpoint index, or it doesn't match the blocks, see
+    /// `SeekableDecompressor`.
+    InvalidIndex,
+}
+
+impl Error {
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::LimitExceeded { .. } => io::ErrorKind::Other,
+            Error::LengthMismatch { .. } => io::ErrorKind::InvalidData,
+            Error::Truncated { cause, .. } => cause.unwrap_or(io::ErrorKind::UnexpectedEof),
+            Error::UnknownMagic => io::ErrorKind::InvalidData,
+            Error::Mismatch { .. } => io::ErrorKind::InvalidInput,
+            Error::Walk(_) => io::ErrorKind::InvalidData,
+            Error::DictionaryRequired => io::ErrorKind::InvalidInput,
+            Error::DictionaryMismatch { .. } => io::ErrorKind::InvalidInput,
+            Error::InvalidDictionary => io::ErrorKind::InvalidData,
+            Error::InvalidFrameLength => io::ErrorKind::InvalidData,
+            Error::UnknownFlavor => io::ErrorKind::InvalidInput,
+            Error::InvalidIndex => io::ErrorKind::InvalidData,
+        }
+    }
+
+    /// Returns the splaycompress-specific error wrapped in `e`, if any.
+    #[cfg(feature = "std")]
+    pub fn from_io_error(e: &io::Error) -> Option<&Error> {
+        e.get_ref().and_then(|inner| inner.downcast_ref::<Error>())
+    }
+
+    /// Returns the splaycompress-specific error wrapped in `e`, if any.
+    #[cfg(not(feature = "std"))]
+    pub fn from_io_error(e: &io::Error) -> Option<&Error> {
+        e.splay_error()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LimitExceeded { written } => {
+                write!(f, "output limit exceeded after writing {written} bytes")
+            }
+            Error::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes of output, but got {actual}")
+            }
+            Error::Truncated { bytes_out, cause } => {
+                write!(f, "input truncated after {bytes_out} bytes of output")?;
+                match cause {
+                    Some(kind) => write!(f, " ({kind})"),
+                    None => write!(f, " (in the middle of a symbol)"),
+                }
+            }
+            Error::UnknownMagic => write!(f, "not a splaycompress file"),
+            Error::Mismatch {
+                arena_max,
+                symbols_max,
+            } => write!(
+                f,
+                "arena covers symbols up to {arena_max}, but the symbols go up to {symbols_max}"
+            ),
+            Error::Walk(e) => write!(f, "invalid tree walk: {e}"),
+            Error::DictionaryRequired => {
+                write!(
+                    f,
+                    "compressed with a preset dictionary, which is needed to decompress"
+                )
+            }
+            Error::DictionaryMismatch { expected, actual } => write!(
+                f,
+                "compressed with dictionary {expected:08x}, but got dictionary {actual:08x}"
+            ),
+            Error::InvalidDictionary => write!(f, "not a splaycompress dictionary"),
+            Error::InvalidFrameLength => write!(f, "malformed frame length"),
+            Error::UnknownFlavor => write!(
+                f,
+                "unknown flavor, expected one of bit8, bit16-be, bit16-le, i16-be, or i16-le"
+            ),
+            Error::InvalidIndex => write!(f, "missing or malformed checkpoint index"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<WalkError> for Error {
+    fn from(e: WalkError) -> Error {
+        Error::Walk(e)
+    }
+}
+
+impl From<WalkError> for io::Error {
+    fn from(e: WalkError) -> io::Error {
+        Error::Walk(e).into()
+    }
+}
+
+/// Without `std`, `io::Error` provides this itself.
+#[cfg(feature = "std")]
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        io::Error::new(e.kind(), e)
+    }
+}
@@ -0,0 +1,170 @@
+//! Length-prefixed framing, for embedding compressed data in a larger binary format: The
+//! compressed data is preceded by its length in bytes, so that a reader can extract or skip it
+//! without scanning. Note that this is the length of the *compressed* data, not of the original.
+//!
+//! Layout:
+//! - The length of the compressed data in bytes, as an unsigned LEB128 varint: 7 bits per byte,
+//!   least significant group first, with the high bit set on all bytes except the last.
+//! - The compressed data, as written by `compress`.
+//!
+//! There is no filemagic, since the surrounding format is expected to know what it embeds.
+
+use crate::{
+    compress_with_stats, decompress_with_options, DecompressOptions, Error, Flavor, Stats,
+};
+use std::io::{self, ErrorKind, Read, Result, Write};
+
+/// Longest possible encoding of a u64.
+const MAX_VARINT_LEN: usize = 10;
+
+fn encode_varint(mut value: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MAX_VARINT_LEN);
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    bytes
+}
+
+/// Reads a varint as written by `encode_varint`. Encodings that are longer than necessary are
+/// accepted, as long as the value fits into a u64.
+fn read_varint<R: Read>(r: &mut R) -> Result<u64> {
+    let mut value = 0;
+    for i in 0..MAX_VARINT_LEN {
+        let mut byte = [0];
+        match r.read_exact(&mut byte) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(Error::Truncated {
+                    bytes_out: 0,
+                    cause: None,
+                }
+                .into());
+            }
+            Err(e) => return Err(e),
+        }
+        let group = (byte[0] & 0x7F) as u64;
+        // The tenth byte only has room for the single most significant bit.
+        if i == MAX_VARINT_LEN - 1 && group > 1 {
+            return Err(Error::InvalidFrameLength.into());
+        }
+        value |= group << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidFrameLength.into())
+}
+
+/// Like `compress_with_stats`, but prefixes the output with its length, see the module
+/// documentation. The compressed data is buffered in memory, since its length must come first.
+pub fn compress_framed<R: Read, W: Write>(flavor: Flavor, r: R, mut w: W) -> Result<Stats> {
+    let mut compressed = Vec::new();
+    let mut stats = compress_with_stats(flavor, r, &mut compressed)?;
+    let prefix = encode_varint(compressed.len() as u64);
+    w.write_all(&prefix)?;
+    w.write_all(&compressed)?;
+    w.flush()?;
+    stats.bytes_out += prefix.len() as u64;
+    Ok(stats)
+}
+
+/// Reverses `compress_framed`. Reads exactly one frame and nothing beyond it, so pass `&mut r` to
+/// continue reading whatever follows. Fails with `Error::Truncated` if the input ends before the
+/// frame does.
+pub fn decompress_framed<R: Read, W: Write>(
+    flavor: Flavor,
+    mut r: R,
+    w: W,
+    options: &DecompressOptions,
+) -> Result<Stats> {
+    let len = read_varint(&mut r)?;
+    let mut frame = r.take(len);
+    let truncated = |bytes_out| -> io::Error {
+        Error::Truncated {
+            bytes_out,
+            cause: None,
+        }
+        .into()
+    };
+    match decompress_with_options(flavor, &mut frame, w, options) {
+        Ok(stats) if frame.limit() != 0 => Err(truncated(stats.bytes_out)),
+        Ok(stats) => Ok(stats),
+        // If the frame ended early, whatever went wrong is only a symptom, unless the writer
+        // failed.
+        Err(e) if frame.limit() != 0 && Error::from_io_error(&e).is_some() => Err(truncated(0)),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, 16383, 16384, u32::MAX as u64, u64::MAX] {
+            let bytes = encode_varint(value);
+            assert_eq!(read_varint(&mut &bytes[..]).unwrap(), value);
+        }
+        assert_eq!(encode_varint(0), [0]);
+        assert_eq!(encode_varint(300), [0xAC, 0x02]);
+        assert_eq!(encode_varint(u64::MAX).len(), MAX_VARINT_LEN);
+        // Redundant, but harmless.
+        assert_eq!(read_varint(&mut &[0x81, 0x80, 0x00][..]).unwrap(), 1);
+
+        let err = read_varint(&mut &[0xFF; 11][..]).unwrap_err();
+        assert_eq!(Error::from_io_error(&err), Some(&Error::InvalidFrameLength));
+        let mut overflow = [0xFF; 10];
+        overflow[9] = 0x02;
+        let err = read_varint(&mut &overflow[..]).unwrap_err();
+        assert_eq!(Error::from_io_error(&err), Some(&Error::InvalidFrameLength));
+        let err = read_varint(&mut &[0x80][..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_embedded() {
+        let input = b"Hello, World! Hello, World!\n";
+        let mut container = vec![0xAA];
+        let stats = compress_framed(Flavor::Symbol8, &input[..], &mut container).unwrap();
+        assert_eq!(stats.bytes_out, container.len() as u64 - 1);
+        container.push(0x55);
+
+        let mut r = &container[..];
+        let mut marker = [0];
+        r.read_exact(&mut marker).unwrap();
+        assert_eq!(marker, [0xAA]);
+        let mut output = Vec::new();
+        decompress_framed(
+            Flavor::Symbol8,
+            &mut r,
+            &mut output,
+            &DecompressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(output, input);
+        // Exactly the frame was consumed.
+        assert_eq!(r, [0x55]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut framed = Vec::new();
+        compress_framed(Flavor::Symbol8, &b"some data"[..], &mut framed).unwrap();
+        for len in 0..framed.len() {
+            let err = decompress_framed(
+                Flavor::Symbol8,
+                &framed[..len],
+                &mut Vec::new(),
+                &DecompressOptions::default(),
+            )
+            .unwrap_err();
+            assert!(
+                matches!(Error::from_io_error(&err), Some(Error::Truncated { .. })),
+                "{len}: {err}"
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! The IO traits that the core of the codec is written against. With the `std` feature, these are
+//! simply those of `std::io`, so that any reader or writer works. Without it, there is no
+//! `std::io`, and this module provides stand-ins with just what the core needs, implemented for
+//! slices, `Vec`, and `VecDeque`.
+
+#[cfg(feature = "std")]
+pub(crate) use std::io::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+    use core::fmt::{self, Display, Formatter};
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// The subset of `std::io::ErrorKind` that the codec reports.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        InvalidInput,
+        InvalidData,
+        UnexpectedEof,
+        WriteZero,
+        Interrupted,
+        Other,
+    }
+
+    impl Display for ErrorKind {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                ErrorKind::InvalidInput => "invalid input parameter",
+                ErrorKind::InvalidData => "invalid data",
+                ErrorKind::UnexpectedEof => "unexpected end of file",
+                ErrorKind::WriteZero => "write zero",
+                ErrorKind::Interrupted => "operation interrupted",
+                ErrorKind::Other => "other error",
+            })
+        }
+    }
+
+    /// Like `std::io::Error`, an `ErrorKind` together with either a message or the
+    /// splaycompress-specific `crate::Error`, which `crate::Error::from_io_error` gets back out.
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        payload: Payload,
+    }
+
+    #[derive(Debug)]
+    enum Payload {
+        Message(&'static str),
+        Splay(crate::Error),
+    }
+
+    impl Error {
+        pub fn new(kind: ErrorKind, message: &'static str) -> Self {
+            Self {
+                kind,
+                payload: Payload::Message(message),
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+
+        pub(crate) fn splay_error(&self) -> Option<&crate::Error> {
+            match &self.payload {
+                Payload::Message(_) => None,
+                Payload::Splay(e) => Some(e),
+            }
+        }
+    }
+
+    impl From<crate::Error> for Error {
+        fn from(e: crate::Error) -> Self {
+            Self {
+                kind: e.kind(),
+                payload: Payload::Splay(e),
+            }
+        }
+    }
+
+    impl Display for Error {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match &self.payload {
+                Payload::Message(message) => f.write_str(message),
+                Payload::Splay(e) => e.fmt(f),
+            }
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    /// Like `std::io::Read`.
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf) {
+                    Ok(0) => break,
+                    Ok(n) => buf = &mut buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if buf.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+        }
+    }
+
+    /// Like `std::io::Write`.
+    pub trait Write {
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        fn flush(&mut self) -> Result<()>;
+
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ))
+                    }
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = self.len().min(buf.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+
+    impl Write for &mut [u8] {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let n = self.len().min(buf.len());
+            let (head, tail) = core::mem::take(self).split_at_mut(n);
+            head.copy_from_slice(&buf[..n]);
+            *self = tail;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Write for VecDeque<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
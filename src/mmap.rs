@@ -0,0 +1,22 @@
+//! Compressing files by mapping them into memory, instead of reading them piece by piece.
+
+use crate::{compress, Flavor};
+use std::fs::File;
+use std::io::{Result, Write};
+use std::path::Path;
+
+/// Like `compress`, but maps the file at `path` into memory and compresses the mapped bytes as a
+/// slice, which spares the read calls of going through the file. An empty file, which cannot be
+/// mapped, is compressed as the empty input.
+///
+/// As with any mapped file, the output is garbled if another process changes the file meanwhile,
+/// and the process gets SIGBUS if the file is truncated.
+pub fn compress_mmap<P: AsRef<Path>, W: Write>(flavor: Flavor, path: P, w: W) -> Result<()> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return compress(flavor, &[][..], w);
+    }
+    // SAFETY: The map is only ever read. Changes by other processes are documented above.
+    let map = unsafe { memmap2::Mmap::map(&file) }?;
+    compress(flavor, &map[..], w)
+}
@@ -0,0 +1,80 @@
+//! Helpers for looking at sample data, e.g. to decide how to seed a tree.
+
+/// Counts how often each byte value occurs in `sample`. An empty sample results in all zeros.
+pub fn histogram8(sample: &[u8]) -> [u64; 256] {
+    let mut counts = [0; 256];
+    for &symbol in sample {
+        counts[symbol as usize] += 1;
+    }
+    counts
+}
+
+/// Counts how often each 16-bit value occurs in `sample`. The result always has exactly 65536
+/// entries, indexed by symbol. It lives on the heap, because 512 KiB is too large for the stack.
+pub fn histogram16(sample: &[u16]) -> Vec<u64> {
+    let mut counts = vec![0; u16::MAX as usize + 1];
+    for &symbol in sample {
+        counts[symbol as usize] += 1;
+    }
+    counts
+}
+
+/// The Shannon entropy of the symbols that `counts` describes, e.g. from `histogram8`, in bits per
+/// symbol. That's the least that any coder needs which looks at each symbol on its own, without
+/// any context. Without any symbols at all, this is 0.
+pub fn entropy(counts: &[u64]) -> f64 {
+    let total = counts.iter().sum::<u64>() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| count as f64 / total)
+        // Written this way, a single symbol has an entropy of 0, and not -0.
+        .fold(0.0, |sum, p| sum + p * (1.0 / p).log2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram8() {
+        let counts = histogram8(b"aaab");
+        assert_eq!(counts[b'a' as usize], 3);
+        assert_eq!(counts[b'b' as usize], 1);
+        assert_eq!(counts.iter().sum::<u64>(), 4);
+    }
+
+    #[test]
+    fn test_histogram8_empty() {
+        assert_eq!(histogram8(&[]), [0; 256]);
+    }
+
+    #[test]
+    fn test_histogram16() {
+        let counts = histogram16(&[0x6161, 0x6161, 0x6162, 0xFFFF]);
+        assert_eq!(counts.len(), 65536);
+        assert_eq!(counts[0x6161], 2);
+        assert_eq!(counts[0x6162], 1);
+        assert_eq!(counts[0xFFFF], 1);
+        assert_eq!(counts.iter().sum::<u64>(), 4);
+    }
+
+    #[test]
+    fn test_histogram16_empty() {
+        let counts = histogram16(&[]);
+        assert_eq!(counts.len(), 65536);
+        assert!(counts.iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&histogram8(b"")), 0.0);
+        assert!(entropy(&histogram8(b"aaaa")).is_sign_positive());
+        assert_eq!(entropy(&histogram8(b"aaaa")), 0.0);
+        assert_eq!(entropy(&histogram8(b"abab")), 1.0);
+        assert_eq!(entropy(&histogram8(b"abcd")), 2.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&histogram8(&all)), 8.0);
+        assert_eq!(entropy(&histogram16(&[1, 2, 3, 4, 1, 2, 3, 4])), 2.0);
+    }
+}
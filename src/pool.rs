@@ -0,0 +1,167 @@
+//! Reusing trees across many small inputs: An `ArenaPool` keeps the `Arena8` of each finished
+//! input, and hands it out again, back in the uniform shape, instead of building a new one. This
+//! is meant for servers that compress many small messages concurrently, where the tree is a large
+//! part of the cost per message.
+//!
+//! The output is exactly that of `compress` and `decompress` with `Flavor::Symbol8`.
+
+use crate::splay::Arena8;
+use crate::symbol::{SymbolRead8, SymbolWrite8};
+use crate::{compress_raw, decompress_raw, DecompressOptions, Stats};
+use std::io::{Read, Result, Write};
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// A thread-safe stock of uniform trees, see the module documentation. It starts out empty, and
+/// keeps as many trees as were in use at the same time.
+#[derive(Debug, Default)]
+pub struct ArenaPool {
+    // Boxed, so that handing a tree out and taking it back only moves a pointer.
+    #[allow(clippy::vec_box)]
+    arenas: Mutex<Vec<Box<Arena8>>>,
+}
+
+impl ArenaPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A uniform tree, taken from the pool if there is one, and built otherwise. Dropping the
+    /// guard gives it back.
+    pub fn acquire(&self) -> PooledArena<'_> {
+        let arena = self.arenas.lock().unwrap().pop().unwrap_or_default();
+        PooledArena {
+            pool: self,
+            arena: Some(arena),
+        }
+    }
+
+    /// Resets `arena` and keeps it for a later `acquire`. This is what dropping a `PooledArena`
+    /// does, and it also takes trees that didn't come from the pool.
+    pub fn release(&self, mut arena: Box<Arena8>) {
+        // Reset outside the lock, so that other threads only ever wait for a push or a pop.
+        arena.reset();
+        self.arenas.lock().unwrap().push(arena);
+    }
+
+    /// How many trees are waiting in the pool, i.e. not currently acquired.
+    pub fn idle(&self) -> usize {
+        self.arenas.lock().unwrap().len()
+    }
+}
+
+/// A tree acquired from an `ArenaPool`, which goes back into the pool when this is dropped.
+#[derive(Debug)]
+pub struct PooledArena<'a> {
+    pool: &'a ArenaPool,
+    // Only `None` while dropping.
+    arena: Option<Box<Arena8>>,
+}
+
+impl PooledArena<'_> {
+    /// Takes the tree out of the pool for good, e.g. to keep it beyond the lifetime of the pool.
+    pub fn detach(mut self) -> Box<Arena8> {
+        self.arena.take().unwrap()
+    }
+}
+
+impl Deref for PooledArena<'_> {
+    type Target = Arena8;
+
+    fn deref(&self) -> &Arena8 {
+        self.arena.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledArena<'_> {
+    fn deref_mut(&mut self) -> &mut Arena8 {
+        self.arena.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledArena<'_> {
+    fn drop(&mut self) {
+        if let Some(arena) = self.arena.take() {
+            self.pool.release(arena);
+        }
+    }
+}
+
+/// Like `compress8`, but splays `arena` instead of a new uniform tree, and leaves it in whatever
+/// shape it ends up in. The output only decompresses normally if `arena` started out uniform.
+/// This doesn't allocate by itself, so it also works with a tree in a `static`.
+pub fn compress_with_arena<R: Read, W: Write>(arena: &mut Arena8, r: R, w: W) -> Result<Stats> {
+    compress_raw(arena, &mut SymbolRead8(r), w)
+}
+
+/// Like `decompress_with_options` with `Flavor::Symbol8`, but splays `arena` instead of a new
+/// uniform tree, and leaves it in whatever shape it ends up in. This doesn't allocate by itself.
+pub fn decompress_with_arena<R: Read, W: Write>(
+    arena: &mut Arena8,
+    r: R,
+    w: W,
+    options: &DecompressOptions,
+) -> Result<Stats> {
+    decompress_raw(arena, r, &mut SymbolWrite8(w), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, Flavor};
+    use std::thread;
+
+    #[test]
+    fn test_reacquire_is_uniform() {
+        let pool = ArenaPool::new();
+        let input = b"Hello, World! Hello, pool!";
+        let mut arena = pool.acquire();
+        assert_eq!(*arena, Arena8::new_uniform());
+        let mut compressed = Vec::new();
+        compress_with_arena(&mut arena, &input[..], &mut compressed).unwrap();
+        assert_ne!(*arena, Arena8::new_uniform());
+        let address: *const Arena8 = &*arena;
+        assert_eq!(pool.idle(), 0);
+        drop(arena);
+        assert_eq!(pool.idle(), 1);
+
+        let mut arena = pool.acquire();
+        assert_eq!(pool.idle(), 0);
+        // The same allocation, but back in the uniform shape.
+        assert_eq!(&*arena as *const Arena8, address);
+        assert_eq!(*arena, Arena8::new_uniform());
+        let mut decompressed = Vec::new();
+        let options = DecompressOptions::default();
+        decompress_with_arena(&mut arena, &compressed[..], &mut decompressed, &options).unwrap();
+        assert_eq!(decompressed, input);
+
+        let detached = arena.detach();
+        assert_eq!(pool.idle(), 0);
+        pool.release(detached);
+        assert_eq!(*pool.acquire(), Arena8::new_uniform());
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let pool = ArenaPool::new();
+        let inputs: Vec<Vec<u8>> = (0..8u8)
+            .map(|i| format!("message {i}: {}", "la".repeat(i.into())).into_bytes())
+            .collect();
+        thread::scope(|s| {
+            for input in &inputs {
+                let pool = &pool;
+                s.spawn(move || {
+                    for _ in 0..10 {
+                        let mut compressed = Vec::new();
+                        compress_with_arena(&mut pool.acquire(), &input[..], &mut compressed)
+                            .unwrap();
+                        let mut expected = Vec::new();
+                        compress(Flavor::Symbol8, &input[..], &mut expected).unwrap();
+                        assert_eq!(compressed, expected);
+                    }
+                });
+            }
+        });
+        assert!((1..=inputs.len()).contains(&pool.idle()));
+    }
+}
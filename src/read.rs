@@ -0,0 +1,203 @@
+//! Streaming adapters that implement `Read`.
+
+use crate::{DecompressOptions, DecompressState, Flavor, Stats};
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// How many compressed bytes are read from the inner reader at once. Each of them decodes to at
+/// most 8 symbols, which bounds the decompressed data that is buffered at any time.
+const MAX_CHUNK: usize = 4096;
+
+/// Decompresses a raw stream (without filemagic), as written by `compress` or
+/// `write::Compressor`, from the inner reader while it is being read. The output is identical to
+/// what `decompress_with_options` would produce for the same input.
+///
+/// With `DecompressOptions::max_output_bytes`, reading fails with `Error::LimitExceeded` once the
+/// output would grow beyond the limit. Everything up to the limit can still be read before that.
+/// After any error, the stream is broken, and further reads fail.
+pub struct Decompressor<R: Read> {
+    inner: R,
+    /// `None` once the input has ended, or after an error.
+    state: Option<DecompressState>,
+    /// Decompressed bytes, of which those from `pos` on haven't been read yet.
+    pending: Vec<u8>,
+    pos: usize,
+    /// An error that is returned once `pending` has been read.
+    error: Option<Error>,
+    failed: bool,
+    stats: Option<Stats>,
+}
+
+impl<R: Read> Decompressor<R> {
+    pub fn new(flavor: Flavor, inner: R) -> Self {
+        Self::with_options(flavor, inner, &DecompressOptions::default())
+    }
+
+    pub fn with_options(flavor: Flavor, inner: R, options: &DecompressOptions) -> Self {
+        Self {
+            inner,
+            state: Some(DecompressState::new(flavor, options)),
+            pending: Vec::new(),
+            pos: 0,
+            error: None,
+            failed: false,
+            stats: None,
+        }
+    }
+
+    /// The same `Stats` that `decompress_with_options` returns, once everything has been read.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads and decodes the next chunk of input into `pending`, which must be fully read.
+    fn fill(&mut self, state: &mut DecompressState) -> Result<bool> {
+        let mut input = [0; MAX_CHUNK];
+        let n = loop {
+            match self.inner.read(&mut input) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        if n == 0 {
+            return Ok(false);
+        }
+        self.pending.clear();
+        self.pos = 0;
+        state.push(&input[..n], &mut self.pending)?;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            if self.pos < self.pending.len() {
+                let n = buf.len().min(self.pending.len() - self.pos);
+                buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+                self.pos += n;
+                return Ok(n);
+            }
+            if let Some(e) = self.error.take() {
+                return Err(e);
+            }
+            if self.failed {
+                return Err(Error::other("cannot read after an error"));
+            }
+            let Some(mut state) = self.state.take() else {
+                return Ok(0);
+            };
+            self.pending.clear();
+            self.pos = 0;
+            match self.fill(&mut state) {
+                Ok(true) => self.state = Some(state),
+                Ok(false) => match state.finish() {
+                    Ok(stats) => self.stats = Some(stats),
+                    Err(e) => {
+                        self.failed = true;
+                        return Err(e);
+                    }
+                },
+                // The symbols before the error are fine, so hand them out first.
+                Err(e) => {
+                    self.failed = true;
+                    self.error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress, Error as SplayError};
+
+    const INPUT: &[u8] = b"However, this compression scheme is actually surprisingly good for short snippets of data, often beating zstd, lz4, and others.\n";
+
+    fn compressed(flavor: Flavor, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        compress(flavor, input, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_same_as_decompress() {
+        for flavor in [Flavor::Symbol8, Flavor::Symbol16BE, Flavor::SymbolI16LE] {
+            let compressed = compressed(flavor, INPUT);
+            let mut expected = Vec::new();
+            decompress(flavor, &compressed[..], &mut expected).unwrap();
+            let mut d = Decompressor::new(flavor, &compressed[..]);
+            // Small reads, so that most of them end in the middle of a chunk.
+            let mut decompressed = Vec::new();
+            let mut buf = [0; 7];
+            loop {
+                let n = d.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                decompressed.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(decompressed, expected, "{flavor:?}");
+            assert_eq!(d.stats().unwrap().bytes_out, INPUT.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_max_output_bytes() {
+        // Runs of a single byte take about a bit each, so this expands about eightfold.
+        let input = vec![0; 64 << 10];
+        let compressed = compressed(Flavor::Symbol8, &input);
+        assert!(compressed.len() < input.len() / 4, "{}", compressed.len());
+
+        let options = DecompressOptions {
+            max_output_bytes: Some(1000),
+            ..DecompressOptions::default()
+        };
+        let mut d = Decompressor::with_options(Flavor::Symbol8, &compressed[..], &options);
+        let mut decompressed = Vec::new();
+        let e = d.read_to_end(&mut decompressed).unwrap_err();
+        assert_eq!(
+            SplayError::from_io_error(&e),
+            Some(&SplayError::LimitExceeded { written: 1000 })
+        );
+        // Exactly up to the limit, and not a byte more.
+        assert_eq!(decompressed, &input[..1000]);
+        assert!(d.read(&mut [0; 16]).is_err());
+        assert_eq!(d.stats(), None);
+
+        let options = DecompressOptions {
+            max_output_bytes: Some(input.len() as u64),
+            ..DecompressOptions::default()
+        };
+        let mut d = Decompressor::with_options(Flavor::Symbol8, &compressed[..], &options);
+        let mut decompressed = Vec::new();
+        d.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, input);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let compressed = compressed(Flavor::Symbol16LE, INPUT);
+        let options = DecompressOptions {
+            strict: true,
+            expected_output_bytes: Some(INPUT.len() as u64),
+            ..DecompressOptions::default()
+        };
+        let truncated = &compressed[..compressed.len() / 2];
+        let mut d = Decompressor::with_options(Flavor::Symbol16LE, truncated, &options);
+        assert!(d.read_to_end(&mut Vec::new()).is_err());
+    }
+}
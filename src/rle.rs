@@ -0,0 +1,168 @@
+//! A tiny run-length pre-pass for the 8-bit flavor.
+//!
+//! The token stream consists of literal bytes, except that after two identical literals, the next
+//! token is the number of *additional* repetitions (0 to 255) of that byte. After such a count,
+//! the next token is a literal again, even if it equals the previous byte. For example,
+//! `b"aaaaaaaabc"` becomes `b"aa\x06bc"`, and `b"ab"` stays `b"ab"`. This means that data without
+//! runs is never expanded, and data with runs only needs one token per 257 repetitions.
+
+use crate::symbol::{SymbolRead, SymbolRead8, SymbolWrite};
+use std::io::{Read, Result};
+
+pub struct SymbolReadRle8<R: Read> {
+    inner: SymbolRead8<R>,
+    lookahead: Option<u8>,
+    prev: Option<u8>,
+    run_byte: Option<u8>,
+}
+
+impl<R: Read> SymbolReadRle8<R> {
+    pub fn new(r: R) -> Self {
+        Self {
+            inner: SymbolRead8(r),
+            lookahead: None,
+            prev: None,
+            run_byte: None,
+        }
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        match self.lookahead.take() {
+            Some(b) => Ok(Some(b)),
+            None => self.inner.read_one(),
+        }
+    }
+}
+
+impl<R: Read> SymbolRead<u8> for SymbolReadRle8<R> {
+    fn read_one(&mut self) -> Result<Option<u8>> {
+        if let Some(run_byte) = self.run_byte.take() {
+            let mut extra: u8 = 0;
+            while extra < u8::MAX {
+                match self.next_byte()? {
+                    Some(b) if b == run_byte => extra += 1,
+                    Some(b) => {
+                        self.lookahead = Some(b);
+                        break;
+                    }
+                    None => break,
+                }
+            }
+            self.prev = None;
+            return Ok(Some(extra));
+        }
+        let Some(b) = self.next_byte()? else {
+            return Ok(None);
+        };
+        if self.prev == Some(b) {
+            self.run_byte = Some(b);
+        } else {
+            self.prev = Some(b);
+        }
+        Ok(Some(b))
+    }
+
+    fn max_symbol(&self) -> u8 {
+        u8::MAX
+    }
+}
+
+pub struct SymbolWriteRle8<W: SymbolWrite<u8>> {
+    inner: W,
+    prev: Option<u8>,
+    run_byte: Option<u8>,
+}
+
+impl<W: SymbolWrite<u8>> SymbolWriteRle8<W> {
+    pub fn new(w: W) -> Self {
+        Self {
+            inner: w,
+            prev: None,
+            run_byte: None,
+        }
+    }
+}
+
+impl<W: SymbolWrite<u8>> SymbolWrite<u8> for SymbolWriteRle8<W> {
+    fn write_one(&mut self, token: u8) -> Result<()> {
+        if let Some(run_byte) = self.run_byte.take() {
+            for _ in 0..token {
+                self.inner.write_one(run_byte)?;
+            }
+            self.prev = None;
+            return Ok(());
+        }
+        self.inner.write_one(token)?;
+        if self.prev == Some(token) {
+            self.run_byte = Some(token);
+        } else {
+            self.prev = Some(token);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn max_symbol(&self) -> u8 {
+        u8::MAX
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol::SymbolWrite8;
+
+    fn tokens(input: &[u8]) -> Vec<u8> {
+        let mut r = SymbolReadRle8::new(input);
+        let mut tokens = Vec::new();
+        while let Some(token) = r.read_one().unwrap() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    fn expand(tokens: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut w = SymbolWriteRle8::new(SymbolWrite8(&mut buf));
+        for &token in tokens {
+            w.write_one(token).unwrap();
+        }
+        w.flush().unwrap();
+        buf
+    }
+
+    fn assert_tokens(input: &[u8], expected: &[u8]) {
+        assert_eq!(tokens(input), expected);
+        assert_eq!(expand(expected), input);
+    }
+
+    #[test]
+    fn test_no_runs() {
+        assert_tokens(b"", b"");
+        assert_tokens(b"a", b"a");
+        assert_tokens(b"abcab", b"abcab");
+    }
+
+    #[test]
+    fn test_runs() {
+        assert_tokens(b"aa", b"aa\x00");
+        assert_tokens(b"aaa", b"aa\x01");
+        assert_tokens(b"aaaaaaaabc", b"aa\x06bc");
+        assert_tokens(b"aaaaaaaabbbbbbbb", b"aa\x06bb\x06");
+        assert_tokens(b"abba", b"abb\x00a");
+    }
+
+    #[test]
+    fn test_long_run() {
+        assert_tokens(&[7; 257], b"\x07\x07\xff");
+        assert_tokens(&[7; 258], b"\x07\x07\xff\x07");
+        assert_tokens(&[7; 259], b"\x07\x07\xff\x07\x07\x00");
+        assert_tokens(
+            &[7; 1000],
+            b"\x07\x07\xff\x07\x07\xff\x07\x07\xff\x07\x07\xe3",
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Random access into the block container, via the checkpoint index that `compress_blocks` appends
+//! with `BlockOptions::index`. Each block starts over with a fresh tree, so each block is a
+//! checkpoint from which decompression can start, and reading from the middle of a large file
+//! only needs to decompress the one block that contains the position.
+
+use crate::block::{flavor_from_tag, read_index, TAG_END, TAG_INDEX};
+use crate::{decompress_with_options, DecompressOptions, Error, MAGIC_FORMAT_BLOCKS, MAGIC_LEN};
+use std::io::{self, ErrorKind, Read, Result, Seek, SeekFrom};
+
+/// Decompresses a block container with a checkpoint index, and implements `Read` and `Seek` on the
+/// decompressed data. Only the block that contains the current position is held in memory.
+///
+/// The container must be the last thing in `inner`, since the index is found from the end. It
+/// may start anywhere, though: The position of `inner` when calling `new` is taken as its start.
+#[derive(Debug)]
+pub struct SeekableDecompressor<R> {
+    inner: R,
+    /// Where the container starts in `inner`, since the index counts from there.
+    start: u64,
+    /// For each block, where it starts in the container and in the decompressed data, followed
+    /// by the end of both, see `block::read_index`.
+    index: Vec<(u64, u64)>,
+    /// Position in the decompressed data.
+    pos: u64,
+    /// The block that was decompressed last, by number, since reads are usually sequential.
+    current: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableDecompressor<R> {
+    /// Reads the filemagic and the index, but no blocks yet. Fails with `Error::UnknownMagic` if
+    /// `inner` is not a block container, and with `Error::InvalidIndex` if it has no index.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let start = inner.stream_position()?;
+        let mut magic = [0; MAGIC_LEN];
+        match inner.read_exact(&mut magic) {
+            Ok(()) if magic == MAGIC_FORMAT_BLOCKS => {}
+            Err(e) if e.kind() != ErrorKind::UnexpectedEof => return Err(e),
+            _ => return Err(Error::UnknownMagic.into()),
+        }
+        // The container ends with the length of the index entries and the end tag, and the index
+        // tag and the first copy of the length come before the entries.
+        let end = inner.seek(SeekFrom::End(0))?;
+        if end < start + MAGIC_LEN as u64 + 10 {
+            return Err(Error::InvalidIndex.into());
+        }
+        let mut trailer = [0; 5];
+        inner.seek(SeekFrom::Start(end - 5))?;
+        inner.read_exact(&mut trailer)?;
+        let len = u32::from_be_bytes(trailer[..4].try_into().unwrap()) as u64;
+        if trailer[4] != TAG_END || end < start + MAGIC_LEN as u64 + 10 + len {
+            return Err(Error::InvalidIndex.into());
+        }
+        let index_pos = end - 10 - len;
+        inner.seek(SeekFrom::Start(index_pos))?;
+        let mut tag = [0; 1];
+        inner.read_exact(&mut tag)?;
+        if tag[0] != TAG_INDEX {
+            return Err(Error::InvalidIndex.into());
+        }
+        let index = match read_index(&mut inner) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(Error::InvalidIndex.into()),
+            result => result,
+        }?;
+        // The final entry points at the index itself.
+        if index.last().unwrap().0 != index_pos - start {
+            return Err(Error::InvalidIndex.into());
+        }
+        Ok(Self {
+            inner,
+            start,
+            index,
+            pos: 0,
+            current: None,
+        })
+    }
+
+    /// Length of the decompressed data.
+    pub fn len(&self) -> u64 {
+        self.index.last().unwrap().1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of checkpoints, i.e. of blocks.
+    pub fn checkpoints(&self) -> usize {
+        self.index.len() - 1
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Decompresses block `block`, unless that already happened, and checks it against the index.
+    fn load(&mut self, block: usize) -> Result<&[u8]> {
+        if self
+            .current
+            .as_ref()
+            .is_none_or(|(current, _)| *current != block)
+        {
+            self.current = None;
+            let (offset, plain_offset) = self.index[block];
+            let (next_offset, next_plain_offset) = self.index[block + 1];
+            let plain_len = next_plain_offset - plain_offset;
+            self.inner.seek(SeekFrom::Start(self.start + offset))?;
+            let mut header = [0; 9];
+            self.inner.read_exact(&mut header)?;
+            let flavor = flavor_from_tag(header[0]).ok_or(Error::InvalidIndex)?;
+            let header_plain_len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as u64;
+            let compressed_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as u64;
+            if header_plain_len != plain_len || offset + 9 + compressed_len != next_offset {
+                return Err(Error::InvalidIndex.into());
+            }
+            let options = DecompressOptions {
+                max_output_bytes: Some(plain_len),
+                expected_output_bytes: Some(plain_len),
+                strict: true,
+                ..DecompressOptions::default()
+            };
+            let mut data = Vec::with_capacity(plain_len as usize);
+            let r = (&mut self.inner).take(compressed_len);
+            decompress_with_options(flavor, r, &mut data, &options)?;
+            self.current = Some((block, data));
+        }
+        Ok(&self.current.as_ref().unwrap().1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableDecompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.pos >= self.len() || buf.is_empty() {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        let block = self
+            .index
+            .partition_point(|&(_, plain_offset)| plain_offset <= pos)
+            - 1;
+        let skip = (pos - self.index[block].1) as usize;
+        let data = &self.load(block)?[skip..];
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// Seeking is cheap, the work happens when reading. Like for files, seeking beyond the end is
+/// allowed, and reading there yields nothing.
+impl<R: Read + Seek> Seek for SeekableDecompressor<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_blocks, decompress_blocks, BlockOptions, Flavor};
+    use std::io::Cursor;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| b"seekable checkpoints\n"[i % 21] ^ (i / 1000) as u8)
+            .collect()
+    }
+
+    fn indexed(input: &[u8], block_size: usize) -> Vec<u8> {
+        let options = BlockOptions {
+            block_size,
+            index: true,
+            ..BlockOptions::default()
+        };
+        let mut compressed = Vec::new();
+        compress_blocks(Flavor::Symbol8, input, &mut compressed, &options).unwrap();
+        compressed
+    }
+
+    #[test]
+    fn test_read_from_middle() {
+        let input = sample(10_000);
+        let compressed = indexed(&input, 1000);
+        let mut seekable = SeekableDecompressor::new(Cursor::new(&compressed)).unwrap();
+        assert_eq!(seekable.len(), 10_000);
+        assert_eq!(seekable.checkpoints(), 10);
+        for (pos, len) in [(4500, 100), (999, 2), (0, 10_000), (3000, 2500), (9990, 10)] {
+            seekable.seek(SeekFrom::Start(pos)).unwrap();
+            let mut buf = vec![0; len];
+            seekable.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, &input[pos as usize..][..len], "{pos}");
+        }
+        assert_eq!(seekable.read(&mut [0; 10]).unwrap(), 0);
+        assert_eq!(seekable.seek(SeekFrom::End(-5)).unwrap(), 9995);
+        let mut rest = Vec::new();
+        seekable.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &input[9995..]);
+        assert!(seekable.seek(SeekFrom::Current(-20_000)).is_err());
+    }
+
+    #[test]
+    fn test_index_is_skipped() {
+        let input = sample(2500);
+        let compressed = indexed(&input, 1000);
+        let mut decompressed = Vec::new();
+        decompress_blocks(&compressed[..], &mut decompressed, &Default::default()).unwrap();
+        assert_eq!(decompressed, input);
+
+        // Apart from the index, the output is the same.
+        let mut plain = Vec::new();
+        let options = BlockOptions {
+            block_size: 1000,
+            ..BlockOptions::default()
+        };
+        compress_blocks(Flavor::Symbol8, &input[..], &mut plain, &options).unwrap();
+        assert_eq!(compressed[..plain.len() - 1], plain[..plain.len() - 1]);
+        assert_eq!(compressed.len(), plain.len() + 9 + 4 * 16);
+    }
+
+    #[test]
+    fn test_empty() {
+        let compressed = indexed(b"", 1000);
+        let mut seekable = SeekableDecompressor::new(Cursor::new(&compressed)).unwrap();
+        assert!(seekable.is_empty());
+        assert_eq!(seekable.checkpoints(), 0);
+        assert_eq!(seekable.read(&mut [0; 10]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_starts_midway() {
+        let input = sample(3000);
+        let mut file = b"some prefix".to_vec();
+        file.extend(indexed(&input, 1000));
+        let mut cursor = Cursor::new(&file);
+        cursor.seek(SeekFrom::Start(11)).unwrap();
+        let mut seekable = SeekableDecompressor::new(cursor).unwrap();
+        seekable.seek(SeekFrom::Start(1500)).unwrap();
+        let mut buf = [0; 1000];
+        seekable.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, input[1500..2500]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let input = sample(3000);
+        let options = BlockOptions {
+            block_size: 1000,
+            ..BlockOptions::default()
+        };
+        let mut unindexed = Vec::new();
+        compress_blocks(Flavor::Symbol8, &input[..], &mut unindexed, &options).unwrap();
+        let compressed = indexed(&input, 1000);
+        for (data, expected) in [
+            (&b"not compressed at all"[..], Error::UnknownMagic),
+            (&unindexed, Error::InvalidIndex),
+            (&compressed[..compressed.len() - 1], Error::InvalidIndex),
+        ] {
+            let err = SeekableDecompressor::new(Cursor::new(data)).unwrap_err();
+            assert_eq!(Error::from_io_error(&err), Some(&expected));
+        }
+
+        // Entries are only checked against the blocks when they are needed. This is the last
+        // byte of where the second block starts, which also breaks the first one.
+        let mut wrong_offset = compressed.clone();
+        wrong_offset[compressed.len() - 5 - 3 * 16 + 7] += 1;
+        let mut seekable = SeekableDecompressor::new(Cursor::new(&wrong_offset)).unwrap();
+        let mut buf = [0; 10];
+        seekable.seek(SeekFrom::Start(2500)).unwrap();
+        seekable.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, input[2500..2510]);
+        seekable.seek(SeekFrom::Start(1500)).unwrap();
+        let err = seekable.read_exact(&mut buf).unwrap_err();
+        assert_eq!(Error::from_io_error(&err), Some(&Error::InvalidIndex));
+    }
+}
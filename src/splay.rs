@@ -1,62 +1,178 @@
 use crate::common::{Direction, Node, NodeRef};
-use std::array::from_fn;
-use std::cmp::PartialOrd;
-use std::fmt::Debug;
+use alloc::vec::Vec;
+use core::cmp::PartialOrd;
+use core::fmt::{self, Debug, Display, Formatter};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::Hash;
 
+/// Misuse of a `Splayable` that would otherwise panic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WalkError {
+    /// Tried to go further down, but the current node is already a leaf.
+    DescendOnLeaf,
+    /// Tried to splay the parent of a leaf, but the current node is not a leaf.
+    NotOnLeaf,
+    /// Tried to splay the parent of a leaf, but the leaf has no parent, i.e. it is the root.
+    NoParent,
+}
+
+impl Display for WalkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WalkError::DescendOnLeaf => write!(f, "tried to descend on leaf"),
+            WalkError::NotOnLeaf => write!(f, "tried to splay parent of non-leaf"),
+            WalkError::NoParent => write!(f, "tried to splay parent of root"),
+        }
+    }
+}
+
+impl core::error::Error for WalkError {}
+
+/// Why `NodeArena::validate` rejected a tree. Each internal node must lie within the range of
+/// symbols that its position in the tree covers, and each leaf must be the only symbol in its
+/// range.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InconsistencyError<T> {
+    /// The internal node `node` is outside of the range it covers, or at its very end, which
+    /// leaves nothing for its right arm.
+    Internal {
+        node: T,
+        cover_min: T,
+        cover_max_incl: T,
+    },
+    /// The leaf below the internal node `parent` is not the only symbol in the range it covers.
+    Leaf {
+        parent: T,
+        leaf: T,
+        cover_min: T,
+        cover_max_incl: T,
+    },
+}
+
+impl<T: Debug> Display for InconsistencyError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InconsistencyError::Internal {
+                node,
+                cover_min,
+                cover_max_incl,
+            } => write!(
+                f,
+                "internal node {node:?} is outside of its range {cover_min:?}..={cover_max_incl:?}"
+            ),
+            InconsistencyError::Leaf {
+                parent,
+                leaf,
+                cover_min,
+                cover_max_incl,
+            } => write!(
+                f,
+                "leaf {leaf:?} below internal node {parent:?} should cover exactly {cover_min:?}..={cover_max_incl:?}"
+            ),
+        }
+    }
+}
+
+impl<T: Debug> core::error::Error for InconsistencyError<T> {}
+
+/// A tree over all symbols of type `T`, stored as its internal nodes. `splayable_mut` walks it.
 pub trait NodeArena<T: Clone + Copy + Debug + Eq + PartialEq>: Debug {
-    fn node(&self, internal_id: T) -> &Node<T>;
+    fn node(&self, internal_id: T) -> Node<T>;
     fn node_mut(&mut self, internal_id: T) -> &mut Node<T>;
     fn root_idx(&self) -> NodeRef<T>;
     fn root_idx_mut(&mut self) -> &mut T;
     fn ref_internal(&self, internal_id: T) -> NodeRef<T>;
 
-    fn is_consistent(&self) -> bool;
-    // TODO: 'incr' is an ugly wart, but sadly there's just no good way to express the concept "u8 or u16".
-    fn incr(&self, v: T) -> T;
+    /// Checks that the tree is a valid search tree over all symbols, see `InconsistencyError`.
+    fn validate(&self) -> Result<(), InconsistencyError<T>>;
+    fn is_consistent(&self) -> bool {
+        self.validate().is_ok()
+    }
+    // TODO: 'checked_incr' is an ugly wart, but sadly there's just no good way to express the concept "u8 or u16".
+    /// Returns `v + 1`, or `None` if `v` is already the largest value of `T`. Note that the
+    /// largest value can only ever be a leaf, never an internal node: Internal node `v` separates
+    /// the symbols up to and including `v` from those above it, and there is nothing above the
+    /// largest value. That's also why there is one internal node less than there are symbols. So
+    /// in a consistent tree, this never returns `None` for an internal node, and `validate`
+    /// reports it as an inconsistency instead of wrapping around.
+    fn checked_incr(&self, v: T) -> Option<T>;
+    /// The largest symbol, i.e. the rightmost leaf. Used to check that the arena agrees with the
+    /// symbols it is fed.
+    fn max_symbol(&self) -> T;
 
-    fn is_subtree_consistent(&self, root_index: T, cover_min: T, cover_max_incl: T) -> bool
+    /// Checks that the internal node `root_index` and everything below it covers exactly the
+    /// symbols from `cover_min` to `cover_max_incl`, and that the nodes are in order. Works
+    /// without recursion, since a degenerate tree can be as deep as there are symbols. At most one
+    /// node per level is pending at a time, so this doesn't allocate for an `Arena8` either.
+    fn validate_subtree(
+        &self,
+        root_index: T,
+        cover_min: T,
+        cover_max_incl: T,
+    ) -> Result<(), InconsistencyError<T>>
     where
         T: PartialOrd,
     {
-        let node = &self.node(root_index);
-        // eprintln!("ENTER internal node {root_index}={node:?} cover_min={cover_min}, cover_max_incl={cover_max_incl}");
-        let index_consistent = cover_min <= root_index && root_index < cover_max_incl;
-        if !index_consistent {
-            eprintln!(
-                "internal node {root_index:?} not consistent: cover_min={cover_min:?}, cover_max_incl={cover_max_incl:?}"
-            );
-        }
-        let left_consistent = self.is_arm_consistent(&node.left, cover_min, root_index);
-        let right_consistent =
-            self.is_arm_consistent(&node.right, self.incr(root_index), cover_max_incl);
-        if !left_consistent || !right_consistent {
-            eprintln!(
-                "internal node {root_index:?} has inconsistent arms: cover_min={cover_min:?}, cover_max_incl={cover_max_incl:?}"
-            );
+        // Internal nodes that still need checking, each with the range it must cover.
+        let mut pending = InlineStack::new();
+        pending.push((root_index, cover_min, cover_max_incl));
+        while let Some((index, cover_min, cover_max_incl)) = pending.pop() {
+            // Check the range before looking up the node, since an out-of-range ID might not even
+            // exist in the arena, like internal node 255 in `Arena8`. Checking the index first
+            // also guarantees that 'index < cover_max_incl', so the increment can't overflow.
+            // Custom arenas might still hand us nonsense, so don't rely on it.
+            let right_min = match self.checked_incr(index) {
+                Some(right_min) if cover_min <= index && index < cover_max_incl => right_min,
+                _ => {
+                    return Err(InconsistencyError::Internal {
+                        node: index,
+                        cover_min,
+                        cover_max_incl,
+                    })
+                }
+            };
+            let node = self.node(index);
+            self.validate_arm(index, node.left, cover_min, index, &mut pending)?;
+            self.validate_arm(index, node.right, right_min, cover_max_incl, &mut pending)?;
         }
-        //eprintln!("EXIT internal node {root_index} cover_min={cover_min}, cover_max_incl={cover_max_incl}");
-        index_consistent && left_consistent && right_consistent
+        Ok(())
     }
 
-    fn is_arm_consistent(&self, root: &NodeRef<T>, cover_min: T, cover_max_incl: T) -> bool
-    where
-        T: PartialOrd,
-    {
-        if let Some(child_index) = root.as_internal() {
-            return self.is_subtree_consistent(child_index, cover_min, cover_max_incl);
-        }
-        if let Some(leaf_index) = root.as_leaf() {
-            return cover_min == leaf_index && leaf_index == cover_max_incl;
+    /// Checks a leaf right away, and adds an internal node to `pending`, see `validate_subtree`.
+    fn validate_arm(
+        &self,
+        parent: T,
+        arm: NodeRef<T>,
+        cover_min: T,
+        cover_max_incl: T,
+        pending: &mut InlineStack<(T, T, T)>,
+    ) -> Result<(), InconsistencyError<T>> {
+        match arm {
+            NodeRef::Internal(child) => pending.push((child, cover_min, cover_max_incl)),
+            NodeRef::Leaf(leaf) if cover_min == leaf && leaf == cover_max_incl => {}
+            NodeRef::Leaf(leaf) => {
+                return Err(InconsistencyError::Leaf {
+                    parent,
+                    leaf,
+                    cover_min,
+                    cover_max_incl,
+                })
+            }
         }
-        panic!("empty child?!")
+        Ok(())
     }
 
+    /// A walker that starts at the root.
     fn splayable_mut(&mut self) -> Splayable<'_, T, Self> {
         Splayable::new(self)
     }
 }
 
-#[derive(Debug)]
+/// The tree over all `u8` symbols, as used by `Flavor::Symbol8`. It is small enough to live on the
+/// stack, and has no allocations of its own.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Arena8 {
     // Exploit the fact that "255" is such a small number, and try to fit all data on the stack.
     internal_nodes: [Node<u8>; u8::MAX as usize],
@@ -66,35 +182,107 @@ pub struct Arena8 {
 }
 
 impl Arena8 {
-    pub fn new_uniform() -> Self {
-        let nodes: [Node<u8>; u8::MAX as usize] = from_fn(|i| {
-            let level = i.trailing_ones();
-            assert!(level < u8::BITS);
-            let ibu = i as u8;
-            if level == 0 {
+    /// The balanced tree, in which every symbol takes exactly 8 bits. This is a `const fn`, so that
+    /// the tree can be placed in a `static`.
+    pub const fn new_uniform() -> Self {
+        let leaf = NodeRef::Leaf(0);
+        let mut internal_nodes = [Node {
+            left: leaf,
+            right: leaf,
+        }; u8::MAX as usize];
+        let mut id = 0;
+        while id < u8::MAX {
+            let level = id.trailing_ones();
+            internal_nodes[id as usize] = if level == 0 {
                 Node {
-                    left: NodeRef::new_leaf(ibu),
-                    right: NodeRef::new_leaf(ibu + 1),
+                    left: NodeRef::Leaf(id),
+                    right: NodeRef::Leaf(id + 1),
                 }
             } else {
-                let masked = ibu & !(1 << (level - 1));
+                let masked = id & !(1 << (level - 1));
                 let added_bit = 1 << level;
                 Node {
-                    left: NodeRef::new_internal(masked, u8::MAX),
-                    right: NodeRef::new_internal(masked | added_bit, u8::MAX),
+                    left: NodeRef::Internal(masked),
+                    right: NodeRef::Internal(masked | added_bit),
                 }
-            }
-        });
+            };
+            id += 1;
+        }
         Self {
-            internal_nodes: nodes,
+            internal_nodes,
             root: u8::MAX / 2,
         }
     }
+
+    /// Puts the tree back into the shape of `new_uniform`, in place, so that it can be reused for
+    /// the next input instead of building a new one.
+    pub fn reset(&mut self) {
+        *self = Self::new_uniform();
+    }
+
+    /// Builds the tree with the internal nodes `nodes` and the root `root`, where `nodes[i]` is
+    /// internal node `i`. This sets up a specific shape directly, e.g. a degenerate chain, instead
+    /// of splaying the uniform tree into it.
+    pub fn from_nodes(
+        nodes: [Node<u8>; u8::MAX as usize],
+        root: u8,
+    ) -> Result<Self, InconsistencyError<u8>> {
+        let arena = Self {
+            internal_nodes: nodes,
+            root,
+        };
+        arena.validate()?;
+        Ok(arena)
+    }
+
+    /// Length of the output of `to_bytes`.
+    pub const SERIALIZED_LEN: usize = 1 + 4 * u8::MAX as usize;
+
+    /// Serializes the shape of the tree: The root, followed by both arms of each internal node,
+    /// each as a tag byte (0 for a leaf, 1 for an internal node) and the value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SERIALIZED_LEN);
+        bytes.push(self.root);
+        for node in &self.internal_nodes {
+            for arm in [node.left, node.right] {
+                match arm {
+                    NodeRef::Leaf(v) => bytes.extend_from_slice(&[0, v]),
+                    NodeRef::Internal(v) => bytes.extend_from_slice(&[1, v]),
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Reverses `to_bytes`. Returns `None` if `bytes` doesn't describe a consistent tree.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::SERIALIZED_LEN {
+            return None;
+        }
+        let arm = |offset: usize| match bytes[offset..offset + 2] {
+            [0, v] => Some(NodeRef::new_leaf(v)),
+            [1, v] if v != u8::MAX => Some(NodeRef::new_internal(v, u8::MAX)),
+            _ => None,
+        };
+        let mut internal_nodes = Self::new_uniform().internal_nodes;
+        for (i, node) in internal_nodes.iter_mut().enumerate() {
+            node.left = arm(1 + 4 * i)?;
+            node.right = arm(3 + 4 * i)?;
+        }
+        Self::from_nodes(internal_nodes, bytes[0]).ok()
+    }
+}
+
+/// The same as `Arena8::new_uniform`.
+impl Default for Arena8 {
+    fn default() -> Self {
+        Self::new_uniform()
+    }
 }
 
 impl NodeArena<u8> for Arena8 {
-    fn node(&self, internal_id: u8) -> &Node<u8> {
-        &self.internal_nodes[internal_id as usize]
+    fn node(&self, internal_id: u8) -> Node<u8> {
+        self.internal_nodes[internal_id as usize]
     }
 
     fn node_mut(&mut self, internal_id: u8) -> &mut Node<u8> {
@@ -113,16 +301,42 @@ impl NodeArena<u8> for Arena8 {
         NodeRef::new_internal(internal_id, u8::MAX)
     }
 
-    fn incr(&self, v: u8) -> u8 {
-        v + 1
+    fn checked_incr(&self, v: u8) -> Option<u8> {
+        v.checked_add(1)
     }
 
-    fn is_consistent(&self) -> bool {
-        self.is_subtree_consistent(self.root, 0, u8::MAX)
+    fn max_symbol(&self) -> u8 {
+        u8::MAX
+    }
+
+    fn validate(&self) -> Result<(), InconsistencyError<u8>> {
+        self.validate_subtree(self.root, 0, u8::MAX)
     }
 }
 
-#[derive(Debug)]
+/// Internal node `id` of the uniform tree over all `u16` symbols, i.e. of `Arena16::new_uniform`.
+/// Its level is the number of trailing ones, so e.g. all even nodes are just above two leaves.
+fn uniform_node16(id: u16) -> Node<u16> {
+    let level = id.trailing_ones();
+    assert!(level < u16::BITS);
+    if level == 0 {
+        Node {
+            left: NodeRef::new_leaf(id),
+            right: NodeRef::new_leaf(id + 1),
+        }
+    } else {
+        let masked = id & !(1 << (level - 1));
+        let added_bit = 1 << level;
+        Node {
+            left: NodeRef::new_internal(masked, u16::MAX),
+            right: NodeRef::new_internal(masked | added_bit, u16::MAX),
+        }
+    }
+}
+
+/// The tree over all `u16` symbols, as used by `Flavor::Symbol16BE` and `Flavor::Symbol16LE`.
+/// Its nodes live on the heap, since there are too many for the stack.
+#[derive(Debug, Eq, PartialEq)]
 pub struct Arena16 {
     // Sadly, a [Node<u16>; u16::MAX] would be 255.9 KiB, which is too large for the stack. Therefore, allocate it on the heap.
     internal_nodes: Vec<Node<u16>>,
@@ -132,37 +346,40 @@ pub struct Arena16 {
 }
 
 impl Arena16 {
+    /// The balanced tree, in which every symbol takes exactly 16 bits.
     pub fn new_uniform() -> Self {
-        let mut nodes = Vec::with_capacity(u16::MAX as usize);
-        for i in 0..u16::MAX as usize {
-            let level = i.trailing_ones();
-            assert!(level < u16::BITS);
-            let ibu = i as u16;
-            let to_add = if level == 0 {
-                Node {
-                    left: NodeRef::new_leaf(ibu),
-                    right: NodeRef::new_leaf(ibu + 1),
-                }
-            } else {
-                let masked = ibu & !(1 << (level - 1));
-                let added_bit = 1 << level;
-                Node {
-                    left: NodeRef::new_internal(masked, u16::MAX),
-                    right: NodeRef::new_internal(masked | added_bit, u16::MAX),
-                }
-            };
-            nodes.push(to_add);
-        }
         Self {
-            internal_nodes: nodes,
+            internal_nodes: (0..u16::MAX).map(uniform_node16).collect(),
             root: u16::MAX / 2,
         }
     }
+
+    /// Like `Arena8::from_nodes`.
+    ///
+    /// # Panics
+    ///
+    /// If there aren't exactly `u16::MAX` nodes.
+    pub fn from_nodes(nodes: Vec<Node<u16>>, root: u16) -> Result<Self, InconsistencyError<u16>> {
+        assert_eq!(nodes.len(), u16::MAX as usize, "wrong number of nodes");
+        let arena = Self {
+            internal_nodes: nodes,
+            root,
+        };
+        arena.validate()?;
+        Ok(arena)
+    }
+}
+
+/// The same as `Arena16::new_uniform`.
+impl Default for Arena16 {
+    fn default() -> Self {
+        Self::new_uniform()
+    }
 }
 
 impl NodeArena<u16> for Arena16 {
-    fn node(&self, internal_id: u16) -> &Node<u16> {
-        &self.internal_nodes[internal_id as usize]
+    fn node(&self, internal_id: u16) -> Node<u16> {
+        self.internal_nodes[internal_id as usize]
     }
 
     fn node_mut(&mut self, internal_id: u16) -> &mut Node<u16> {
@@ -181,20 +398,359 @@ impl NodeArena<u16> for Arena16 {
         NodeRef::new_internal(internal_id, u16::MAX)
     }
 
-    fn incr(&self, v: u16) -> u16 {
-        v + 1
+    fn checked_incr(&self, v: u16) -> Option<u16> {
+        v.checked_add(1)
     }
 
-    fn is_consistent(&self) -> bool {
-        self.is_subtree_consistent(self.root, 0, u16::MAX)
+    fn max_symbol(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn validate(&self) -> Result<(), InconsistencyError<u16>> {
+        self.validate_subtree(self.root, 0, u16::MAX)
+    }
+}
+
+/// Maps the unsigned 16-bit space onto the signed one while preserving the order, i.e. 0 becomes
+/// `i16::MIN` and `u16::MAX` becomes `i16::MAX`.
+fn bias(v: u16) -> i16 {
+    (v ^ 0x8000) as i16
+}
+
+fn unbias(v: i16) -> u16 {
+    v as u16 ^ 0x8000
+}
+
+/// Internal node `id` of the uniform tree over all `i16` symbols. Since the bias preserves the
+/// order, relabeling the uniform unsigned tree yields the uniform signed tree.
+fn uniform_node_i16(id: i16) -> Node<i16> {
+    let bias_ref = |r: NodeRef<u16>| match r {
+        NodeRef::Internal(v) => NodeRef::new_internal(bias(v), i16::MAX),
+        NodeRef::Leaf(v) => NodeRef::new_leaf(bias(v)),
+    };
+    let node = uniform_node16(unbias(id));
+    Node {
+        left: bias_ref(node.left),
+        right: bias_ref(node.right),
+    }
+}
+
+/// Like `Arena16`, but for signed symbols: The tree is ordered by the signed value, so that e.g.
+/// -1 and 0 are neighbors, which matters for streams of small deltas.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ArenaI16 {
+    // Indexed by the unbiased internal ID, so the layout is the same as in `Arena16`.
+    internal_nodes: Vec<Node<i16>>,
+    // A leaf is always "right before" its corresponding internal node, if any.
+    // That must be this way around, because there is a leaf 32767 but no internal node 32767.
+    root: i16,
+}
+
+impl ArenaI16 {
+    pub fn new_uniform() -> Self {
+        Self {
+            internal_nodes: (0..u16::MAX).map(|i| uniform_node_i16(bias(i))).collect(),
+            root: bias(u16::MAX / 2),
+        }
+    }
+}
+
+/// The same as `ArenaI16::new_uniform`.
+impl Default for ArenaI16 {
+    fn default() -> Self {
+        Self::new_uniform()
     }
 }
 
+impl NodeArena<i16> for ArenaI16 {
+    fn node(&self, internal_id: i16) -> Node<i16> {
+        self.internal_nodes[unbias(internal_id) as usize]
+    }
+
+    fn node_mut(&mut self, internal_id: i16) -> &mut Node<i16> {
+        &mut self.internal_nodes[unbias(internal_id) as usize]
+    }
+
+    fn root_idx(&self) -> NodeRef<i16> {
+        NodeRef::new_internal(self.root, i16::MAX)
+    }
+
+    fn root_idx_mut(&mut self) -> &mut i16 {
+        &mut self.root
+    }
+
+    fn ref_internal(&self, internal_id: i16) -> NodeRef<i16> {
+        NodeRef::new_internal(internal_id, i16::MAX)
+    }
+
+    fn checked_incr(&self, v: i16) -> Option<i16> {
+        v.checked_add(1)
+    }
+
+    fn max_symbol(&self) -> i16 {
+        i16::MAX
+    }
+
+    fn validate(&self) -> Result<(), InconsistencyError<i16>> {
+        self.validate_subtree(self.root, i16::MIN, i16::MAX)
+    }
+}
+
+/// Like `Arena16` or `ArenaI16`, but builds the tree lazily: It only stores the internal nodes
+/// that splaying touched, and derives all others from the uniform tree on the fly. That saves
+/// memory when only few distinct symbols occur, at the cost of a hash lookup per step.
+///
+/// Once more than `capacity` nodes are stored, the least recently touched ones that are the same
+/// as in the uniform tree are dropped again, since they can be derived anytime. Nodes that differ
+/// are never dropped, so this never changes the tree, but it also means that `capacity` is only a
+/// soft limit: The store holds at most `capacity` nodes, or twice as many as differ from the
+/// uniform tree, whichever is more.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct LazyArena<T: Clone + Copy + Debug + Eq + PartialEq> {
+    /// Each stored internal node, with the value of `clock` when it was last handed out mutably.
+    nodes: HashMap<T, (Node<T>, u64)>,
+    uniform: fn(T) -> Node<T>,
+    root: T,
+    capacity: usize,
+    /// Store size at which to look for nodes to drop. Raised when too few of them can be dropped,
+    /// so that a store full of changed nodes isn't scanned on every step.
+    next_eviction: usize,
+    clock: u64,
+}
+
+#[cfg(feature = "std")]
+impl LazyArena<u16> {
+    /// The uniform tree, like `Arena16::new_uniform`, storing about `capacity` nodes.
+    pub fn new_uniform(capacity: usize) -> Self {
+        Self::with_uniform(uniform_node16, u16::MAX / 2, capacity)
+    }
+}
+
+#[cfg(feature = "std")]
+impl LazyArena<i16> {
+    /// The uniform tree, like `ArenaI16::new_uniform`, storing about `capacity` nodes.
+    pub fn new_uniform(capacity: usize) -> Self {
+        Self::with_uniform(uniform_node_i16, bias(u16::MAX / 2), capacity)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Clone + Copy + Debug + Eq + PartialEq + Hash + Ord> LazyArena<T> {
+    fn with_uniform(uniform: fn(T) -> Node<T>, root: T, capacity: usize) -> Self {
+        Self {
+            nodes: HashMap::new(),
+            uniform,
+            root,
+            capacity,
+            next_eviction: capacity,
+            clock: 0,
+        }
+    }
+
+    /// How many internal nodes are currently stored.
+    #[cfg(test)]
+    pub fn stored_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn get(&self, internal_id: T) -> Node<T> {
+        match self.nodes.get(&internal_id) {
+            Some(&(node, _)) => node,
+            None => (self.uniform)(internal_id),
+        }
+    }
+
+    fn get_mut(&mut self, internal_id: T) -> &mut Node<T> {
+        if self.nodes.len() >= self.next_eviction && !self.nodes.contains_key(&internal_id) {
+            self.evict();
+        }
+        self.clock += 1;
+        let (uniform, clock) = (self.uniform, self.clock);
+        let entry = self
+            .nodes
+            .entry(internal_id)
+            .or_insert_with(|| (uniform(internal_id), clock));
+        entry.1 = clock;
+        &mut entry.0
+    }
+
+    /// Drops the least recently touched nodes that are the same as in the uniform tree, until the
+    /// store is down to half of `capacity`, or there are none left to drop.
+    fn evict(&mut self) {
+        let mut droppable: Vec<(u64, T)> = self
+            .nodes
+            .iter()
+            .filter(|&(&id, &(node, _))| node == (self.uniform)(id))
+            .map(|(&id, &(_, touched))| (touched, id))
+            .collect();
+        droppable.sort_unstable();
+        let excess = self.nodes.len().saturating_sub(self.capacity / 2);
+        for &(_, id) in droppable.iter().take(excess) {
+            self.nodes.remove(&id);
+        }
+        self.next_eviction = self.capacity.max(2 * self.nodes.len());
+    }
+}
+
+#[cfg(feature = "std")]
+impl NodeArena<u16> for LazyArena<u16> {
+    fn node(&self, internal_id: u16) -> Node<u16> {
+        self.get(internal_id)
+    }
+
+    fn node_mut(&mut self, internal_id: u16) -> &mut Node<u16> {
+        self.get_mut(internal_id)
+    }
+
+    fn root_idx(&self) -> NodeRef<u16> {
+        NodeRef::new_internal(self.root, u16::MAX)
+    }
+
+    fn root_idx_mut(&mut self) -> &mut u16 {
+        &mut self.root
+    }
+
+    fn ref_internal(&self, internal_id: u16) -> NodeRef<u16> {
+        NodeRef::new_internal(internal_id, u16::MAX)
+    }
+
+    fn checked_incr(&self, v: u16) -> Option<u16> {
+        v.checked_add(1)
+    }
+
+    fn max_symbol(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn validate(&self) -> Result<(), InconsistencyError<u16>> {
+        self.validate_subtree(self.root, 0, u16::MAX)
+    }
+}
+
+#[cfg(feature = "std")]
+impl NodeArena<i16> for LazyArena<i16> {
+    fn node(&self, internal_id: i16) -> Node<i16> {
+        self.get(internal_id)
+    }
+
+    fn node_mut(&mut self, internal_id: i16) -> &mut Node<i16> {
+        self.get_mut(internal_id)
+    }
+
+    fn root_idx(&self) -> NodeRef<i16> {
+        NodeRef::new_internal(self.root, i16::MAX)
+    }
+
+    fn root_idx_mut(&mut self) -> &mut i16 {
+        &mut self.root
+    }
+
+    fn ref_internal(&self, internal_id: i16) -> NodeRef<i16> {
+        NodeRef::new_internal(internal_id, i16::MAX)
+    }
+
+    fn checked_incr(&self, v: i16) -> Option<i16> {
+        v.checked_add(1)
+    }
+
+    fn max_symbol(&self) -> i16 {
+        i16::MAX
+    }
+
+    fn validate(&self) -> Result<(), InconsistencyError<i16>> {
+        self.validate_subtree(self.root, i16::MIN, i16::MAX)
+    }
+}
+
+/// How many entries an `InlineStack` holds without allocating. That is the deepest that an
+/// `Arena8` can get, i.e. a chain of all its internal nodes, so walking an 8-bit tree never
+/// allocates, no matter its shape. A uniform 16-bit tree is only 16 levels deep, and splaying keeps
+/// the common symbols near the top, so only degenerate 16-bit trees spill to the heap.
+pub(crate) const INLINE_PARENTS: usize = u8::MAX as usize;
+
+/// A stack that stores the first `INLINE_PARENTS` entries inline, e.g. the parents of the current
+/// node, innermost last. Creating a walker happens once per compress/decompress call, and a heap
+/// allocation there would be the only one in the whole call.
+#[derive(Debug)]
+pub struct InlineStack<E: Copy> {
+    inline: [Option<E>; INLINE_PARENTS],
+    len: usize,
+    /// Everything beyond the first `INLINE_PARENTS` entries.
+    spill: Vec<E>,
+}
+
+impl<E: Copy> InlineStack<E> {
+    pub(crate) fn new() -> Self {
+        Self {
+            inline: [None; INLINE_PARENTS],
+            len: 0,
+            spill: Vec::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn push(&mut self, entry: E) {
+        if self.len < INLINE_PARENTS {
+            self.inline[self.len] = Some(entry);
+        } else {
+            self.spill.push(entry);
+        }
+        self.len += 1;
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<E> {
+        if self.len > INLINE_PARENTS {
+            self.len -= 1;
+            self.spill.pop()
+        } else if self.len > 0 {
+            self.len -= 1;
+            self.inline[self.len].take()
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn last(&self) -> Option<&E> {
+        if self.len > INLINE_PARENTS {
+            self.spill.last()
+        } else if self.len > 0 {
+            self.inline[self.len - 1].as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Keeps the spilled capacity, if any, for the next time.
+    pub(crate) fn clear(&mut self) {
+        self.inline[..self.len.min(INLINE_PARENTS)].fill(None);
+        self.spill.clear();
+        self.len = 0;
+    }
+
+    /// From the bottom of the stack to the top.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = E> + '_ {
+        self.inline[..self.len.min(INLINE_PARENTS)]
+            .iter()
+            .map(|entry| entry.expect("inline entries below len are set"))
+            .chain(self.spill.iter().copied())
+    }
+}
+
+/// A walker over a tree, which remembers the way down from the root, so that it can splay the
+/// nodes on it. Methods that may panic on misuse say so, and have a `try_` variant that doesn't.
 #[derive(Debug)]
 pub struct Splayable<'a, T: Clone + Copy + Debug + Eq + PartialEq, A: NodeArena<T> + ?Sized> {
     arena: &'a mut A,
     node: NodeRef<T>,
-    internal_parents: Vec<(T, Direction)>,
+    internal_parents: InlineStack<(T, Direction)>,
 }
 
 impl<'a, T: Clone + Copy + Debug + Eq + PartialEq, A: NodeArena<T> + ?Sized> Splayable<'a, T, A> {
@@ -203,7 +759,7 @@ impl<'a, T: Clone + Copy + Debug + Eq + PartialEq, A: NodeArena<T> + ?Sized> Spl
         Self {
             arena,
             node,
-            internal_parents: Vec::with_capacity(std::mem::size_of::<T>() * 2),
+            internal_parents: InlineStack::new(),
         }
     }
 
@@ -214,6 +770,11 @@ impl<'a, T: Clone + Copy + Debug + Eq + PartialEq, A: NodeArena<T> + ?Sized> Spl
         }
     }
 
+    /// How many steps the walker is below the root.
+    pub fn depth(&self) -> usize {
+        self.internal_parents.len()
+    }
+
     pub fn is_root(&self) -> bool {
         self.internal_parents.is_empty()
     }
@@ -222,50 +783,104 @@ impl<'a, T: Clone + Copy + Debug + Eq + PartialEq, A: NodeArena<T> + ?Sized> Spl
         matches!(self.node, NodeRef::Leaf(_))
     }
 
+    /// # Panics
+    ///
+    /// Panics if the current node is a leaf. Use `try_go` to handle that gracefully.
     pub fn go(&mut self, dir: Direction) {
+        if self.try_go(dir).is_err() {
+            panic!("Tried to descend on leaf?!");
+        }
+    }
+
+    /// Like `go`, but returns an error instead of panicking when called on a leaf. The walker is
+    /// left unchanged in that case.
+    pub fn try_go(&mut self, dir: Direction) -> Result<(), WalkError> {
         let node_id = match self.node {
             NodeRef::Internal(v) => v,
-            _ => panic!("Tried to descend on leaf?!"),
+            NodeRef::Leaf(_) => return Err(WalkError::DescendOnLeaf),
         };
         self.internal_parents.push((node_id, dir));
-        let node = &self.arena.node(node_id);
+        let node = self.arena.node(node_id);
         self.node = node.arm(dir);
+        Ok(())
     }
 
+    /// The same as `try_go`, under the name it was first added as.
+    #[deprecated(note = "use `try_go`, which is the same")]
+    pub fn go_checked(&mut self, dir: Direction) -> Result<(), WalkError> {
+        self.try_go(dir)
+    }
+
+    /// Finds an internal node at least `min_length` steps below the root.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the walker is not at the root, or if the tree is not deep enough anywhere.
     pub fn find_deep_internal(&self, min_length: usize) -> T {
         assert!(self.is_root());
         assert!(!self.is_leaf());
-        let mut level = 0;
-        let mut candidates = vec![self.node.as_internal().unwrap()];
-        while level < min_length {
-            level += 1;
-            assert!(!candidates.is_empty());
-            let mut next_candidates = Vec::with_capacity(candidates.len() * 2);
-            for candidate_id in &candidates {
-                let node = &self.arena.node(*candidate_id);
-                for d in [Direction::Left, Direction::Right] {
-                    let noderef = node.arm(d);
-                    if let Some(child_id) = noderef.as_internal() {
-                        next_candidates.push(child_id);
-                    }
-                }
-            }
-            candidates = next_candidates;
+        let root = self.node.as_internal().unwrap();
+        self.leftmost_internal_below(root, min_length)
+            .expect("tree should be deep enough")
+    }
+
+    /// The leftmost internal node exactly `levels` steps below internal node `id`, if any. This
+    /// recurses instead of collecting each level, so that padding never allocates.
+    fn leftmost_internal_below(&self, id: T, levels: usize) -> Option<T> {
+        if levels == 0 {
+            return Some(id);
         }
-        assert!(!candidates.is_empty());
-        candidates[0]
+        let node = self.arena.node(id);
+        [Direction::Left, Direction::Right]
+            .into_iter()
+            .filter_map(|d| node.arm(d).as_internal())
+            .find_map(|child| self.leftmost_internal_below(child, levels - 1))
     }
 
     pub fn is_consistent(&self) -> bool {
         self.arena.is_consistent()
     }
 
+    /// Splays the parent of the current leaf to the root, and moves the walker there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current node is not a leaf, e.g. at the root. Use
+    /// `try_splay_parent_of_leaf` to handle that gracefully.
     pub fn splay_parent_of_leaf(&mut self) {
-        assert!(self.is_leaf());
-        self.node = self
-            .arena
-            .ref_internal(self.internal_parents.pop().unwrap().0);
+        if let Err(e) = self.try_splay_parent_of_leaf() {
+            panic!("Cannot splay parent of leaf: {e}");
+        }
+    }
+
+    /// Accesses `symbol` exactly like compressing it would, but without producing any output:
+    /// Descends from the root to its leaf, and splays the parent. Replaying a sample like this
+    /// warms up the tree, e.g. for a preset dictionary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the walker is not at the root.
+    pub fn splay_symbol(&mut self, symbol: T)
+    where
+        T: PartialOrd,
+    {
+        assert!(self.is_root());
+        while !self.is_leaf() {
+            self.go(Direction::from_bit(symbol > self.current_value()));
+        }
+        self.splay_parent_of_leaf();
+    }
+
+    /// Like `splay_parent_of_leaf`, but returns an error instead of panicking. The walker and the
+    /// tree are left unchanged in that case.
+    pub fn try_splay_parent_of_leaf(&mut self) -> Result<(), WalkError> {
+        if !self.is_leaf() {
+            return Err(WalkError::NotOnLeaf);
+        }
+        let (parent_id, _) = self.internal_parents.pop().ok_or(WalkError::NoParent)?;
+        self.node = self.arena.ref_internal(parent_id);
         self.splay_internal();
+        Ok(())
     }
 
     fn splay_internal(&mut self) {
@@ -391,8 +1006,393 @@ impl<'a, T: Clone + Copy + Debug + Eq + PartialEq, A: NodeArena<T> + ?Sized> Spl
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::identity_op)]
 mod tests {
     use super::*;
+    use core::array::from_fn;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test8_bytes_roundtrip() {
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for symbol in *b"Hello, World!" {
+            walker.splay_symbol(symbol);
+        }
+        let bytes = tree.to_bytes();
+        assert_eq!(bytes.len(), Arena8::SERIALIZED_LEN);
+        let restored = Arena8::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.root, tree.root);
+        assert_eq!(restored.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test8_reset() {
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for symbol in *b"Hello, World!" {
+            walker.splay_symbol(symbol);
+        }
+        assert_ne!(tree, Arena8::new_uniform());
+        tree.reset();
+        assert_eq!(tree, Arena8::new_uniform());
+    }
+
+    #[test]
+    fn test8_bytes_invalid() {
+        let bytes = Arena8::new_uniform().to_bytes();
+        assert!(Arena8::from_bytes(&bytes[1..]).is_none());
+        let mut bad_root = bytes.clone();
+        bad_root[0] = u8::MAX;
+        assert!(Arena8::from_bytes(&bad_root).is_none());
+        let mut bad_tag = bytes.clone();
+        bad_tag[1] = 2;
+        assert!(Arena8::from_bytes(&bad_tag).is_none());
+        // Swapping two leaves keeps the format intact, but breaks the order.
+        let mut swapped = bytes.clone();
+        swapped.swap(2, 4);
+        assert!(Arena8::from_bytes(&swapped).is_none());
+    }
+
+    #[test]
+    fn test_inline_stack_spill() {
+        let mut stack = InlineStack::new();
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.last(), None);
+        let entry = |i: usize| (i as u16, Direction::from_bit(i.is_multiple_of(3)));
+        for i in 0..INLINE_PARENTS * 3 {
+            stack.push(entry(i));
+            assert_eq!(stack.len(), i + 1);
+            assert_eq!(stack.last(), Some(&entry(i)));
+        }
+        assert!(stack.iter().eq((0..INLINE_PARENTS * 3).map(entry)));
+        for i in (0..INLINE_PARENTS * 3).rev() {
+            assert_eq!(stack.pop(), Some(entry(i)));
+            assert_eq!(stack.len(), i);
+        }
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
+
+        for i in 0..INLINE_PARENTS + 1 {
+            stack.push(entry(i));
+        }
+        stack.clear();
+        assert!(stack.is_empty());
+        assert_eq!(stack.iter().count(), 0);
+        stack.push(entry(7));
+        assert!(stack.iter().eq([entry(7)]));
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        // Ascending symbols degenerate the tree into a long path, far deeper than the inline
+        // part of the parent stack. An 8-bit tree can't get that deep, so this needs 16 bits.
+        let mut tree = Arena16::new_uniform();
+        for symbol in (0..=1000).chain((0..=1000).rev()).chain(0..=1000) {
+            let mut walker = tree.splayable_mut();
+            while !walker.is_leaf() {
+                let bit = symbol > walker.current_value();
+                walker.go(Direction::from_bit(bit));
+            }
+            walker.splay_parent_of_leaf();
+            assert!(walker.is_root());
+        }
+        assert!(tree.is_consistent());
+        let mut walker = tree.splayable_mut();
+        while !walker.is_leaf() {
+            walker.go(Direction::Left);
+        }
+        assert!(walker.depth() > INLINE_PARENTS, "{}", walker.depth());
+        walker.splay_parent_of_leaf();
+        assert!(tree.is_consistent());
+    }
+    #[test]
+    fn test_lazy_arena_bounded() {
+        // A few distinct symbols keep touching the same paths, while occasional outliers drag in
+        // nodes that are only needed once.
+        let symbols: Vec<u16> = (0..5000u32)
+            .map(|i| match i % 7 {
+                0 => (i.wrapping_mul(2654435761) >> 16) as u16,
+                k => 1000 + k as u16,
+            })
+            .collect();
+        let capacity = 64;
+        let mut lazy = LazyArena::<u16>::new_uniform(capacity);
+        let mut unbounded = LazyArena::<u16>::new_uniform(usize::MAX);
+        let mut full = Arena16::new_uniform();
+        for (i, &symbol) in symbols.iter().enumerate() {
+            lazy.splayable_mut().splay_symbol(symbol);
+            unbounded.splayable_mut().splay_symbol(symbol);
+            full.splayable_mut().splay_symbol(symbol);
+            if i % 250 != 0 {
+                continue;
+            }
+            let changed = (0..u16::MAX)
+                .filter(|&id| full.node(id) != uniform_node16(id))
+                .count();
+            assert!(lazy.stored_nodes() <= capacity.max(2 * changed));
+        }
+        assert!(lazy.stored_nodes() < unbounded.stored_nodes());
+        // Dropping nodes never loses anything, so both arenas describe the same tree.
+        assert_eq!(lazy.root_idx(), full.root_idx());
+        for id in 0..u16::MAX {
+            assert_eq!(lazy.node(id), full.node(id), "node {id}");
+        }
+        assert!(lazy.is_consistent());
+    }
+
+    #[test]
+    fn test_lazy_arena_signed_uniform() {
+        let lazy = LazyArena::<i16>::new_uniform(0);
+        let full = ArenaI16::new_uniform();
+        assert_eq!(lazy.root_idx(), full.root_idx());
+        for id in i16::MIN..i16::MAX {
+            assert_eq!(lazy.node(id), full.node(id), "node {id}");
+        }
+    }
+
+    /// A tiny arena for the alphabet `min..=u8::MAX`, which stores only the nodes it has.
+    #[derive(Debug)]
+    struct SparseArena {
+        nodes: BTreeMap<u8, Node<u8>>,
+        root: u8,
+        min: u8,
+    }
+
+    impl NodeArena<u8> for SparseArena {
+        fn node(&self, internal_id: u8) -> Node<u8> {
+            self.nodes[&internal_id]
+        }
+
+        fn node_mut(&mut self, internal_id: u8) -> &mut Node<u8> {
+            self.nodes.get_mut(&internal_id).unwrap()
+        }
+
+        fn root_idx(&self) -> NodeRef<u8> {
+            NodeRef::Internal(self.root)
+        }
+
+        fn root_idx_mut(&mut self) -> &mut u8 {
+            &mut self.root
+        }
+
+        fn ref_internal(&self, internal_id: u8) -> NodeRef<u8> {
+            NodeRef::Internal(internal_id)
+        }
+
+        fn checked_incr(&self, v: u8) -> Option<u8> {
+            v.checked_add(1)
+        }
+
+        fn max_symbol(&self) -> u8 {
+            u8::MAX
+        }
+
+        fn validate(&self) -> Result<(), InconsistencyError<u8>> {
+            self.validate_subtree(self.root, self.min, u8::MAX)
+        }
+    }
+
+    /// The alphabet 253, 254, 255, i.e. the top leaf is `u8::MAX`.
+    fn top_arena() -> SparseArena {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(
+            253,
+            Node {
+                left: NodeRef::Leaf(253),
+                right: NodeRef::Leaf(254),
+            },
+        );
+        nodes.insert(
+            254,
+            Node {
+                left: NodeRef::Internal(253),
+                right: NodeRef::Leaf(255),
+            },
+        );
+        SparseArena {
+            nodes,
+            root: 254,
+            min: 253,
+        }
+    }
+
+    #[test]
+    fn test_checked_incr() {
+        let tree = Arena8::new_uniform();
+        assert_eq!(tree.checked_incr(0), Some(1));
+        assert_eq!(tree.checked_incr(254), Some(255));
+        assert_eq!(tree.checked_incr(255), None);
+        let tree = Arena16::new_uniform();
+        assert_eq!(tree.checked_incr(0xFFFE), Some(0xFFFF));
+        assert_eq!(tree.checked_incr(0xFFFF), None);
+        let tree = ArenaI16::new_uniform();
+        assert_eq!(tree.checked_incr(-1), Some(0));
+        assert_eq!(tree.checked_incr(i16::MAX - 1), Some(i16::MAX));
+        assert_eq!(tree.checked_incr(i16::MAX), None);
+    }
+
+    #[test]
+    fn test_top_leaf_is_max() {
+        let mut tree = top_arena();
+        assert!(tree.is_consistent());
+        {
+            let mut walker = tree.splayable_mut();
+            walker.go(Direction::Left);
+            walker.go(Direction::Right);
+            assert_eq!(254, walker.current_value());
+            assert!(walker.is_leaf());
+            walker.splay_parent_of_leaf();
+        }
+        assert_eq!(tree.root, 253);
+        assert!(tree.is_consistent());
+        {
+            let mut walker = tree.splayable_mut();
+            walker.go(Direction::Right);
+            walker.go(Direction::Right);
+            assert_eq!(255, walker.current_value());
+            assert!(walker.is_leaf());
+            walker.splay_parent_of_leaf();
+        }
+        assert_eq!(tree.root, 254);
+        assert!(tree.is_consistent());
+    }
+
+    #[test]
+    fn test_internal_max_is_inconsistent() {
+        // Internal node 255 can't exist, because there would be nothing to its right. Detecting
+        // this must not overflow.
+        let mut tree = top_arena();
+        tree.nodes.get_mut(&254).unwrap().right = NodeRef::Internal(255);
+        tree.nodes.insert(
+            255,
+            Node {
+                left: NodeRef::Leaf(255),
+                right: NodeRef::Leaf(255),
+            },
+        );
+        assert!(!tree.is_consistent());
+
+        let mut tree = top_arena();
+        tree.root = 255;
+        tree.nodes.insert(
+            255,
+            Node {
+                left: NodeRef::Internal(254),
+                right: NodeRef::Leaf(255),
+            },
+        );
+        assert!(!tree.is_consistent());
+    }
+
+    #[test]
+    fn test_validate_names_bad_node() {
+        let mut tree = Arena8::new_uniform();
+        tree.internal_nodes[0].right = NodeRef::new_leaf(5);
+        let err = tree.validate().unwrap_err();
+        assert_eq!(
+            err,
+            InconsistencyError::Leaf {
+                parent: 0,
+                leaf: 5,
+                cover_min: 1,
+                cover_max_incl: 1,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "leaf 5 below internal node 0 should cover exactly 1..=1"
+        );
+        assert!(!tree.is_consistent());
+
+        let mut tree = Arena8::new_uniform();
+        tree.internal_nodes[127].left = NodeRef::new_internal(200, u8::MAX);
+        let err = tree.validate().unwrap_err();
+        assert_eq!(
+            err,
+            InconsistencyError::Internal {
+                node: 200,
+                cover_min: 0,
+                cover_max_incl: 127,
+            }
+        );
+        assert!(err.to_string().contains("internal node 200"));
+    }
+
+    /// Internal node `i` has the leaf `i` on its left and everything else on its right, so the
+    /// leaf `max` is at the very bottom.
+    fn chain<T: Clone + Copy + Debug + Eq>(i: T, next: Option<T>, max: T) -> Node<T> {
+        Node {
+            left: NodeRef::Leaf(i),
+            right: match next {
+                Some(next) if next != max => NodeRef::Internal(next),
+                _ => NodeRef::Leaf(max),
+            },
+        }
+    }
+
+    #[test]
+    fn test_from_nodes() {
+        let nodes = from_fn(|i| chain(i as u8, (i as u8).checked_add(1), u8::MAX));
+        let mut tree = Arena8::from_nodes(nodes, 0).unwrap();
+        let mut walker = tree.splayable_mut();
+        while !walker.is_leaf() {
+            walker.go(Direction::Right);
+        }
+        assert_eq!(walker.current_value(), u8::MAX);
+        assert_eq!(walker.depth(), u8::MAX as usize);
+
+        let nodes = (0..u16::MAX)
+            .map(|i| chain(i, i.checked_add(1), u16::MAX))
+            .collect();
+        assert!(Arena16::from_nodes(nodes, 0).is_ok());
+        let nodes = Arena16::new_uniform().internal_nodes;
+        assert!(Arena16::from_nodes(nodes, u16::MAX / 2).is_ok());
+    }
+
+    #[test]
+    fn test_from_nodes_inconsistent() {
+        // Right shape, wrong root.
+        let nodes = from_fn(|i| chain(i as u8, (i as u8).checked_add(1), u8::MAX));
+        assert_eq!(
+            Arena8::from_nodes(nodes, 1).unwrap_err(),
+            InconsistencyError::Leaf {
+                parent: 1,
+                leaf: 1,
+                cover_min: 0,
+                cover_max_incl: 1,
+            }
+        );
+        // There is no internal node 255, which must be an error and not a panic.
+        assert!(Arena8::from_nodes(nodes, u8::MAX).is_err());
+        let mut bad = nodes;
+        bad[254].right = NodeRef::Internal(u8::MAX);
+        assert!(Arena8::from_nodes(bad, 0).is_err());
+        // Two arms pointing at the same node.
+        let mut bad = nodes;
+        bad[0].left = NodeRef::Internal(1);
+        assert!(Arena8::from_nodes(bad, 0).is_err());
+
+        let mut nodes = Arena16::new_uniform().internal_nodes;
+        nodes.swap(0, 2);
+        assert!(Arena16::from_nodes(nodes, u16::MAX / 2).is_err());
+    }
+
+    #[test]
+    fn test_default_is_uniform() {
+        assert_eq!(Arena8::default(), Arena8::new_uniform());
+        assert_eq!(Arena16::default(), Arena16::new_uniform());
+        assert_eq!(ArenaI16::default(), ArenaI16::new_uniform());
+        // Any splay makes a difference.
+        let mut tree = Arena8::default();
+        let mut walker = tree.splayable_mut();
+        while !walker.is_leaf() {
+            walker.go(Direction::Left);
+        }
+        walker.splay_parent_of_leaf();
+        assert_ne!(tree, Arena8::new_uniform());
+    }
 
     #[test]
     fn test_uniform_is_consistent() {
@@ -473,6 +1473,207 @@ mod tests {
         assert_eq!(true, walker.is_leaf());
     }
 
+    #[test]
+    fn test_try_go_on_leaf() {
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for _ in 0..8 {
+            assert_eq!(Ok(()), walker.try_go(Direction::Left));
+        }
+        assert!(walker.is_leaf());
+        assert_eq!(0, walker.current_value());
+        // A ninth bit for the same symbol is malformed input, and must not panic:
+        assert_eq!(
+            Err(WalkError::DescendOnLeaf),
+            walker.try_go(Direction::Right)
+        );
+        assert!(walker.is_leaf());
+        assert_eq!(0, walker.current_value());
+        walker.splay_parent_of_leaf();
+        assert!(tree.is_consistent());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_go_checked_on_leaf() {
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for _ in 0..8 {
+            assert_eq!(Ok(()), walker.go_checked(Direction::Left));
+        }
+        assert!(walker.is_leaf());
+        assert_eq!(0, walker.current_value());
+        assert_eq!(
+            Err(WalkError::DescendOnLeaf),
+            walker.go_checked(Direction::Right)
+        );
+        assert!(walker.is_leaf());
+        assert_eq!(0, walker.current_value());
+        walker.splay_parent_of_leaf();
+        assert!(tree.is_consistent());
+    }
+
+    #[test]
+    #[should_panic = "Tried to descend on leaf"]
+    fn test_go_on_leaf() {
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for _ in 0..9 {
+            walker.go(Direction::Left);
+        }
+    }
+
+    #[test]
+    fn test_try_splay_at_root() {
+        let mut tree = Arena8::new_uniform();
+        let before = format!("{tree:?}");
+        let mut walker = tree.splayable_mut();
+        assert_eq!(Err(WalkError::NotOnLeaf), walker.try_splay_parent_of_leaf());
+        assert!(walker.is_root());
+        assert_eq!(127, walker.current_value());
+        assert_eq!(before, format!("{tree:?}"));
+    }
+
+    #[test]
+    fn test_try_splay_on_internal() {
+        let mut tree = Arena8::new_uniform();
+        let before = format!("{tree:?}");
+        let mut walker = tree.splayable_mut();
+        walker.go(Direction::Left);
+        walker.go(Direction::Right);
+        assert_eq!(Err(WalkError::NotOnLeaf), walker.try_splay_parent_of_leaf());
+        assert_eq!(2, walker.depth());
+        assert_eq!(before, format!("{tree:?}"));
+    }
+
+    #[test]
+    fn test_try_splay_on_leaf() {
+        let mut expected = Arena8::new_uniform();
+        let mut walker = expected.splayable_mut();
+        for _ in 0..8 {
+            walker.go(Direction::Right);
+        }
+        walker.splay_parent_of_leaf();
+
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for _ in 0..8 {
+            walker.go(Direction::Right);
+        }
+        assert_eq!(Ok(()), walker.try_splay_parent_of_leaf());
+        assert!(walker.is_root());
+        assert_eq!(format!("{expected:?}"), format!("{tree:?}"));
+    }
+
+    #[test]
+    fn test_splay_symbol() {
+        for sample in [&[170u8][..], b"Hello, World!\n", &[0, 255, 0, 255, 1]] {
+            let mut compressed_tree = Arena8::new_uniform();
+            crate::compress_raw(
+                &mut compressed_tree,
+                &mut crate::symbol::SymbolRead8(sample),
+                std::io::sink(),
+            )
+            .unwrap();
+
+            let mut tree = Arena8::new_uniform();
+            let mut walker = tree.splayable_mut();
+            for &symbol in sample {
+                walker.splay_symbol(symbol);
+                assert!(walker.is_root());
+            }
+            // Padding only walks, it never splays, so the final trees must agree.
+            assert_eq!(format!("{compressed_tree:?}"), format!("{tree:?}"));
+            assert!(tree.is_consistent());
+        }
+    }
+
+    #[test]
+    fn test_splay_symbol_random() {
+        // A fixed LCG (Knuth's MMIX constants), so any failure reproduces. Mixing a narrow range
+        // with the full one makes both shallow and deep leaves common, i.e. all kinds of rotations.
+        let mut state: u64 = 0x5eed;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u32
+        };
+        let mut tree = Arena8::new_uniform();
+        let mut walker = tree.splayable_mut();
+        for i in 0..5000 {
+            let r = next();
+            let symbol = if r % 3 == 0 {
+                100 + (r >> 8) % 8
+            } else {
+                r >> 8
+            } as u8;
+            walker.splay_symbol(symbol);
+            assert!(walker.is_root());
+            assert!(walker.is_consistent(), "after {i} symbols");
+        }
+    }
+
+    /// A degenerate arena whose root is a leaf, i.e. the alphabet is just `0`.
+    #[derive(Debug)]
+    struct LeafRootArena {
+        unused_root: u8,
+    }
+
+    impl NodeArena<u8> for LeafRootArena {
+        fn node(&self, _internal_id: u8) -> Node<u8> {
+            unreachable!("there are no internal nodes")
+        }
+
+        fn node_mut(&mut self, _internal_id: u8) -> &mut Node<u8> {
+            unreachable!("there are no internal nodes")
+        }
+
+        fn root_idx(&self) -> NodeRef<u8> {
+            NodeRef::Leaf(0)
+        }
+
+        fn root_idx_mut(&mut self) -> &mut u8 {
+            &mut self.unused_root
+        }
+
+        fn ref_internal(&self, internal_id: u8) -> NodeRef<u8> {
+            NodeRef::Internal(internal_id)
+        }
+
+        fn checked_incr(&self, v: u8) -> Option<u8> {
+            v.checked_add(1)
+        }
+
+        fn max_symbol(&self) -> u8 {
+            0
+        }
+
+        fn validate(&self) -> Result<(), InconsistencyError<u8>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_try_splay_leaf_root() {
+        let mut tree = LeafRootArena { unused_root: 0 };
+        let mut walker = tree.splayable_mut();
+        assert!(walker.is_leaf());
+        assert_eq!(Err(WalkError::NoParent), walker.try_splay_parent_of_leaf());
+        assert_eq!(
+            Err(WalkError::DescendOnLeaf),
+            walker.try_go(Direction::Left)
+        );
+        assert!(walker.is_root());
+    }
+
+    #[test]
+    #[should_panic = "Cannot splay parent of leaf: tried to splay parent of non-leaf"]
+    fn test_splay_at_root() {
+        let mut tree = Arena8::new_uniform();
+        tree.splayable_mut().splay_parent_of_leaf();
+    }
+
     #[test]
     fn test_splay_noop() {
         let mut tree = Arena8::new_uniform();
@@ -896,6 +2097,43 @@ mod tests {
         assert!(tree.is_consistent());
     }
 
+    #[test]
+    fn test_i16_uniform_is_consistent() {
+        let tree = ArenaI16::new_uniform();
+        assert!(tree.is_consistent());
+    }
+
+    #[test]
+    fn test_i16_go_basic() {
+        let mut tree = ArenaI16::new_uniform();
+        let mut walker = tree.splayable_mut();
+        assert_eq!(-1, walker.current_value());
+        walker.go(Direction::Left); // negative numbers
+        assert_eq!(-16385, walker.current_value());
+        walker.go(Direction::Left);
+        assert_eq!(-24577, walker.current_value());
+        for _ in 0..14 {
+            walker.go(Direction::Left);
+        }
+        assert_eq!(i16::MIN, walker.current_value());
+        assert!(walker.is_leaf());
+        walker.splay_parent_of_leaf();
+        assert!(walker.is_consistent());
+    }
+
+    #[test]
+    fn test_i16_neighbors() {
+        // -1 and 0 are neighbors, and separated by the root.
+        let mut tree = ArenaI16::new_uniform();
+        let mut walker = tree.splayable_mut();
+        walker.go(Direction::Right);
+        for _ in 0..15 {
+            walker.go(Direction::Left);
+        }
+        assert_eq!(0, walker.current_value());
+        assert!(walker.is_leaf());
+    }
+
     #[test]
     fn test16_tree_structure() {
         let tree = Arena16::new_uniform();
@@ -0,0 +1,686 @@
+//! Sans-IO compression and decompression: The caller hands in the bytes as they arrive, and gets
+//! back the result, without any `Read` or `Write` in between. These are the cores of
+//! `write::Compressor` and `decompress_raw`, and can be wrapped in whatever IO model the caller
+//! has, e.g. an async runtime.
+
+use crate::bits::{BitReader, BitWriter};
+use crate::common::Direction;
+use crate::io::{ErrorKind, Result, Write};
+use crate::splay::{Arena16, Arena8, ArenaI16, InlineStack, NodeArena};
+use crate::symbol::{
+    SymbolWrite, SymbolWrite16BE, SymbolWrite16LE, SymbolWrite8, SymbolWriteI16BE, SymbolWriteI16LE,
+};
+#[cfg(feature = "stats")]
+use crate::telemetry::BitsPerSymbol;
+use crate::{DecompressOptions, Error, Flavor, Stats};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// Everything that must survive between two pieces of input, apart from the tree itself.
+pub(crate) struct Decoder {
+    options: DecompressOptions,
+    stats: Stats,
+    /// The way from the root to the current node, i.e. the bits of the incomplete symbol. A
+    /// walker borrows the tree, so it can't be kept around. Instead, each `push` walks this path
+    /// again, which is cheap compared to decoding a whole chunk.
+    path: InlineStack<Direction>,
+    /// Each decoded symbol with its code length, if requested, see `DecompressState::trace`.
+    trace: Option<Vec<(i64, u32)>>,
+}
+
+impl Decoder {
+    pub(crate) fn new(options: &DecompressOptions) -> Self {
+        Self {
+            options: options.clone(),
+            stats: Stats {
+                padding_symbols: options.expected_output_bytes.map(|_| 0),
+                ..Stats::default()
+            },
+            path: InlineStack::new(),
+            trace: None,
+        }
+    }
+
+    pub(crate) fn bytes_out(&self) -> u64 {
+        self.stats.bytes_out
+    }
+
+    /// Decodes all of `input`, writing each symbol to `w` as soon as it is complete.
+    pub(crate) fn push<
+        T: Clone + Copy + Debug + Eq + Ord + PartialEq + PartialOrd + Into<i64>,
+        A: NodeArena<T>,
+        W: SymbolWrite<T>,
+    >(
+        &mut self,
+        arena: &mut A,
+        input: &[u8],
+        w: &mut W,
+    ) -> Result<()> {
+        let symbol_bytes = core::mem::size_of::<T>() as u64;
+        let mut walker = arena.splayable_mut();
+        for dir in self.path.iter() {
+            walker.try_go(dir)?;
+        }
+        let mut reader = BitReader::new(input);
+        for _ in 0..input.len() * 8 {
+            let dir = Direction::from_bit(reader.read_bit()?);
+            // Can't fail, because we always splay as soon as we reach a leaf. Nevertheless,
+            // untrusted input must not be able to cause a panic here.
+            walker.try_go(dir)?;
+            self.path.push(dir);
+            if !walker.is_leaf() {
+                continue;
+            }
+            if let Some(max_output_bytes) = self.options.max_output_bytes {
+                if self.stats.bytes_out + symbol_bytes > max_output_bytes {
+                    return Err(Error::LimitExceeded {
+                        written: self.stats.bytes_out,
+                    }
+                    .into());
+                }
+            }
+            if let Some(expected) = self.options.expected_output_bytes {
+                if self.stats.bytes_out >= expected {
+                    if self.options.strict {
+                        return Err(Error::LengthMismatch {
+                            expected,
+                            actual: self.stats.bytes_out + symbol_bytes,
+                        }
+                        .into());
+                    }
+                    self.stats.padding_symbols = self.stats.padding_symbols.map(|n| n + 1);
+                }
+            }
+            w.write_one(walker.current_value())?;
+            if let Some(trace) = &mut self.trace {
+                trace.push((walker.current_value().into(), self.path.len() as u32));
+            }
+            self.stats.symbols += 1;
+            self.stats.bytes_out += symbol_bytes;
+            walker.splay_parent_of_leaf();
+            debug_assert!(walker.is_consistent());
+            self.path.clear();
+        }
+        Ok(())
+    }
+
+    /// The input ended. Any bits of an incomplete symbol are considered padding, unless `strict`
+    /// disagrees.
+    pub(crate) fn finish(mut self) -> Result<Stats> {
+        self.stats.trailing_bits = self.path.len();
+        if self.options.strict && self.stats.trailing_bits >= 8 {
+            return Err(Error::Truncated {
+                bytes_out: self.stats.bytes_out,
+                cause: None,
+            }
+            .into());
+        }
+        if let (true, Some(expected)) = (self.options.strict, self.options.expected_output_bytes) {
+            if self.stats.bytes_out != expected {
+                return Err(Error::LengthMismatch {
+                    expected,
+                    actual: self.stats.bytes_out,
+                }
+                .into());
+            }
+        }
+        Ok(self.stats)
+    }
+}
+
+enum FlavorArena {
+    Symbol8(Box<Arena8>),
+    Symbol16BE(Arena16),
+    Symbol16LE(Arena16),
+    SymbolI16BE(ArenaI16),
+    SymbolI16LE(ArenaI16),
+}
+
+/// Incremental decompression of a raw stream (without filemagic), as written by `compress`. Feed
+/// it the compressed data piece by piece with `push`, in whatever sizes it arrives, and call
+/// `finish` at the end of the input. The state never does any IO itself.
+///
+/// Each input byte decodes to at most 8 symbols, so the output of a single `push` is bounded by
+/// the size of its input. To apply backpressure, simply push less at a time.
+///
+/// After an error, the stream is broken, and the state should be dropped.
+pub struct DecompressState {
+    arena: FlavorArena,
+    decoder: Decoder,
+}
+
+impl DecompressState {
+    pub fn new(flavor: Flavor, options: &DecompressOptions) -> Self {
+        let arena = match flavor {
+            Flavor::Symbol8 => FlavorArena::Symbol8(Box::default()),
+            Flavor::Symbol16BE => FlavorArena::Symbol16BE(Arena16::new_uniform()),
+            Flavor::Symbol16LE => FlavorArena::Symbol16LE(Arena16::new_uniform()),
+            Flavor::SymbolI16BE => FlavorArena::SymbolI16BE(ArenaI16::new_uniform()),
+            Flavor::SymbolI16LE => FlavorArena::SymbolI16LE(ArenaI16::new_uniform()),
+        };
+        Self {
+            arena,
+            decoder: Decoder::new(options),
+        }
+    }
+
+    /// Decodes `input`, and appends the decoded bytes to `out`. Returns how many bytes of `input`
+    /// were consumed, which is all of them: A symbol that is still incomplete at the end of
+    /// `input` is remembered, and completed by the next call.
+    pub fn push(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+        self.push_to(input, out)?;
+        Ok(input.len())
+    }
+
+    /// Like `push`, but for a ring buffer: Decodes all of `input` without making it contiguous
+    /// first, and appends the decoded bytes to `out`. Afterwards, `input` is empty.
+    pub fn push_deque(&mut self, input: &mut VecDeque<u8>, out: &mut VecDeque<u8>) -> Result<()> {
+        let (front, back) = input.as_slices();
+        self.push_to(front, &mut *out)?;
+        self.push_to(back, out)?;
+        input.clear();
+        Ok(())
+    }
+
+    fn push_to<W: Write>(&mut self, input: &[u8], out: W) -> Result<()> {
+        match &mut self.arena {
+            FlavorArena::Symbol8(arena) => {
+                self.decoder
+                    .push(arena.as_mut(), input, &mut SymbolWrite8(out))?;
+            }
+            FlavorArena::Symbol16BE(arena) => {
+                self.decoder.push(arena, input, &mut SymbolWrite16BE(out))?;
+            }
+            FlavorArena::Symbol16LE(arena) => {
+                self.decoder.push(arena, input, &mut SymbolWrite16LE(out))?;
+            }
+            FlavorArena::SymbolI16BE(arena) => {
+                self.decoder
+                    .push(arena, input, &mut SymbolWriteI16BE(out))?;
+            }
+            FlavorArena::SymbolI16LE(arena) => {
+                self.decoder
+                    .push(arena, input, &mut SymbolWriteI16LE(out))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes that `push` has produced so far.
+    pub fn bytes_out(&self) -> u64 {
+        self.decoder.bytes_out()
+    }
+
+    /// From now on, record each decoded symbol together with its code length, i.e. how many bits
+    /// its path from the root took at that moment. This is the number of bits that the symbol
+    /// cost, so it shows which parts of the data compress badly. Collect them with `take_trace`.
+    pub fn trace(&mut self) {
+        self.decoder.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// The symbols recorded since `trace` or the last call, oldest first. A symbol is its value,
+    /// e.g. a byte for `Flavor::Symbol8`, and the signed value for the signed flavors. Together
+    /// with `Stats::trailing_bits`, the code lengths add up to all bits of the input.
+    pub fn take_trace(&mut self) -> Vec<(i64, u32)> {
+        self.decoder
+            .trace
+            .as_mut()
+            .map_or_else(Vec::new, core::mem::take)
+    }
+
+    /// Signals the end of the input, and returns the same `Stats` that `decompress_with_options`
+    /// would. In strict mode, this is where truncation and length mismatches are detected.
+    pub fn finish(self) -> Result<Stats> {
+        self.decoder.finish()
+    }
+}
+
+enum Tree {
+    Symbol8(Box<Arena8>),
+    Symbol16 {
+        arena: Box<Arena16>,
+        little_endian: bool,
+        /// The first byte of a symbol whose second byte hasn't arrived yet.
+        half: Option<u8>,
+    },
+    SymbolI16 {
+        arena: Box<ArenaI16>,
+        little_endian: bool,
+        half: Option<u8>,
+    },
+}
+
+/// What the `stats` feature records about the code lengths, see `CompressState::bits_per_symbol`
+/// and `CompressState::max_depth`.
+#[cfg(feature = "stats")]
+#[derive(Default)]
+struct Telemetry {
+    bits_per_symbol: BitsPerSymbol,
+    max_depth: u32,
+    symbols: u64,
+    /// Sum of all code lengths, i.e. the output so far in bits, without any padding.
+    code_bits: u64,
+}
+
+#[cfg(feature = "stats")]
+impl Telemetry {
+    fn record(&mut self, code_length: u32) {
+        self.bits_per_symbol.push(code_length);
+        self.max_depth = self.max_depth.max(code_length);
+        self.symbols += 1;
+        self.code_bits += u64::from(code_length);
+    }
+}
+
+/// Incremental compression, the counterpart of `DecompressState`: Feed it the input piece by piece
+/// with `push`, and call `finish` at the end. Together, the bytes that both append to `out` are
+/// identical to what `compress` would produce for the same input.
+pub struct CompressState {
+    tree: Tree,
+    /// The incomplete last byte. Complete bytes are moved to `out` right away.
+    bits: BitWriter<Vec<u8>>,
+    finished: bool,
+    #[cfg(feature = "stats")]
+    telemetry: Telemetry,
+}
+
+impl CompressState {
+    pub fn new(flavor: Flavor) -> Self {
+        let tree = match flavor {
+            Flavor::Symbol8 => Tree::Symbol8(Box::default()),
+            Flavor::Symbol16BE | Flavor::Symbol16LE => Tree::Symbol16 {
+                arena: Box::default(),
+                little_endian: flavor == Flavor::Symbol16LE,
+                half: None,
+            },
+            Flavor::SymbolI16BE | Flavor::SymbolI16LE => Tree::SymbolI16 {
+                arena: Box::default(),
+                little_endian: flavor == Flavor::SymbolI16LE,
+                half: None,
+            },
+        };
+        Self {
+            tree,
+            bits: BitWriter::new(Vec::new()),
+            finished: false,
+            #[cfg(feature = "stats")]
+            telemetry: Telemetry::default(),
+        }
+    }
+
+    /// Moving average of the code length of the most recent symbols, see `BitsPerSymbol`.
+    #[cfg(feature = "stats")]
+    pub fn bits_per_symbol(&self) -> &BitsPerSymbol {
+        &self.telemetry.bits_per_symbol
+    }
+
+    /// Restarts the moving average with a different window size.
+    #[cfg(feature = "stats")]
+    pub fn set_bits_per_symbol_window(&mut self, window: usize) {
+        self.telemetry.bits_per_symbol = BitsPerSymbol::new(window);
+    }
+
+    /// The deepest that any symbol so far was in the tree, i.e. the longest code, or since the
+    /// last `reset_max_depth`. The uniform 8-bit tree has all symbols at depth 8, so if this stays
+    /// at 8 or more, the tree doesn't adapt to the data in any useful way, e.g. for random data.
+    /// 0 before the first symbol.
+    #[cfg(feature = "stats")]
+    pub fn max_depth(&self) -> u32 {
+        self.telemetry.max_depth
+    }
+
+    /// Starts over with `max_depth`, e.g. to only look at the data after the tree had time to
+    /// adapt, since the first few symbols are always deep.
+    #[cfg(feature = "stats")]
+    pub fn reset_max_depth(&mut self) {
+        self.telemetry.max_depth = 0;
+    }
+
+    /// The number of symbols so far, and the number of bits they took, see `compress_traced`.
+    #[cfg(feature = "stats")]
+    pub(crate) fn totals(&self) -> (u64, u64) {
+        (self.telemetry.symbols, self.telemetry.code_bits)
+    }
+
+    /// Compresses all of `input`, and appends the complete bytes of the result to `out`. For the
+    /// 16-bit flavors, a symbol may be split across calls. Fails with `ErrorKind::InvalidInput`
+    /// after `finish`.
+    pub fn push(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.encode_all(input)?;
+        out.append(self.bits.get_mut());
+        Ok(())
+    }
+
+    /// Like `push`, but for a ring buffer: Compresses all of `input` without making it contiguous
+    /// first, and appends the complete bytes of the result to `out`. Afterwards, `input` is empty.
+    pub fn push_deque(&mut self, input: &mut VecDeque<u8>, out: &mut VecDeque<u8>) -> Result<()> {
+        let (front, back) = input.as_slices();
+        self.encode_all(front)?;
+        self.encode_all(back)?;
+        out.extend(self.bits.get_mut().drain(..));
+        input.clear();
+        Ok(())
+    }
+
+    /// Encodes `input`, and leaves the result in `bits`.
+    fn encode_all(&mut self, input: &[u8]) -> Result<()> {
+        if self.finished {
+            return Err(crate::io::Error::new(
+                ErrorKind::InvalidInput,
+                "cannot write after finishing",
+            ));
+        }
+        match &mut self.tree {
+            Tree::Symbol8(arena) => {
+                for &byte in input {
+                    let _bits = encode(arena.as_mut(), byte, &mut self.bits);
+                    #[cfg(feature = "stats")]
+                    self.telemetry.record(_bits);
+                }
+            }
+            Tree::Symbol16 {
+                arena,
+                little_endian,
+                half,
+            } => {
+                for &byte in input {
+                    let Some(pair) = complete_pair(half, byte, *little_endian) else {
+                        continue;
+                    };
+                    let _bits = encode(arena.as_mut(), u16::from_be_bytes(pair), &mut self.bits);
+                    #[cfg(feature = "stats")]
+                    self.telemetry.record(_bits);
+                }
+            }
+            Tree::SymbolI16 {
+                arena,
+                little_endian,
+                half,
+            } => {
+                for &byte in input {
+                    let Some(pair) = complete_pair(half, byte, *little_endian) else {
+                        continue;
+                    };
+                    let _bits = encode(arena.as_mut(), i16::from_be_bytes(pair), &mut self.bits);
+                    #[cfg(feature = "stats")]
+                    self.telemetry.record(_bits);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pads the last byte, and appends it to `out`. Fails with `ErrorKind::UnexpectedEof` if the
+    /// input ended in the middle of a 16-bit symbol, in which case nothing changes. Calling this
+    /// again after it succeeded does nothing.
+    pub fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        match &mut self.tree {
+            Tree::Symbol8(arena) => pad(arena.as_mut(), &mut self.bits),
+            Tree::Symbol16 { half: Some(_), .. } | Tree::SymbolI16 { half: Some(_), .. } => {
+                return Err(crate::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Cannot interpret last byte as u16",
+                ));
+            }
+            Tree::Symbol16 { arena, .. } => pad(arena.as_mut(), &mut self.bits),
+            Tree::SymbolI16 { arena, .. } => pad(arena.as_mut(), &mut self.bits),
+        }
+        self.finished = true;
+        out.append(self.bits.get_mut());
+        Ok(())
+    }
+
+    /// Like `finish`, but appends the last byte to a ring buffer.
+    pub fn finish_deque(&mut self, out: &mut VecDeque<u8>) -> Result<()> {
+        let mut last = Vec::new();
+        self.finish(&mut last)?;
+        out.extend(last);
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Collects the two bytes of a 16-bit symbol, and returns them in big-endian order once both
+/// have arrived.
+fn complete_pair(half: &mut Option<u8>, byte: u8, little_endian: bool) -> Option<[u8; 2]> {
+    let Some(first) = half.take() else {
+        *half = Some(byte);
+        return None;
+    };
+    Some(if little_endian {
+        [byte, first]
+    } else {
+        [first, byte]
+    })
+}
+
+/// Returns the code length of `symbol`, i.e. the number of bits written.
+fn encode<T: Clone + Copy + Debug + Eq + Ord, A: NodeArena<T>>(
+    arena: &mut A,
+    symbol: T,
+    bits: &mut BitWriter<Vec<u8>>,
+) -> u32 {
+    let mut walker = arena.splayable_mut();
+    let mut code_length = 0;
+    while !walker.is_leaf() {
+        let bit = symbol > walker.current_value();
+        walker.go(Direction::from_bit(bit));
+        bits.write_bit(bit).expect("writing to Vec cannot fail");
+        code_length += 1;
+    }
+    walker.splay_parent_of_leaf();
+    debug_assert!(walker.is_consistent());
+    code_length
+}
+
+/// Same padding as `compress_raw`: Walk towards a deep internal node, so that the padding
+/// can't be mistaken for another symbol.
+fn pad<T: Clone + Copy + Debug + Eq + Ord, A: NodeArena<T>>(
+    arena: &mut A,
+    bits: &mut BitWriter<Vec<u8>>,
+) {
+    let need_pad_bits = bits.padding_needed();
+    if need_pad_bits == 0 {
+        return;
+    }
+    let mut walker = arena.splayable_mut();
+    let goal = walker.find_deep_internal(need_pad_bits);
+    for _ in 0..need_pad_bits {
+        let bit = goal > walker.current_value();
+        walker.go(Direction::from_bit(bit));
+        bits.write_bit(bit).expect("writing to Vec cannot fail");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress_with_options};
+
+    #[test]
+    fn test_one_byte_at_a_time() {
+        let input = b"Hello, World! This is a test. Hello, World! This is a test.\n".repeat(10);
+        for flavor in Flavor::ALL {
+            let mut compressed = Vec::new();
+            compress(flavor, &input[..], &mut compressed).unwrap();
+            let options = DecompressOptions::default();
+            let mut expected = Vec::new();
+            let expected_stats =
+                decompress_with_options(flavor, &compressed[..], &mut expected, &options).unwrap();
+
+            let mut state = DecompressState::new(flavor, &options);
+            let mut out = Vec::new();
+            for byte in compressed.chunks(1) {
+                assert_eq!(state.push(byte, &mut out).unwrap(), 1);
+                assert_eq!(state.bytes_out(), out.len() as u64);
+            }
+            // Pushing nothing is fine, too.
+            assert_eq!(state.push(&[], &mut out).unwrap(), 0);
+            assert_eq!(state.finish().unwrap(), expected_stats, "{flavor:?}");
+            assert_eq!(out, expected, "{flavor:?}");
+            assert_eq!(&out[..input.len()], input, "{flavor:?}");
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        for vector in crate::vectors::KNOWN_ANSWERS {
+            let mut state = DecompressState::new(vector.flavor, &DecompressOptions::default());
+            let mut out = Vec::new();
+            assert!(state.take_trace().is_empty());
+            state.trace();
+            let mut trace = Vec::new();
+            // Symbols that are split across pushes count all their bits.
+            for byte in vector.compressed.chunks(1) {
+                state.push(byte, &mut out).unwrap();
+                trace.extend(state.take_trace());
+            }
+            let stats = state.finish().unwrap();
+            let bits: u32 = trace.iter().map(|&(_, bits)| bits).sum();
+            assert_eq!(
+                bits as usize + stats.trailing_bits,
+                8 * vector.compressed.len(),
+                "{}",
+                vector.name
+            );
+            assert_eq!(trace.len() as u64, stats.symbols, "{}", vector.name);
+        }
+
+        let mut state = DecompressState::new(Flavor::SymbolI16BE, &DecompressOptions::default());
+        state.trace();
+        let mut out = Vec::new();
+        state.push(b"\x00\x00", &mut out).unwrap();
+        // The uniform tree has every symbol at depth 16.
+        assert_eq!(state.take_trace(), [(-32768, 16)]);
+        assert!(state.take_trace().is_empty());
+    }
+
+    #[test]
+    fn test_compress_chunked() {
+        let input = b"Hello, World! This is a test. Hello, World! This is a test.\n".repeat(10);
+        for flavor in Flavor::ALL {
+            let mut expected = Vec::new();
+            compress(flavor, &input[..], &mut expected).unwrap();
+            // Odd chunk sizes split 16-bit symbols, too.
+            for chunk_size in [1, 3, 64, input.len()] {
+                let mut state = CompressState::new(flavor);
+                let mut out = Vec::new();
+                for chunk in input.chunks(chunk_size) {
+                    state.push(chunk, &mut out).unwrap();
+                }
+                state.finish(&mut out).unwrap();
+                assert_eq!(out, expected, "{flavor:?}, chunks of {chunk_size}");
+                let err = state.push(b"x", &mut out).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            }
+        }
+    }
+
+    /// Appends `chunk` to the empty `staged`, such that it wraps around the end of the buffer if
+    /// it is long enough. `clear` starts over at the front of the buffer, so move the start by hand.
+    fn stage(staged: &mut VecDeque<u8>, chunk: &[u8]) {
+        for _ in 0..staged.capacity() - 3 {
+            staged.push_back(0);
+            staged.pop_front();
+        }
+        staged.extend(chunk);
+    }
+
+    #[test]
+    fn test_deque() {
+        let input = b"Hello, World! This is a test. Hello, World! This is a test.\n".repeat(10);
+        for flavor in Flavor::ALL {
+            let mut expected = Vec::new();
+            compress(flavor, &input[..], &mut expected).unwrap();
+
+            // A small ring buffer that is refilled in irregular chunks.
+            let mut staged = VecDeque::with_capacity(16);
+            let mut compressed = VecDeque::new();
+            let mut state = CompressState::new(flavor);
+            let mut rest = &input[..];
+            let mut wrapped = false;
+            for chunk_size in [5, 1, 13, 7, 2, 11].into_iter().cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+                let (chunk, tail) = rest.split_at(chunk_size.min(rest.len()));
+                stage(&mut staged, chunk);
+                wrapped |= !staged.as_slices().1.is_empty();
+                rest = tail;
+                state.push_deque(&mut staged, &mut compressed).unwrap();
+                assert!(staged.is_empty());
+            }
+            assert!(wrapped);
+            state.finish_deque(&mut compressed).unwrap();
+            assert_eq!(compressed, expected, "{flavor:?}");
+
+            let mut state = DecompressState::new(flavor, &DecompressOptions::default());
+            let mut staged = VecDeque::with_capacity(8);
+            let mut decompressed = VecDeque::new();
+            while !compressed.is_empty() {
+                let n = compressed.len().min(3 + compressed.len() % 5);
+                let chunk: Vec<u8> = compressed.drain(..n).collect();
+                stage(&mut staged, &chunk);
+                state.push_deque(&mut staged, &mut decompressed).unwrap();
+                assert!(staged.is_empty());
+            }
+            state.finish().unwrap();
+            assert_eq!(decompressed.make_contiguous(), &input[..], "{flavor:?}");
+        }
+    }
+
+    #[test]
+    fn test_compress_odd() {
+        let mut state = CompressState::new(Flavor::Symbol16LE);
+        let mut out = Vec::new();
+        state.push(b"odd", &mut out).unwrap();
+        let err = state.finish(&mut out).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert!(!state.is_finished());
+    }
+
+    #[test]
+    fn test_limit() {
+        let options = DecompressOptions {
+            max_output_bytes: Some(5),
+            ..DecompressOptions::default()
+        };
+        let mut state = DecompressState::new(Flavor::Symbol8, &options);
+        let mut out = Vec::new();
+        let compressed = b"\x48\xa5\xa8\xf9\x81\x62\x19\x2f\x91\x16\x4a\x40\x50";
+        let err = state.push(compressed, &mut out).unwrap_err();
+        assert_eq!(
+            Error::from_io_error(&err),
+            Some(&Error::LimitExceeded { written: 5 })
+        );
+        assert_eq!(out, b"Hello");
+    }
+
+    #[test]
+    fn test_truncated_in_finish() {
+        let options = DecompressOptions {
+            strict: true,
+            ..DecompressOptions::default()
+        };
+        let mut state = DecompressState::new(Flavor::Symbol16BE, &options);
+        let mut out = Vec::new();
+        state.push(b"\x48", &mut out).unwrap();
+        assert!(out.is_empty());
+        let err = state.finish().unwrap_err();
+        assert_eq!(
+            Error::from_io_error(&err),
+            Some(&Error::Truncated {
+                bytes_out: 0,
+                cause: None,
+            })
+        );
+    }
+}
@@ -1,11 +1,19 @@
-use std::io::{Error, ErrorKind, Read, Result, Write};
+use crate::io::{Error, ErrorKind, Read, Result, Write};
 
 pub trait SymbolRead<T> {
     /// This is supposed to return exactly one symbol.
     /// TODO: Revisit this interface when dealing with higher throughput.
     /// Regular EOF should be indicated as `Ok(None)`, whereas ErrorKind::UnexpectedEof should
     /// indicate an actual error, like trying to read a u16 when only 2 bytes are left.
+    ///
+    /// The underlying reader must be blocking: A `read` of zero bytes is taken as EOF, as
+    /// `Read` demands. A non-blocking reader that has no data yet must fail with
+    /// `ErrorKind::WouldBlock` instead, which is passed on as an error, and never mistaken for
+    /// EOF. Like any other error, this aborts the (de)compression, which can't be resumed, since
+    /// a partially read symbol is lost. Wait for the data before handing the reader over instead.
     fn read_one(&mut self) -> Result<Option<T>>;
+    /// The largest symbol that `read_one` can ever return.
+    fn max_symbol(&self) -> T;
 }
 
 pub struct SymbolRead8<R: Read>(pub R);
@@ -19,29 +27,35 @@ impl<R: Read> SymbolRead<u8> for SymbolRead8<R> {
             Err(e) => Err(e),
         }
     }
+
+    fn max_symbol(&self) -> u8 {
+        u8::MAX
+    }
 }
 
 /// Reads two bytes. The difference to read_exact([u8; 2]) is that *zero* bytes being available is
 /// not an error, but *one* byte is an error.
 fn read_two_bytes<R: Read>(r: &mut R) -> Result<Option<[u8; 2]>> {
-    // Calling Read::read() by hand is a bad idea, because we might need to retry many times due to ErrKind::Interrupted.
-    // Calling Read::read_exact() would lose the information whether we read zero or one byte.
-    // Read::read_to_end() is nice, but would consume everything.
-
-    // This is terribly inefficient: Avoid allocating just for these two bytes?!
-    let mut buf = Vec::with_capacity(2);
-    let bytes_read = r.take(2).read_to_end(&mut buf)?;
-    assert_eq!(bytes_read, buf.len());
-    match bytes_read {
-        2 => Ok(Some([buf[0], buf[1]])),
+    // Read::read_exact() would lose the information whether we read zero or one byte, so call
+    // Read::read() by hand, retrying on ErrorKind::Interrupted just like read_exact() does.
+    let mut buf = [0; 2];
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            // Only a blocking reader can be trusted to mean EOF here, see `SymbolRead`.
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    match filled {
+        2 => Ok(Some(buf)),
         1 => Err(Error::new(
             ErrorKind::UnexpectedEof,
             "Cannot interpret last byte as u16",
         )),
-        0 => Ok(None),
-        _ => {
-            panic!("Impossible number of bytes read into two-byte-buffer: {bytes_read}");
-        }
+        _ => Ok(None),
     }
 }
 
@@ -52,6 +66,10 @@ impl<R: Read> SymbolRead<u16> for SymbolRead16LE<R> {
         let maybe_bytes = read_two_bytes(&mut self.0)?;
         Ok(maybe_bytes.map(u16::from_le_bytes))
     }
+
+    fn max_symbol(&self) -> u16 {
+        u16::MAX
+    }
 }
 
 pub struct SymbolRead16BE<R: Read>(pub R);
@@ -61,6 +79,36 @@ impl<R: Read> SymbolRead<u16> for SymbolRead16BE<R> {
         let maybe_bytes = read_two_bytes(&mut self.0)?;
         Ok(maybe_bytes.map(u16::from_be_bytes))
     }
+
+    fn max_symbol(&self) -> u16 {
+        u16::MAX
+    }
+}
+
+pub struct SymbolReadI16LE<R: Read>(pub R);
+
+impl<R: Read> SymbolRead<i16> for SymbolReadI16LE<R> {
+    fn read_one(&mut self) -> Result<Option<i16>> {
+        let maybe_bytes = read_two_bytes(&mut self.0)?;
+        Ok(maybe_bytes.map(i16::from_le_bytes))
+    }
+
+    fn max_symbol(&self) -> i16 {
+        i16::MAX
+    }
+}
+
+pub struct SymbolReadI16BE<R: Read>(pub R);
+
+impl<R: Read> SymbolRead<i16> for SymbolReadI16BE<R> {
+    fn read_one(&mut self) -> Result<Option<i16>> {
+        let maybe_bytes = read_two_bytes(&mut self.0)?;
+        Ok(maybe_bytes.map(i16::from_be_bytes))
+    }
+
+    fn max_symbol(&self) -> i16 {
+        i16::MAX
+    }
 }
 
 pub trait SymbolWrite<T> {
@@ -68,6 +116,8 @@ pub trait SymbolWrite<T> {
     /// TODO: Revisit this interface when dealing with higher throughput.
     fn write_one(&mut self, symbol: T) -> Result<()>;
     fn flush(&mut self) -> Result<()>;
+    /// The largest symbol that `write_one` accepts.
+    fn max_symbol(&self) -> T;
 }
 
 pub struct SymbolWrite8<W: Write>(pub W);
@@ -81,6 +131,10 @@ impl<W: Write> SymbolWrite<u8> for SymbolWrite8<W> {
     fn flush(&mut self) -> Result<()> {
         self.0.flush()
     }
+
+    fn max_symbol(&self) -> u8 {
+        u8::MAX
+    }
 }
 
 pub struct SymbolWrite16LE<W: Write>(pub W);
@@ -94,6 +148,10 @@ impl<W: Write> SymbolWrite<u16> for SymbolWrite16LE<W> {
     fn flush(&mut self) -> Result<()> {
         self.0.flush()
     }
+
+    fn max_symbol(&self) -> u16 {
+        u16::MAX
+    }
 }
 
 pub struct SymbolWrite16BE<W: Write>(pub W);
@@ -107,9 +165,48 @@ impl<W: Write> SymbolWrite<u16> for SymbolWrite16BE<W> {
     fn flush(&mut self) -> Result<()> {
         self.0.flush()
     }
+
+    fn max_symbol(&self) -> u16 {
+        u16::MAX
+    }
+}
+
+pub struct SymbolWriteI16LE<W: Write>(pub W);
+
+impl<W: Write> SymbolWrite<i16> for SymbolWriteI16LE<W> {
+    fn write_one(&mut self, symbol: i16) -> Result<()> {
+        let buf = symbol.to_le_bytes();
+        self.0.write_all(buf.as_slice())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+
+    fn max_symbol(&self) -> i16 {
+        i16::MAX
+    }
+}
+
+pub struct SymbolWriteI16BE<W: Write>(pub W);
+
+impl<W: Write> SymbolWrite<i16> for SymbolWriteI16BE<W> {
+    fn write_one(&mut self, symbol: i16) -> Result<()> {
+        let buf = symbol.to_be_bytes();
+        self.0.write_all(buf.as_slice())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+
+    fn max_symbol(&self) -> i16 {
+        i16::MAX
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_operation)]
 mod tests {
     use super::*;
 
@@ -159,6 +256,87 @@ mod tests {
         assert_eq!(r.read_one().unwrap_err().kind(), ErrorKind::UnexpectedEof);
     }
 
+    /// Fails with `kind` a few times before each chunk of data, like a non-blocking socket or a
+    /// reader that gets interrupted by signals would.
+    struct FlakyReader<'a> {
+        chunks: Vec<&'a [u8]>,
+        kind: ErrorKind,
+        fails_left: usize,
+    }
+
+    impl<'a> FlakyReader<'a> {
+        fn new(chunks: Vec<&'a [u8]>, kind: ErrorKind, fails_left: usize) -> Self {
+            Self {
+                chunks,
+                kind,
+                fails_left,
+            }
+        }
+    }
+
+    impl Read for FlakyReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            if self.fails_left > 0 {
+                self.fails_left -= 1;
+                return Err(self.kind.into());
+            }
+            self.fails_left = 3;
+            let n = self.chunks[0].len().min(buf.len());
+            buf[..n].copy_from_slice(&self.chunks[0][..n]);
+            self.chunks[0] = &self.chunks[0][n..];
+            if self.chunks[0].is_empty() {
+                self.chunks.remove(0);
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_read_would_block() {
+        // Reported as what it is, never as EOF, and no data is lost between symbols.
+        let mut r = SymbolRead8(FlakyReader::new(vec![&[42]], ErrorKind::WouldBlock, 3));
+        for _ in 0..3 {
+            assert_eq!(r.read_one().unwrap_err().kind(), ErrorKind::WouldBlock);
+        }
+        assert_eq!(r.read_one().unwrap(), Some(42));
+        assert_eq!(r.read_one().unwrap(), None);
+
+        let mut r = SymbolRead16BE(FlakyReader::new(
+            vec![&[0x12, 0x34]],
+            ErrorKind::WouldBlock,
+            3,
+        ));
+        for _ in 0..3 {
+            assert_eq!(r.read_one().unwrap_err().kind(), ErrorKind::WouldBlock);
+        }
+        assert_eq!(r.read_one().unwrap(), Some(0x1234));
+        assert_eq!(r.read_one().unwrap(), None);
+
+        // In the middle of a symbol, too.
+        let mut r = SymbolRead16BE(FlakyReader::new(
+            vec![&[0x12], &[0x34]],
+            ErrorKind::WouldBlock,
+            0,
+        ));
+        assert_eq!(r.read_one().unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn test_read16_split() {
+        // The halves of a symbol may arrive separately, with interruptions in between.
+        let mut r = SymbolRead16LE(FlakyReader::new(
+            vec![&[0x12], &[0x34, 0x56], &[0x78]],
+            ErrorKind::Interrupted,
+            1,
+        ));
+        assert_eq!(r.read_one().unwrap(), Some(0x3412));
+        assert_eq!(r.read_one().unwrap(), Some(0x7856));
+        assert_eq!(r.read_one().unwrap(), None);
+    }
+
     #[test]
     fn write8_noop() {
         let mut buf = [1, 1, 1, 1, 1, 1, 1];
@@ -205,4 +383,29 @@ mod tests {
             [0x34, 0x12, 0xCD, 0xAB, 0x00, 0x00, 0xFF, 0xFF, 1, 1, 1]
         );
     }
+
+    #[test]
+    fn test_read_i16() {
+        let buf = [0xFF, 0xFE, 0x80, 0x00];
+        let mut r = SymbolReadI16BE(buf.as_slice());
+        assert_eq!(r.read_one().unwrap(), Some(-2));
+        assert_eq!(r.read_one().unwrap(), Some(i16::MIN));
+        assert_eq!(r.read_one().unwrap(), None);
+        let mut r = SymbolReadI16LE(buf.as_slice());
+        assert_eq!(r.read_one().unwrap(), Some(-257));
+        assert_eq!(r.read_one().unwrap(), Some(0x0080));
+        assert_eq!(r.read_one().unwrap(), None);
+    }
+
+    #[test]
+    fn write_i16() {
+        let mut buf = [1, 1, 1, 1, 1];
+        let mut w = SymbolWriteI16BE(buf.as_mut_slice());
+        w.write_one(-2).unwrap();
+        w.write_one(i16::MIN).unwrap();
+        assert_eq!(buf, [0xFF, 0xFE, 0x80, 0x00, 1]);
+        let mut w = SymbolWriteI16LE(buf.as_mut_slice());
+        w.write_one(-2).unwrap();
+        assert_eq!(buf, [0xFE, 0xFF, 0x80, 0x00, 1]);
+    }
 }
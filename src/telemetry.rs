@@ -0,0 +1,188 @@
+//! Optional instrumentation of the compressor, enabled by the `stats` feature.
+
+use crate::{CompressState, Flavor};
+use std::collections::VecDeque;
+use std::io::{Read, Result, Write};
+
+/// Default number of symbols that `BitsPerSymbol` averages over.
+pub const DEFAULT_WINDOW: usize = 256;
+
+/// Moving average of the code length (in bits) of the most recent symbols. When this stops
+/// going down, the tree has stopped adapting to the data.
+#[derive(Clone, Debug)]
+pub struct BitsPerSymbol {
+    window: usize,
+    recent: VecDeque<u32>,
+    sum: u64,
+}
+
+impl BitsPerSymbol {
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window must not be empty");
+        Self {
+            window,
+            recent: VecDeque::with_capacity(window),
+            sum: 0,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Records the code length of one more symbol, forgetting the oldest one if the window is full.
+    pub fn push(&mut self, bits: u32) {
+        if self.recent.len() == self.window {
+            self.sum -= u64::from(self.recent.pop_front().unwrap());
+        }
+        self.recent.push_back(bits);
+        self.sum += u64::from(bits);
+    }
+
+    /// The average over the last `window` symbols, or fewer if there haven't been that many yet.
+    /// `None` before the first symbol.
+    pub fn average(&self) -> Option<f64> {
+        if self.recent.is_empty() {
+            None
+        } else {
+            Some(self.sum as f64 / self.recent.len() as f64)
+        }
+    }
+}
+
+impl Default for BitsPerSymbol {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+/// A sample of how far `compress_traced` got, for plotting how the tree converges.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TracePoint {
+    /// Number of symbols compressed so far.
+    pub symbols: u64,
+    /// Number of bits written for them so far, without the padding at the very end.
+    pub output_bits: u64,
+    /// The longest code since the previous sample, see `CompressState::max_depth`.
+    pub max_depth: u32,
+}
+
+/// Like `compress`, but also appends a `TracePoint` to `trace` after every `every` symbols, and
+/// one more at the end for the rest, if any. The bits per symbol between two samples show how
+/// quickly the tree adapts to the data, and when it stops improving.
+///
+/// # Panics
+///
+/// Panics if `every` is zero.
+pub fn compress_traced<R: Read, W: Write>(
+    flavor: Flavor,
+    mut r: R,
+    mut w: W,
+    every: usize,
+    trace: &mut Vec<TracePoint>,
+) -> Result<()> {
+    assert!(every > 0, "must sample at least every symbol");
+    let symbol_len = if flavor == Flavor::Symbol8 { 1 } else { 2 };
+    let chunk_len = every * symbol_len;
+    let mut state = CompressState::new(flavor);
+    let mut chunk = Vec::with_capacity(chunk_len);
+    let mut out = Vec::new();
+    let mut sampled = 0;
+    loop {
+        chunk.clear();
+        r.by_ref().take(chunk_len as u64).read_to_end(&mut chunk)?;
+        state.push(&chunk, &mut out)?;
+        w.write_all(&out)?;
+        out.clear();
+        let (symbols, output_bits) = state.totals();
+        if symbols > sampled {
+            trace.push(TracePoint {
+                symbols,
+                output_bits,
+                max_depth: state.max_depth(),
+            });
+            state.reset_max_depth();
+            sampled = symbols;
+        }
+        if chunk.len() < chunk_len {
+            break;
+        }
+    }
+    state.finish(&mut out)?;
+    w.write_all(&out)?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let mut avg = BitsPerSymbol::new(2);
+        assert_eq!(avg.average(), None);
+        avg.push(8);
+        assert_eq!(avg.average(), Some(8.0));
+        avg.push(4);
+        assert_eq!(avg.average(), Some(6.0));
+        avg.push(2);
+        assert_eq!(avg.average(), Some(3.0));
+    }
+
+    #[test]
+    fn test_compress_traced() {
+        let input = b"abcd".repeat(1500);
+        let mut compressed = Vec::new();
+        let mut trace = Vec::new();
+        compress_traced(Flavor::Symbol8, &input[..], &mut compressed, 40, &mut trace).unwrap();
+        let mut expected = Vec::new();
+        crate::compress(Flavor::Symbol8, &input[..], &mut expected).unwrap();
+        assert_eq!(compressed, expected);
+
+        // 6000 symbols, so the last sample is exactly at the end.
+        assert_eq!(trace.len(), 150);
+        let last = trace[149];
+        assert_eq!(last.symbols, 6000);
+        assert_eq!(last.output_bits.div_ceil(8), compressed.len() as u64);
+        // The first symbols take 8 bits, until the tree has moved the four letters to the top.
+        // After that, the bits per symbol so far only go down. Each sample covers the same
+        // number of repetitions, so they don't oscillate.
+        let rates: Vec<f64> = trace
+            .iter()
+            .map(|point| point.output_bits as f64 / point.symbols as f64)
+            .collect();
+        assert!(rates.windows(2).all(|pair| pair[1] <= pair[0]), "{rates:?}");
+        assert!(rates[149] < rates[0] - 0.15, "{rates:?}");
+        assert_eq!(trace[0].max_depth, 8);
+        assert!(last.max_depth < 8);
+    }
+
+    #[test]
+    fn test_compress_traced_16() {
+        // A sample for the odd rest, and none for the empty input.
+        let mut trace = Vec::new();
+        compress_traced(
+            Flavor::Symbol16LE,
+            &b"0123456789"[..],
+            Vec::new(),
+            2,
+            &mut trace,
+        )
+        .unwrap();
+        let symbols: Vec<u64> = trace.iter().map(|point| point.symbols).collect();
+        assert_eq!(symbols, [2, 4, 5]);
+        assert!(trace[0].output_bits > trace[2].output_bits - trace[1].output_bits);
+        let mut trace = Vec::new();
+        compress_traced(Flavor::Symbol8, &b""[..], Vec::new(), 2, &mut trace).unwrap();
+        assert!(trace.is_empty());
+    }
+
+    #[test]
+    #[should_panic = "window must not be empty"]
+    fn test_empty_window() {
+        BitsPerSymbol::new(0);
+    }
+}
@@ -0,0 +1,214 @@
+//! Small reusable adapters around `Read` and `Write`.
+
+use std::io::{Error, Read, Result, Write};
+use std::iter::Fuse;
+
+/// Lookup table for the reflected CRC-32 polynomial 0xEDB88320, as used by zlib, gzip, and PNG.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Running CRC-32 state.
+#[derive(Clone, Copy, Debug)]
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(!0)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 >> 8) ^ CRC32_TABLE[((self.0 ^ byte as u32) & 0xFF) as usize];
+        }
+    }
+
+    fn value(self) -> u32 {
+        !self.0
+    }
+}
+
+/// The CRC-32 of `bytes`.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.value()
+}
+
+/// Computes the CRC-32 of everything that is read through it.
+#[derive(Debug)]
+pub struct ChecksumReader<R: Read> {
+    inner: R,
+    crc: Crc32,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// The CRC-32 of the bytes read so far.
+    pub fn finalize(&self) -> u32 {
+        self.crc.value()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Computes the CRC-32 of everything that is written through it. Only the bytes that the inner
+/// writer actually accepted are counted, so partial writes and errors are handled correctly.
+#[derive(Debug)]
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// The CRC-32 of the bytes written so far.
+    pub fn finalize(&self) -> u32 {
+        self.crc.value()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads the bytes of an iterator, e.g. a combinator chain that has no `Read` of its own. An error
+/// from the iterator is returned by the `read` that gets to it; the bytes before it in the same
+/// `read` are returned first, and the error on the next call.
+#[derive(Debug)]
+pub struct IterReader<I: Iterator<Item = Result<u8>>> {
+    inner: Fuse<I>,
+    error: Option<Error>,
+}
+
+impl<I: Iterator<Item = Result<u8>>> IterReader<I> {
+    pub fn new<T: IntoIterator<IntoIter = I>>(inner: T) -> Self {
+        Self {
+            inner: inner.into_iter().fuse(),
+            error: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<u8>>> Read for IterReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let mut n = 0;
+        while n < buf.len() {
+            match self.inner.next() {
+                Some(Ok(byte)) => {
+                    buf[n] = byte;
+                    n += 1;
+                }
+                Some(Err(e)) if n == 0 => return Err(e),
+                Some(Err(e)) => {
+                    self.error = Some(e);
+                    break;
+                }
+                None => break,
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    // Reference values, e.g. from `python3 -c 'import zlib; print(hex(zlib.crc32(b"...")))'`.
+    const REFERENCE: &[(&[u8], u32)] = &[
+        (b"", 0),
+        (b"123456789", 0xCBF4_3926),
+        (b"The quick brown fox jumps over the lazy dog", 0x414F_A339),
+    ];
+
+    #[test]
+    fn test_writer() {
+        for &(data, crc) in REFERENCE {
+            let mut w = ChecksumWriter::new(Vec::new());
+            // In pieces, to make sure that the state carries over.
+            for chunk in data.chunks(4) {
+                w.write_all(chunk).unwrap();
+            }
+            assert_eq!(w.finalize(), crc);
+            assert_eq!(w.into_inner(), data);
+        }
+    }
+
+    #[test]
+    fn test_reader() {
+        for &(data, crc) in REFERENCE {
+            let mut r = ChecksumReader::new(data);
+            let mut buf = Vec::new();
+            r.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, data);
+            assert_eq!(r.finalize(), crc);
+        }
+    }
+
+    #[test]
+    fn test_writer_partial() {
+        // Accepts only two bytes, so the rest must not be counted.
+        let mut buf = [0u8; 2];
+        let mut w = ChecksumWriter::new(&mut buf[..]);
+        assert_eq!(w.write(b"123456789").unwrap(), 2);
+        assert_eq!(w.write(b"3456789").unwrap(), 0);
+        let mut expected = ChecksumWriter::new(io::sink());
+        expected.write_all(b"12").unwrap();
+        assert_eq!(w.finalize(), expected.finalize());
+    }
+}
@@ -0,0 +1,83 @@
+//! Known-answer vectors: inputs together with exactly what `compress` makes of them. Since the
+//! format has no room for variation, these never change without breaking compatibility. The unit
+//! tests check them, and `jan --self-test` runs them on the installed build.
+
+use crate::{compress, decompress, Flavor};
+
+#[derive(Clone, Copy, Debug)]
+pub struct KnownAnswer {
+    pub name: &'static str,
+    pub flavor: Flavor,
+    pub plaintext: &'static [u8],
+    pub compressed: &'static [u8],
+}
+
+impl KnownAnswer {
+    /// Compresses and decompresses in memory, and returns whether both gave the expected result.
+    pub fn check(&self) -> bool {
+        let mut compressed = Vec::new();
+        let mut decompressed = Vec::new();
+        compress(self.flavor, self.plaintext, &mut compressed).is_ok()
+            && compressed == self.compressed
+            && decompress(self.flavor, self.compressed, &mut decompressed).is_ok()
+            && decompressed == self.plaintext
+    }
+}
+
+const fn vector(
+    name: &'static str,
+    flavor: Flavor,
+    plaintext: &'static [u8],
+    compressed: &'static [u8],
+) -> KnownAnswer {
+    KnownAnswer {
+        name,
+        flavor,
+        plaintext,
+        compressed,
+    }
+}
+
+pub const KNOWN_ANSWERS: &[KnownAnswer] = &[
+    vector(
+        "hello-world bit8",
+        Flavor::Symbol8,
+        b"Hello, World!\n",
+        b"\x48\xa5\xa8\xf9\x81\x62\x19\x2f\x91\x16\x4a\x40\x50",
+    ),
+    vector(
+        "hello-world bit16-be",
+        Flavor::Symbol16BE,
+        b"Hello, World!\n",
+        b"\x48\x65\xac\x6c\x99\x60\x40\xaf\x8e\x4a\xf4\x43\x0a",
+    ),
+    vector(
+        "hello-world bit16-le",
+        Flavor::Symbol16LE,
+        b"Hello, World!\n",
+        b"\x65\x48\xa8\xd8\x16\x37\xcd\xc8\x34\x9b\xd5\x36\x02\x88\x40",
+    ),
+    vector(
+        "hello-world i16-be",
+        Flavor::SymbolI16BE,
+        b"Hello, World!\n",
+        b"\xc8\x65\xd8\xd9\x65\x81\x40\xaf\x9c\x8d\xd1\x06\x14",
+    ),
+    vector(
+        "hello-world i16-le",
+        Flavor::SymbolI16LE,
+        b"Hello, World!\n",
+        b"\xe5\x48\xd1\xb0\x96\x37\xcd\xc8\x3c\x9b\xd3\x36\x09\x44\x20",
+    ),
+    vector("empty bit8", Flavor::Symbol8, b"", b""),
+    vector("empty bit16-be", Flavor::Symbol16BE, b"", b""),
+    vector("empty bit16-le", Flavor::Symbol16LE, b"", b""),
+    vector("empty i16-be", Flavor::SymbolI16BE, b"", b""),
+    vector("empty i16-le", Flavor::SymbolI16LE, b"", b""),
+    vector(
+        "short bit8",
+        Flavor::Symbol8,
+        b"short",
+        b"\x73\x51\x3e\xf2\x00",
+    ),
+];
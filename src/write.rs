@@ -0,0 +1,322 @@
+//! Streaming adapters that implement `Write`.
+
+#[cfg(feature = "stats")]
+use crate::telemetry::BitsPerSymbol;
+use crate::{CompressState, Flavor};
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// How many input bytes a single call to `write` accepts at most. This bounds the amount of
+/// compressed data that can pile up while the inner writer isn't accepting anything.
+const MAX_CHUNK: usize = 4096;
+
+/// Compresses everything written to it, and writes the result to the inner writer. The output is
+/// identical to what `compress` would produce for the same input.
+///
+/// Call `finish` (or `try_finish`) when done, otherwise the last few bits are lost.
+///
+/// If the inner writer fails, e.g. with `ErrorKind::WriteZero` because the disk is full, the
+/// compressed bytes that it didn't accept stay buffered. The error is returned without consuming
+/// any of the new input, so the caller can simply retry the same call later; no bit is lost or
+/// written twice.
+pub struct Compressor<W: Write> {
+    inner: W,
+    state: CompressState,
+    /// Compressed bytes that haven't been accepted by `inner` yet.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Compressor<W> {
+    pub fn new(flavor: Flavor, inner: W) -> Self {
+        Self {
+            inner,
+            state: CompressState::new(flavor),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Moving average of the code length of the most recent symbols, see `BitsPerSymbol`.
+    #[cfg(feature = "stats")]
+    pub fn bits_per_symbol(&self) -> &BitsPerSymbol {
+        self.state.bits_per_symbol()
+    }
+
+    /// Restarts the moving average with a different window size.
+    #[cfg(feature = "stats")]
+    pub fn set_bits_per_symbol_window(&mut self, window: usize) {
+        self.state.set_bits_per_symbol_window(window);
+    }
+
+    /// The longest code so far, see `CompressState::max_depth`.
+    #[cfg(feature = "stats")]
+    pub fn max_depth(&self) -> u32 {
+        self.state.max_depth()
+    }
+
+    /// See `CompressState::reset_max_depth`.
+    #[cfg(feature = "stats")]
+    pub fn reset_max_depth(&mut self) {
+        self.state.reset_max_depth();
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes as many buffered bytes to the inner writer as it accepts. On error, the rest stays
+    /// buffered.
+    fn drain(&mut self) -> Result<()> {
+        let pending = &mut self.pending;
+        let mut done = 0;
+        let result = loop {
+            if done == pending.len() {
+                break Ok(());
+            }
+            match self.inner.write(&pending[done..]) {
+                Ok(0) => {
+                    break Err(Error::new(
+                        ErrorKind::WriteZero,
+                        "failed to write compressed data",
+                    ))
+                }
+                Ok(n) => done += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        pending.drain(..done);
+        result
+    }
+
+    /// Pads the last byte, writes all buffered data, and flushes the inner writer. Writing more
+    /// data afterwards is an error. Calling this again after it failed retries the remaining work.
+    pub fn try_finish(&mut self) -> Result<()> {
+        self.state.finish(&mut self.pending)?;
+        self.drain()?;
+        self.inner.flush()
+    }
+
+    /// Like `try_finish`, but also returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.try_finish()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.state.is_finished() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "cannot write after finishing",
+            ));
+        }
+        // Get rid of the backlog first, so that an error doesn't consume any of `buf`.
+        self.drain()?;
+        let buf = &buf[..buf.len().min(MAX_CHUNK)];
+        self.state.push(buf, &mut self.pending)?;
+        // Opportunistically pass the data on. Errors can wait until the next call, since `buf`
+        // has already been consumed.
+        let _ = self.drain();
+        Ok(buf.len())
+    }
+
+    /// Writes all complete bytes to the inner writer, and flushes it. The incomplete last byte
+    /// stays buffered, since its remaining bits aren't known yet.
+    fn flush(&mut self) -> Result<()> {
+        self.drain()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress;
+
+    const INPUT: &[u8] = b"However, this compression scheme is actually surprisingly good for short snippets of data, often beating zstd, lz4, and others.\n";
+
+    fn expected(flavor: Flavor, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        compress(flavor, input, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_same_as_compress() {
+        for input in [&b""[..], b"short", b"Hello, World!\n", INPUT] {
+            let mut c = Compressor::new(Flavor::Symbol8, Vec::new());
+            c.write_all(input).unwrap();
+            assert_eq!(c.finish().unwrap(), expected(Flavor::Symbol8, input));
+        }
+    }
+
+    #[test]
+    fn test_same_as_compress_16() {
+        for flavor in [Flavor::Symbol16BE, Flavor::Symbol16LE] {
+            let mut c = Compressor::new(flavor, Vec::new());
+            // Split in the middle of a symbol on purpose.
+            c.write_all(&INPUT[..5]).unwrap();
+            c.write_all(&INPUT[5..]).unwrap();
+            assert_eq!(c.finish().unwrap(), expected(flavor, INPUT));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_bits_per_symbol_trends_down() {
+        let mut c = Compressor::new(Flavor::Symbol8, Vec::new());
+        c.set_bits_per_symbol_window(32);
+        assert_eq!(c.bits_per_symbol().average(), None);
+        let mut averages = Vec::new();
+        for _ in 0..8 {
+            c.write_all(&b"abcd".repeat(8)).unwrap();
+            averages.push(c.bits_per_symbol().average().unwrap());
+        }
+        // The uniform tree needs 8 bits per symbol, but four symbols can be told apart with 2.
+        assert!(averages[0] < 8.0, "{averages:?}");
+        assert!(averages.windows(2).all(|w| w[1] <= w[0]), "{averages:?}");
+        assert!(averages[7] < averages[0], "{averages:?}");
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_max_depth() {
+        use proptest::prelude::Rng;
+        use proptest::test_runner::{RngAlgorithm, TestRng};
+
+        let mut random = vec![0; 4096];
+        TestRng::deterministic_rng(RngAlgorithm::XorShift).fill_bytes(&mut random);
+        let repetitive = b"abcd".repeat(1024);
+
+        let mut c = Compressor::new(Flavor::Symbol8, Vec::new());
+        assert_eq!(c.max_depth(), 0);
+        c.write_all(&random[..2048]).unwrap();
+        // Splaying random symbols to the top pushes others deeper than in the uniform tree.
+        assert!(c.max_depth() > 8, "{}", c.max_depth());
+        c.reset_max_depth();
+        c.write_all(&random[2048..]).unwrap();
+        assert!(c.max_depth() > 8, "{}", c.max_depth());
+
+        let mut c = Compressor::new(Flavor::Symbol8, Vec::new());
+        c.write_all(&repetitive[..64]).unwrap();
+        // Only the uniform tree at the very start.
+        assert_eq!(c.max_depth(), 8);
+        c.reset_max_depth();
+        c.write_all(&repetitive[64..]).unwrap();
+        assert!(c.max_depth() < 8, "{}", c.max_depth());
+    }
+
+    #[test]
+    fn test_16_odd() {
+        let mut c = Compressor::new(Flavor::Symbol16BE, Vec::new());
+        c.write_all(b"odd").unwrap();
+        assert_eq!(c.try_finish().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_write_after_finish() {
+        let mut c = Compressor::new(Flavor::Symbol8, Vec::new());
+        c.try_finish().unwrap();
+        assert_eq!(c.write(b"x").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    /// Accepts at most `max_per_call` bytes per call, and fails every `fail_every`-th call.
+    struct FlakyWriter {
+        data: Vec<u8>,
+        calls: usize,
+        fail_every: usize,
+        max_per_call: usize,
+        failures: usize,
+    }
+
+    impl FlakyWriter {
+        fn new(fail_every: usize, max_per_call: usize) -> Self {
+            Self {
+                data: Vec::new(),
+                calls: 0,
+                fail_every,
+                max_per_call,
+                failures: 0,
+            }
+        }
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(self.fail_every) {
+                self.failures += 1;
+                return Err(ErrorKind::WriteZero.into());
+            }
+            let n = buf.len().min(self.max_per_call);
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(self.fail_every) {
+                self.failures += 1;
+                return Err(ErrorKind::WriteZero.into());
+            }
+            Ok(())
+        }
+    }
+
+    /// Writes everything, retrying after each failure exactly like a caller that frees up space
+    /// and tries again would.
+    fn write_with_retries(flavor: Flavor, input: &[u8], inner: FlakyWriter) -> FlakyWriter {
+        let mut c = Compressor::new(flavor, inner);
+        let mut pos = 0;
+        while pos < input.len() {
+            match c.write(&input[pos..]) {
+                Ok(n) => pos += n,
+                Err(e) => assert_eq!(e.kind(), ErrorKind::WriteZero),
+            }
+        }
+        while let Err(e) = c.flush() {
+            assert_eq!(e.kind(), ErrorKind::WriteZero);
+        }
+        while let Err(e) = c.try_finish() {
+            assert_eq!(e.kind(), ErrorKind::WriteZero);
+        }
+        c.inner
+    }
+
+    #[test]
+    fn test_fails_once() {
+        let inner = write_with_retries(Flavor::Symbol8, INPUT, FlakyWriter::new(2, usize::MAX));
+        assert!(inner.failures > 0);
+        assert_eq!(inner.data, expected(Flavor::Symbol8, INPUT));
+    }
+
+    #[test]
+    fn test_fails_often() {
+        for fail_every in [2, 3, 5] {
+            for max_per_call in [1, 2, 7] {
+                let inner = write_with_retries(
+                    Flavor::Symbol8,
+                    INPUT,
+                    FlakyWriter::new(fail_every, max_per_call),
+                );
+                assert!(inner.failures > 0);
+                assert_eq!(
+                    inner.data,
+                    expected(Flavor::Symbol8, INPUT),
+                    "fail_every={fail_every} max_per_call={max_per_call}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fails_16() {
+        let inner = write_with_retries(Flavor::Symbol16LE, INPUT, FlakyWriter::new(3, 2));
+        assert!(inner.failures > 0);
+        assert_eq!(inner.data, expected(Flavor::Symbol16LE, INPUT));
+    }
+}
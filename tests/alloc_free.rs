@@ -0,0 +1,98 @@
+//! Allocation-free operation of the 8-bit flavor.
+//!
+//! Once an `Arena8` exists, compressing and decompressing with it must not touch the heap, so
+//! that the codec can run where there is none, or where allocating is too slow to be allowed. The
+//! tree lives in a `static` here, and a counting global allocator checks that the steady-state
+//! loop allocates nothing, not even for degenerate trees.
+//!
+//! This is its own test binary, because the global allocator applies to the whole binary.
+
+use splaycompress::pool::{compress_with_arena, decompress_with_arena};
+use splaycompress::{compress, Arena8, DecompressOptions, Flavor};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Mutex;
+
+struct CountingAllocator;
+
+thread_local! {
+    // Per thread, so that the test harness allocating on other threads doesn't count.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+static ARENA: Mutex<Arena8> = Mutex::new(Arena8::new_uniform());
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// Compresses and decompresses `input` with `ARENA`, using only the given buffers. Returns the
+/// size of the compressed data.
+fn roundtrip(input: &[u8], compressed: &mut [u8], decompressed: &mut [u8]) -> usize {
+    let mut arena = ARENA.lock().unwrap();
+    arena.reset();
+    let stats = compress_with_arena(&mut arena, input, &mut *compressed).unwrap();
+    let compressed = &compressed[..stats.bytes_out as usize];
+    arena.reset();
+    let options = DecompressOptions::default();
+    let stats =
+        decompress_with_arena(&mut arena, compressed, &mut *decompressed, &options).unwrap();
+    assert_eq!(&decompressed[..stats.bytes_out as usize], input);
+    compressed.len()
+}
+
+#[test]
+fn test_steady_state_allocates_nothing() {
+    let text = include_bytes!("corpus/english.txt");
+    // Ascending runs degenerate the tree into a chain of all 255 internal nodes, the deepest it
+    // can get.
+    let mut ascending = [0; 3 * 256];
+    for (i, byte) in ascending.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let inputs: [&[u8]; 4] = [b"", b"a", &text[..], &ascending];
+    let expected: Vec<Vec<u8>> = inputs
+        .iter()
+        .map(|input| {
+            let mut out = Vec::new();
+            compress(Flavor::Symbol8, *input, &mut out).unwrap();
+            out
+        })
+        .collect();
+    let mut compressed = vec![0; 2 * text.len() + 1];
+    let mut decompressed = vec![0; 2 * text.len()];
+
+    // Warm up, in case anything in std allocates lazily the first time around.
+    for input in inputs {
+        roundtrip(input, &mut compressed, &mut decompressed);
+    }
+    let before = allocations();
+    for _ in 0..3 {
+        for input in inputs {
+            roundtrip(input, &mut compressed, &mut decompressed);
+        }
+    }
+    assert_eq!(allocations() - before, 0);
+    // Make sure that the counting really works.
+    drop(std::hint::black_box(Vec::<u8>::with_capacity(1)));
+    assert_eq!(allocations() - before, 1);
+
+    for (input, expected) in inputs.iter().zip(&expected) {
+        let len = roundtrip(input, &mut compressed, &mut decompressed);
+        assert_eq!(&compressed[..len], &expected[..]);
+    }
+}
@@ -0,0 +1,2262 @@
+#![cfg(feature = "binary")]
+
+use proptest::prelude::Rng;
+use proptest::test_runner::{RngAlgorithm, TestRng};
+use splaycompress::dict::Dictionary;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn jan_output(args: &[&str], input: &[u8]) -> Output {
+    program_output(env!("CARGO_BIN_EXE_jan").as_ref(), args, input)
+}
+
+fn program_output(program: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    // Feed stdin from a separate thread, so that a full stdout pipe can't deadlock.
+    let feeder = std::thread::spawn(move || {
+        // jan may legitimately stop reading early, e.g. after rejecting the input.
+        let _ = stdin.write_all(&input);
+    });
+    let output = child.wait_with_output().unwrap();
+    feeder.join().unwrap();
+    output
+}
+
+fn run_jan(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let output = jan_output(args, input);
+    assert!(
+        output.status.success(),
+        "jan {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+#[test]
+fn test_threads_roundtrip() {
+    let input: Vec<u8> = include_bytes!("../README.md")
+        .iter()
+        .copied()
+        .cycle()
+        .take(3 << 20)
+        .collect();
+    let compressed = run_jan(&["--threads", "4", "--block-size", "262144"], &input);
+    assert_eq!(&compressed[..8], splaycompress::MAGIC_FORMAT_BLOCKS);
+    assert!(compressed.len() < input.len());
+    let decompressed = run_jan(&["-d"], &compressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_block_size() {
+    let input: Vec<u8> = include_bytes!("../README.md")
+        .iter()
+        .copied()
+        .cycle()
+        .take(10_000)
+        .collect();
+    // The last one is larger than the input, which makes a single block.
+    for (size, blocks) in [("1K", 10), ("4096", 3), ("1M", 1)] {
+        let compressed = run_jan(&["--block-size", size], &input);
+        assert_eq!(&compressed[..8], splaycompress::MAGIC_FORMAT_BLOCKS);
+        // Each block has a header of 9 bytes, the first of which is the flavor tag.
+        let mut pos = 8;
+        for _ in 0..blocks {
+            assert_eq!(compressed[pos], 1, "{size}");
+            let len = u32::from_be_bytes(compressed[pos + 5..pos + 9].try_into().unwrap());
+            pos += 9 + len as usize;
+        }
+        assert_eq!(compressed[pos..], [0], "{size}");
+        assert_eq!(run_jan(&["-d"], &compressed), input, "{size}");
+    }
+
+    // 0 is the same as no --block-size at all.
+    assert_eq!(
+        run_jan(&["--block-size", "0"], &input),
+        run_jan(&[], &input)
+    );
+
+    for invalid in ["1", "100M", "12X"] {
+        let output = jan_output(&["--block-size", invalid], &input);
+        assert_eq!(output.status.code(), Some(2), "{invalid}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--block-size"));
+    }
+    let output = jan_output(&["--block-size", "0", "--threads", "2"], &input);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// Counts the blocks of `compressed`, which must be a single member in the block format.
+fn count_blocks(compressed: &[u8]) -> usize {
+    assert_eq!(&compressed[..8], splaycompress::MAGIC_FORMAT_BLOCKS);
+    let mut pos = 8;
+    let mut blocks = 0;
+    while compressed[pos] != 0 {
+        let len = u32::from_be_bytes(compressed[pos + 5..pos + 9].try_into().unwrap());
+        pos += 9 + len as usize;
+        blocks += 1;
+    }
+    blocks
+}
+
+#[test]
+fn test_block_size_auto() {
+    let dir = TempDir::new("block-size-auto");
+    let input: Vec<u8> = include_bytes!("../README.md")
+        .iter()
+        .copied()
+        .cycle()
+        .take(520 << 10)
+        .collect();
+    fs::write(dir.0.join("input"), &input).unwrap();
+
+    // Split evenly across the threads.
+    let output = run_jan_in(&dir, &["-c", "--threads", "2", "input"]);
+    assert!(output.status.success());
+    assert_eq!(count_blocks(&output.stdout), 2);
+    // But not into blocks smaller than 256K.
+    let output = run_jan_in(&dir, &["-c", "--threads", "8", "input"]);
+    assert_eq!(count_blocks(&output.stdout), 3);
+    // An explicit --block-size wins.
+    let output = run_jan_in(
+        &dir,
+        &["-c", "--threads", "2", "--block-size", "100K", "input"],
+    );
+    assert_eq!(count_blocks(&output.stdout), 6);
+    // The size of stdin is unknown, so it gets the default of 1M.
+    assert_eq!(count_blocks(&run_jan(&["--threads", "2"], &input)), 1);
+}
+
+#[test]
+fn test_threads_same_output() {
+    let input: Vec<u8> = include_bytes!("../README.md")
+        .iter()
+        .copied()
+        .cycle()
+        .take(1 << 18)
+        .collect();
+    let sequential = run_jan(&["--threads", "1", "--block-size", "16384"], &input);
+    assert_eq!(&sequential[..8], splaycompress::MAGIC_FORMAT_BLOCKS);
+
+    let parallel = run_jan(&["--threads", "4", "--block-size", "16384"], &input);
+    assert!(sequential == parallel);
+
+    // By default, as many threads as there are CPUs.
+    let default = run_jan(&["--block-size", "16384"], &input);
+    assert!(sequential == default);
+    // Without --block-size, a single thread keeps the single stream.
+    assert!(run_jan(&["--threads", "1"], &input) == run_jan(&[], &input));
+
+    let output = jan_output(&["--threads", "0"], &input);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--threads"));
+}
+
+/// A fresh, empty directory that is removed again on drop.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("jan-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir(&path).unwrap();
+        Self(path)
+    }
+
+    fn entries(&self) -> Vec<String> {
+        let mut entries: Vec<String> = fs::read_dir(&self.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        entries.sort();
+        entries
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run_jan_in(dir: &TempDir, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jan"))
+        .args(args)
+        .current_dir(&dir.0)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_file_roundtrip() {
+    let dir = TempDir::new("roundtrip");
+    fs::write(dir.0.join("foo.txt"), b"Hello, World!\n").unwrap();
+    let output = run_jan_in(&dir, &["foo.txt"]);
+    assert!(output.status.success());
+    assert_eq!(dir.entries(), ["foo.txt.jan"]);
+    assert_eq!(
+        fs::read(dir.0.join("foo.txt.jan")).unwrap(),
+        run_jan(&[], b"Hello, World!\n")
+    );
+
+    let output = run_jan_in(&dir, &["-d", "foo.txt.jan"]);
+    assert!(output.status.success());
+    assert_eq!(dir.entries(), ["foo.txt"]);
+    assert_eq!(fs::read(dir.0.join("foo.txt")).unwrap(), b"Hello, World!\n");
+}
+
+#[test]
+fn test_file_existing_output() {
+    let dir = TempDir::new("existing");
+    fs::write(dir.0.join("foo"), b"new").unwrap();
+    fs::write(dir.0.join("foo.jan"), b"old").unwrap();
+    let output = run_jan_in(&dir, &["foo"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("foo.jan already exists"));
+    assert_eq!(fs::read(dir.0.join("foo.jan")).unwrap(), b"old");
+
+    let output = run_jan_in(&dir, &["--force", "foo"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(dir.0.join("foo.jan")).unwrap(),
+        run_jan(&[], b"new")
+    );
+
+    // The same for decompression, and -f is short for --force.
+    fs::write(dir.0.join("foo"), b"old").unwrap();
+    let output = run_jan_in(&dir, &["-d", "foo.jan"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("foo already exists"));
+    assert_eq!(dir.entries(), ["foo", "foo.jan"]);
+    assert_eq!(fs::read(dir.0.join("foo")).unwrap(), b"old");
+    let output = run_jan_in(&dir, &["-d", "-f", "foo.jan"]);
+    assert!(output.status.success());
+    assert_eq!(dir.entries(), ["foo"]);
+    assert_eq!(fs::read(dir.0.join("foo")).unwrap(), b"new");
+}
+
+#[test]
+fn test_output() {
+    let dir = TempDir::new("output");
+    let input = b"Hello, World!\n";
+    let compressed = run_jan(&[], input);
+    fs::write(dir.0.join("foo.txt"), input).unwrap();
+
+    // The input is kept, and no other file appears, not even a temporary one.
+    let output = run_jan_in(&dir, &["-o", "x.jan", "foo.txt"]);
+    assert!(output.status.success());
+    assert_eq!(dir.entries(), ["foo.txt", "x.jan"]);
+    assert_eq!(fs::read(dir.0.join("x.jan")).unwrap(), compressed);
+    let output = run_jan_in(&dir, &["-o", "x.jan", "foo.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("x.jan already exists"));
+    assert!(run_jan_in(&dir, &["-f", "--output", "x.jan", "foo.txt"])
+        .status
+        .success());
+
+    // No suffix needed for -d.
+    fs::rename(dir.0.join("x.jan"), dir.0.join("x.bin")).unwrap();
+    let output = run_jan_in(&dir, &["-d", "-o", "plain", "x.bin"]);
+    assert!(output.status.success());
+    assert_eq!(dir.entries(), ["foo.txt", "plain", "x.bin"]);
+    assert_eq!(fs::read(dir.0.join("plain")).unwrap(), input);
+
+    // From stdin, also spelled `-`.
+    let path = dir.0.join("stdin.jan");
+    let path = path.to_str().unwrap();
+    assert!(run_jan(&["-o", path], input).is_empty());
+    assert_eq!(fs::read(path).unwrap(), compressed);
+    assert!(run_jan(&["-d", "-f", "-o", path, "-"], &compressed).is_empty());
+    assert_eq!(fs::read(path).unwrap(), input);
+
+    // `-o -` is stdout, and `jan -d - -o -` is just a pipe.
+    let foo = dir.0.join("foo.txt");
+    assert_eq!(
+        run_jan(&["-o", "-", foo.to_str().unwrap()], b""),
+        compressed
+    );
+    assert_eq!(run_jan(&["-d", "-", "-o", "-"], &compressed), input);
+    assert_eq!(run_jan(&["-", "-o", "-"], input), compressed);
+    assert_eq!(dir.entries(), ["foo.txt", "plain", "stdin.jan", "x.bin"]);
+
+    // A failure leaves nothing behind.
+    let output = run_jan_in(&dir, &["-d", "-o", "broken", "foo.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(dir.entries(), ["foo.txt", "plain", "stdin.jan", "x.bin"]);
+
+    for args in [
+        &["-o", "x", "foo.txt", "plain"][..],
+        &["-o", "-", "foo.txt", "plain"],
+        &["-o", "x", "-c", "foo.txt"],
+        &["-o", "x", "-t", "x.bin"],
+        &["-o", "x", "-l", "x.bin"],
+        &["-", "foo.txt"],
+    ] {
+        let output = run_jan_in(&dir, args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+    }
+    assert_eq!(dir.entries(), ["foo.txt", "plain", "stdin.jan", "x.bin"]);
+}
+
+/// With the `mmap` feature, files are mapped while stdin is streamed, and both must give the same
+/// output. Without it, this still compares files to stdin.
+#[test]
+fn test_file_same_as_stdin() {
+    let dir = TempDir::new("mmap");
+    let english = fs::read("tests/corpus/english.txt").unwrap();
+    let source = fs::read("tests/corpus/source.rs.txt").unwrap();
+    for (name, input) in [
+        ("english", &english[..]),
+        ("source", &source),
+        ("empty", b""),
+    ] {
+        fs::write(dir.0.join(name), input).unwrap();
+        for args in [
+            &["-c"][..],
+            &["-c", "--flavor", "bit16-le"],
+            &["-c", "--block-size", "1K"],
+        ] {
+            let path = dir.0.join(name);
+            let file_args = [args, &[path.to_str().unwrap()]].concat();
+            let compressed = run_jan(&file_args, b"");
+            assert_eq!(compressed, run_jan(args, input), "{name} {args:?}");
+
+            let compressed_path = dir.0.join(format!("{name}.jan"));
+            fs::write(&compressed_path, &compressed).unwrap();
+            let decompressed = run_jan(&["-dc", compressed_path.to_str().unwrap()], b"");
+            assert_eq!(decompressed, input, "{name} {args:?}");
+        }
+    }
+}
+
+#[test]
+fn test_file_missing_input() {
+    let dir = TempDir::new("missing");
+    let output = run_jan_in(&dir, &["nonexistent"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "jan: nonexistent: No such file or directory\n"
+    );
+    assert!(dir.entries().is_empty());
+}
+
+#[test]
+fn test_file_failure_cleanup() {
+    let dir = TempDir::new("cleanup");
+    // An odd number of bytes cannot be split into 16-bit symbols, so this fails halfway.
+    fs::write(dir.0.join("odd"), b"odd").unwrap();
+    let output = run_jan_in(&dir, &["--flavor", "bit16-be", "odd"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("odd"));
+    assert_eq!(dir.entries(), ["odd"]);
+}
+
+#[test]
+fn test_file_name_derivation() {
+    let dir = TempDir::new("names");
+    fs::write(dir.0.join("archive.tar"), b"data").unwrap();
+    fs::write(dir.0.join("not-compressed.txt"), b"data").unwrap();
+    fs::write(dir.0.join("twice.jan"), b"data").unwrap();
+
+    assert!(run_jan_in(&dir, &["archive.tar"]).status.success());
+    let output = run_jan_in(&dir, &["-d", "not-compressed.txt"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown suffix"));
+    let output = run_jan_in(&dir, &["twice.jan"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already has .jan suffix"));
+    assert_eq!(
+        dir.entries(),
+        ["archive.tar.jan", "not-compressed.txt", "twice.jan"]
+    );
+}
+
+#[test]
+fn test_autodetect_roundtrip() {
+    let input = b"Hello, World!\n";
+    for flavor in ["bit8", "bit16-be", "bit16-le", "i16-be", "i16-le"] {
+        let compressed = run_jan(&["--flavor", flavor], input);
+        assert!(splaycompress::Flavor::from_magic(&compressed[..8]).is_some());
+        // No -d, no --flavor: Everything is in the filemagic.
+        assert_eq!(run_jan(&[], &compressed), input);
+        assert_eq!(run_jan(&["-d"], &compressed), input);
+    }
+}
+
+#[test]
+fn test_autodetect_flavor_disagrees() {
+    let compressed = run_jan(&["--flavor", "bit16-le"], b"Hello, World!\n");
+    let output = jan_output(&["-d", "--flavor", "bit8"], &compressed);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello, World!\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("compressed as bit16-le, ignoring --flavor"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_decompress_without_magic() {
+    let output = jan_output(&["-d"], b"definitely not compressed");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a splaycompress file"));
+}
+
+#[test]
+fn test_no_magic() {
+    let input = b"Hello, World!\n";
+    let compressed = run_jan(&["--no-magic"], input);
+    let mut expected = Vec::new();
+    splaycompress::compress(splaycompress::Flavor::Symbol8, &input[..], &mut expected).unwrap();
+    assert_eq!(compressed, expected);
+    assert_eq!(
+        run_jan(&["-d", "--no-magic", "--flavor", "bit8"], &compressed),
+        input
+    );
+    // The input doesn't say which flavor it is, so it must be given.
+    let output = jan_output(&["-d", "--no-magic"], &compressed);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--flavor"));
+
+    // Input that already has a filemagic isn't compressed again without --force.
+    let output = jan_output(&["--no-magic"], &run_jan(&[], input));
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already looks compressed"));
+    let output = jan_output(&["--no-magic", "-f"], &run_jan(&[], input));
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_raw() {
+    let input = include_bytes!("../README.md");
+    let compressed = run_jan(&["--raw", "--flavor", "bit8"], input);
+    let mut expected = Vec::new();
+    splaycompress::compress8(&input[..], &mut expected).unwrap();
+    assert_eq!(compressed, expected);
+    assert_eq!(
+        run_jan(&["-d", "--raw", "--flavor", "bit8"], &compressed),
+        input
+    );
+
+    // Nothing records the flavor, so it must be given in both directions.
+    for args in [
+        &["--raw"][..],
+        &["--raw", "--flavor", "auto"],
+        &["-d", "--raw"],
+    ] {
+        let output = jan_output(args, input);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--flavor"));
+    }
+    let output = jan_output(&["--raw", "--flavor", "bit8", "--format", "magic"], input);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_format() {
+    // The same bytes as in the library tests, without the block header around them.
+    assert_eq!(
+        run_jan(&["--format", "raw"], b"short"),
+        b"\x73\x51\x3e\xf2\x00"
+    );
+    assert_eq!(run_jan(&["--no-magic"], b"short"), b"\x73\x51\x3e\xf2\x00");
+    assert_eq!(
+        run_jan(&["--format", "framed"], b"short"),
+        b"\x05\x73\x51\x3e\xf2\x00"
+    );
+    assert_eq!(
+        run_jan(&["--format", "magic"], b"short"),
+        run_jan(&[], b"short")
+    );
+
+    let input = b"Hello, World!\n".repeat(10);
+    for format in ["raw", "framed"] {
+        let compressed = run_jan(&["--format", format, "--flavor", "bit16-le"], &input);
+        let args = ["-d", "--format", format, "--flavor", "bit16-le"];
+        assert_eq!(run_jan(&args, &compressed), input, "{format}");
+    }
+
+    let framed = run_jan(&["--format", "framed"], &input);
+    let args = ["-d", "--format", "framed", "--flavor", "bit8"];
+    let output = jan_output(&args, &[&framed[..], b"more"].concat());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("trailing data"));
+    let output = jan_output(&args, &framed[..framed.len() - 1]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // The block format always has a filemagic.
+    for args in [
+        &["--format", "raw", "--threads", "2"][..],
+        &["--format", "framed", "--block-size", "4K"],
+        &["--no-magic", "--format", "raw"],
+    ] {
+        let output = jan_output(args, &input);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn test_flavor_auto() {
+    let text = &include_bytes!("../README.md")[..2048];
+    let utf16le: Vec<u8> = String::from_utf8_lossy(text)
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let cases: [(&[u8], &[&[u8]]); 3] = [
+        (text, &[splaycompress::MAGIC_FORMAT_SYMBOL8]),
+        (
+            &utf16le,
+            &[
+                splaycompress::MAGIC_FORMAT_SYMBOL16LE,
+                splaycompress::MAGIC_FORMAT_SYMBOLI16LE,
+            ],
+        ),
+        // Too short to tell.
+        (&utf16le[..64], &[splaycompress::MAGIC_FORMAT_SYMBOL8]),
+    ];
+    for (input, magics) in cases {
+        let compressed = run_jan(&["--flavor", "auto"], input);
+        assert!(magics.contains(&&compressed[..8]), "{:?}", &compressed[..8]);
+        assert_eq!(run_jan(&["-d"], &compressed), input);
+    }
+
+    // The sample is all that auto can go by.
+    let compressed = run_jan(&["--flavor", "auto", "--auto-sample", "100"], &utf16le);
+    assert_eq!(&compressed[..8], splaycompress::MAGIC_FORMAT_SYMBOL8);
+    // A 16-bit flavor fits the sample, but not the odd length of the whole input, so it's bit8
+    // after all. A pipe is read to the end first, also beyond `--compare-memory`.
+    let odd = [&utf16le[..], b"!"].concat();
+    for memory in ["64M", "2K"] {
+        let args = [
+            "--flavor",
+            "auto",
+            "--auto-sample",
+            "1K",
+            "--compare-memory",
+            memory,
+            "-v",
+        ];
+        let output = jan_output(&args, &odd);
+        assert!(output.status.success(), "{memory}");
+        assert_eq!(&output.stdout[..8], splaycompress::MAGIC_FORMAT_SYMBOL8);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("odd length, using bit8"), "{stderr}");
+        assert_eq!(run_jan(&["-d"], &output.stdout), odd);
+    }
+
+    // Without a filemagic, nobody would know which flavor was picked.
+    let output = jan_output(&["--flavor", "auto", "--no-magic"], text);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// A UTF-16 file of `len` bytes, longer than the default `--auto-sample`.
+fn long_utf16le(len: usize) -> Vec<u8> {
+    let utf16le: Vec<u8> = String::from_utf8_lossy(include_bytes!("../README.md"))
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    utf16le.iter().copied().cycle().take(len).collect()
+}
+
+#[test]
+fn test_flavor_auto_odd_file() {
+    let dir = TempDir::new("auto-odd");
+    let even = long_utf16le(4000);
+    let odd = long_utf16le(4001);
+    fs::write(dir.0.join("even"), &even).unwrap();
+    fs::write(dir.0.join("odd"), &odd).unwrap();
+    let output = run_jan_in(
+        &dir,
+        &[
+            "--flavor",
+            "auto",
+            "--auto-sample",
+            "1K",
+            "-k",
+            "even",
+            "odd",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    let even_jan = fs::read(dir.0.join("even.jan")).unwrap();
+    let odd_jan = fs::read(dir.0.join("odd.jan")).unwrap();
+    assert_ne!(&even_jan[..8], splaycompress::MAGIC_FORMAT_SYMBOL8);
+    assert_eq!(&odd_jan[..8], splaycompress::MAGIC_FORMAT_SYMBOL8);
+    assert_eq!(run_jan(&["-d"], &even_jan), even);
+    assert_eq!(run_jan(&["-d"], &odd_jan), odd);
+}
+
+#[test]
+fn test_memory_limit() {
+    let text = &include_bytes!("../README.md")[..2048];
+    let utf16le: Vec<u8> = String::from_utf8_lossy(text)
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+
+    let output = jan_output(
+        &["--flavor", "bit16-le", "--memory-limit", "256K"],
+        &utf16le,
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("more than --memory-limit"), "{stderr}");
+    // 16-bit flavors fit into a generous limit.
+    let compressed = run_jan(&["--flavor", "bit16-le", "--memory-limit", "1M"], &utf16le);
+    assert_eq!(&compressed[..8], splaycompress::MAGIC_FORMAT_SYMBOL16LE);
+
+    // Without the limit, auto would pick a 16-bit flavor, see `test_flavor_auto`.
+    let output = jan_output(
+        &["--flavor", "auto", "--memory-limit", "1K", "-v"],
+        &utf16le,
+    );
+    assert!(output.status.success());
+    assert_eq!(&output.stdout[..8], splaycompress::MAGIC_FORMAT_SYMBOL8);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("using bit8"));
+    assert_eq!(run_jan(&["-d"], &output.stdout), utf16le);
+    let compressed = run_jan(&["--compare", "--memory-limit", "64K"], &utf16le);
+    assert_eq!(&compressed[..8], splaycompress::MAGIC_FORMAT_SYMBOL8);
+
+    // bit8 needs next to nothing, and is never refused.
+    for args in [
+        &["--memory-limit", "1"][..],
+        &["--flavor", "bit8", "--memory-limit", "0"],
+    ] {
+        let compressed = run_jan(args, text);
+        assert_eq!(compressed, run_jan(&[], text));
+    }
+
+    let output = jan_output(&["--memory-limit", "lots"], text);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_compare() {
+    let be: Vec<u8> = (0..300u16)
+        .flat_map(|i| (1000 + i * 3).to_be_bytes())
+        .collect();
+    let le: Vec<u8> = String::from_utf8_lossy(&include_bytes!("../README.md")[..300])
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    // The 16-bit flavors can't compress an odd number of bytes.
+    let odd = &include_bytes!("../README.md")[..599];
+    for (input, flavor) in [(&be[..], "bit16-be"), (&le, "bit16-le"), (odd, "bit8")] {
+        let output = jan_output(&["--compare", "-v"], input);
+        assert!(output.status.success());
+        // The output is the same as with the winning flavor.
+        assert_eq!(
+            output.stdout,
+            run_jan(&["--flavor", flavor], input),
+            "{flavor}"
+        );
+        assert_eq!(run_jan(&["-d"], &output.stdout), input);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("keeping {flavor}")), "{stderr}");
+        assert_eq!(
+            stderr.contains("bit16-le"),
+            input.len() % 2 == 0,
+            "{stderr}"
+        );
+
+        // Spilling to a temporary file makes no difference.
+        let spilled = run_jan(&["--compare", "--compare-memory", "100"], input);
+        assert_eq!(spilled, output.stdout);
+    }
+    let leftovers: Vec<_> = fs::read_dir(env::temp_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("jan-spool-"))
+        .collect();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
+#[test]
+fn test_level() {
+    let le: Vec<u8> = String::from_utf8_lossy(&include_bytes!("../README.md")[..300])
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    // The flavors that `--compare` tries.
+    let sizes: Vec<usize> = ["bit8", "bit16-be", "bit16-le"]
+        .iter()
+        .map(|flavor| run_jan(&["--flavor", flavor], &le).len())
+        .collect();
+
+    let fastest = run_jan(&["--level", "1"], &le);
+    assert_eq!(fastest, run_jan(&["--flavor", "bit8"], &le));
+    assert_eq!(
+        run_jan(&["--level", "5"], &le),
+        run_jan(&["--flavor", "auto"], &le)
+    );
+    // Like `--flavor auto`, falls back to bit8 for an odd length beyond the sample.
+    let odd = long_utf16le(70_001);
+    let compressed = run_jan(&["--level", "5"], &odd);
+    assert_eq!(compressed, run_jan(&["--flavor", "bit8"], &odd));
+    assert_eq!(run_jan(&["-d"], &compressed), odd);
+    let best = run_jan(&["--level", "9"], &le);
+    assert_eq!(best, run_jan(&["--compare"], &le));
+    assert_eq!(best, run_jan(&["--flavor", "bit16-le"], &le));
+    assert!(sizes.iter().all(|&size| best.len() <= size), "{sizes:?}");
+    assert!(best.len() < fastest.len());
+    // Decompression doesn't care.
+    assert_eq!(run_jan(&["-d", "--level", "9"], &best), le);
+
+    for args in [
+        &["--level", "0"][..],
+        &["--level", "10"],
+        &["--level", "1", "--flavor", "bit8"],
+        &["--level", "9", "--compare"],
+    ] {
+        assert_eq!(jan_output(args, b"").status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn test_benchmark() {
+    let dir = TempDir::new("benchmark");
+    let even = &include_bytes!("../README.md")[..1000];
+    let odd = &even[..499];
+    fs::write(dir.0.join("even"), even).unwrap();
+    fs::write(dir.0.join("odd"), odd).unwrap();
+
+    let output = run_jan_in(&dir, &["--benchmark", "even", "odd"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let flavors = ["bit8", "bit16-be", "bit16-le", "i16-be", "i16-le"];
+    let lines: Vec<&str> = stdout.lines().collect();
+    // A line with the name, the header, and a row per flavor.
+    assert_eq!(lines.len(), 2 * (2 + flavors.len()), "{stdout}");
+    let tables = lines.chunks(2 + flavors.len());
+    for (lines, (name, input)) in tables.zip([("even", even), ("odd", odd)]) {
+        assert_eq!(lines[0], format!("{name}:"), "{stdout}");
+        assert_eq!(
+            lines[1].split_whitespace().collect::<Vec<_>>(),
+            ["flavor", "compressed", "ratio", "MB/s"]
+        );
+        for (line, flavor) in lines[2..].iter().zip(flavors) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields[0], flavor, "{stdout}");
+            if input.len() % 2 == 1 && flavor != "bit8" {
+                assert_eq!(fields[1..], ["-", "-", "-"], "{stdout}");
+                continue;
+            }
+            // Exactly what compressing with that flavor produces.
+            let compressed = run_jan(&["--flavor", flavor], input).len();
+            assert_eq!(fields[1].parse::<usize>().unwrap(), compressed, "{line}");
+            let ratio: f64 = fields[2].parse().unwrap();
+            let expected = compressed as f64 / input.len() as f64;
+            assert!((ratio - expected).abs() < 1e-4, "{line}");
+            assert!(fields[3].parse::<f64>().unwrap() >= 0.0, "{line}");
+        }
+    }
+    // Nothing was written, and nothing was removed.
+    assert_eq!(dir.entries(), ["even", "odd"]);
+
+    let output = run_jan_in(&dir, &["--benchmark", "missing", "odd"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("jan: missing: "));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("odd:\n"));
+
+    for args in [
+        &["--benchmark"][..],
+        &["--benchmark", "-d", "even"],
+        &["--benchmark", "--flavor", "bit8", "even"],
+    ] {
+        assert_eq!(run_jan_in(&dir, args).status.code(), Some(2), "{args:?}");
+    }
+}
+
+/// Parses the output of `--recommend` into one list of `key: value` pairs per file.
+fn parse_recommend(stdout: &str) -> Vec<Vec<(&str, &str)>> {
+    let mut files = Vec::new();
+    for line in stdout.lines() {
+        let (key, value) = line.split_once(": ").unwrap();
+        if key == "file" {
+            files.push(Vec::new());
+        }
+        files.last_mut().unwrap().push((key, value));
+    }
+    files
+}
+
+#[test]
+fn test_recommend() {
+    let dir = TempDir::new("recommend");
+    let text = &include_bytes!("../README.md")[..4096];
+    let mut random = vec![0; 4096];
+    TestRng::deterministic_rng(RngAlgorithm::XorShift).fill_bytes(&mut random);
+    let utf16: Vec<u8> = text.iter().flat_map(|&byte| [byte, 0]).collect();
+    fs::write(dir.0.join("text"), text).unwrap();
+    fs::write(dir.0.join("random"), &random).unwrap();
+    fs::write(dir.0.join("utf16"), &utf16).unwrap();
+
+    let output = run_jan_in(&dir, &["--recommend", "text", "random", "utf16"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let files = parse_recommend(&stdout);
+    assert_eq!(files.len(), 3, "{stdout}");
+    let keys = [
+        "file",
+        "bytes",
+        "distinct_bytes",
+        "byte_entropy",
+        "distinct_words",
+        "word_entropy",
+        "recommended_flavor",
+    ];
+    for file in &files {
+        assert_eq!(file.iter().map(|&(key, _)| key).collect::<Vec<_>>(), keys);
+    }
+    let value = |file: &[(&str, &str)], key: &str| {
+        let (_, value) = file.iter().find(|&&(k, _)| k == key).unwrap();
+        value.to_string()
+    };
+    let entropy = |file: &[(&str, &str)]| value(file, "byte_entropy").parse::<f64>().unwrap();
+    assert_eq!(value(&files[0], "file"), "text");
+    assert_eq!(value(&files[0], "bytes"), "4096");
+    assert_eq!(value(&files[0], "recommended_flavor"), "bit8");
+    assert!(entropy(&files[0]) < 6.0, "{stdout}");
+    // Random bytes can't be compressed at all.
+    assert_eq!(value(&files[1], "distinct_bytes"), "256");
+    assert_eq!(value(&files[1], "recommended_flavor"), "none");
+    assert!(entropy(&files[1]) > 7.9, "{stdout}");
+    // Every byte of the text is a word of UTF-16, which 16-bit symbols see right away.
+    assert_eq!(
+        value(&files[2], "distinct_words"),
+        value(&files[0], "distinct_bytes")
+    );
+    let flavor = value(&files[2], "recommended_flavor");
+    assert!(flavor.ends_with("-le"), "{stdout}");
+    // Nothing was written.
+    assert_eq!(dir.entries(), ["random", "text", "utf16"]);
+
+    for args in [
+        &["--recommend"][..],
+        &["--recommend", "-d", "text"],
+        &["--recommend", "--flavor", "bit8", "text"],
+        &["--recommend", "--benchmark", "text"],
+    ] {
+        assert_eq!(run_jan_in(&dir, args).status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn test_force_compresses_again() {
+    let once = run_jan(&[], b"Hello, World!\n");
+    let twice = run_jan(&["--force"], &once);
+    assert_ne!(twice, b"Hello, World!\n");
+    assert_eq!(run_jan(&[], &run_jan(&[], &twice)), b"Hello, World!\n");
+}
+
+#[test]
+fn test_file_keep() {
+    let dir = TempDir::new("keep");
+    fs::write(dir.0.join("foo"), b"data").unwrap();
+    assert!(run_jan_in(&dir, &["--keep", "foo"]).status.success());
+    assert_eq!(dir.entries(), ["foo", "foo.jan"]);
+    assert!(run_jan_in(&dir, &["-k", "-d", "--force", "foo.jan"])
+        .status
+        .success());
+    assert_eq!(dir.entries(), ["foo", "foo.jan"]);
+    assert_eq!(fs::read(dir.0.join("foo")).unwrap(), b"data");
+}
+
+#[test]
+fn test_file_to_stdout() {
+    let dir = TempDir::new("stdout");
+    fs::write(dir.0.join("foo"), b"data").unwrap();
+    let output = run_jan_in(&dir, &["-c", "foo"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, run_jan(&[], b"data"));
+    assert_eq!(dir.entries(), ["foo"]);
+}
+
+#[test]
+fn test_file_error_keeps_input() {
+    let dir = TempDir::new("error-keeps");
+    fs::write(dir.0.join("foo"), b"data").unwrap();
+    fs::write(dir.0.join("foo.jan"), b"old").unwrap();
+    // Refusing to overwrite is an error, so the input must survive.
+    assert!(!run_jan_in(&dir, &["foo"]).status.success());
+    assert_eq!(dir.entries(), ["foo", "foo.jan"]);
+    assert_eq!(fs::read(dir.0.join("foo")).unwrap(), b"data");
+}
+
+#[test]
+fn test_reproducible() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = TempDir::new("reproducible");
+    let input: Vec<u8> = (0..300_000u32)
+        .map(|i| (i % 251) as u8 ^ (i >> 12) as u8)
+        .collect();
+    let path = dir.0.join("input");
+    fs::write(&path, &input).unwrap();
+    let path = path.to_str().unwrap();
+    let set_mtime = |secs| {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap()
+    };
+
+    // Normally, the block size depends on whether the length of the input is known.
+    let from_stdin = run_jan(&["--threads", "3"], &input);
+    assert_ne!(run_jan(&["-c", "--threads", "2", path], b""), from_stdin);
+
+    let expected = run_jan(&["--reproducible", "--threads", "3"], &input);
+    assert_eq!(expected, from_stdin);
+    for (secs, threads) in [(0, "2"), (2_000_000_000, "8")] {
+        set_mtime(secs);
+        let args = ["-c", "--reproducible", "--threads", threads, path];
+        assert_eq!(run_jan(&args, b""), expected, "{args:?}");
+        assert_eq!(run_jan(&args[..4], &input), expected, "{args:?}");
+    }
+    // The single stream never varied.
+    set_mtime(0);
+    let single = run_jan(&["-c", path], b"");
+    set_mtime(1_000_000_000);
+    assert_eq!(run_jan(&["-c", "--reproducible", path], b""), single);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_file_keeps_metadata() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    let dir = TempDir::new("metadata");
+    let input = dir.0.join("foo.txt");
+    fs::write(&input, b"Hello, World!\n").unwrap();
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&input)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    fs::set_permissions(&input, fs::Permissions::from_mode(0o640)).unwrap();
+    let mode = |name: &str| fs::metadata(dir.0.join(name)).unwrap().permissions().mode() & 0o777;
+    let modified = |name: &str| fs::metadata(dir.0.join(name)).unwrap().modified().unwrap();
+
+    assert!(run_jan_in(&dir, &["foo.txt"]).status.success());
+    assert_eq!(mode("foo.txt.jan"), 0o640);
+    assert_eq!(modified("foo.txt.jan"), mtime);
+    // Even if that makes the output read-only.
+    fs::set_permissions(dir.0.join("foo.txt.jan"), fs::Permissions::from_mode(0o444)).unwrap();
+    assert!(run_jan_in(&dir, &["-d", "foo.txt.jan"]).status.success());
+    assert_eq!(mode("foo.txt"), 0o444);
+    assert_eq!(modified("foo.txt"), mtime);
+
+    let before = SystemTime::now() - Duration::from_secs(60);
+    let output = run_jan_in(&dir, &["--no-timestamp", "foo.txt"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(mode("foo.txt.jan"), 0o444);
+    assert!(modified("foo.txt.jan") > before);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_file_same_as_output() {
+    let dir = TempDir::new("same-file");
+    fs::write(dir.0.join("data.jan"), b"data").unwrap();
+    std::os::unix::fs::symlink("data.jan", dir.0.join("data")).unwrap();
+    // The input is a symlink to what becomes the output, so deleting it must be refused.
+    let output = run_jan_in(&dir, &["--force", "data"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not deleting input"));
+    assert_eq!(dir.entries(), ["data", "data.jan"]);
+    assert_eq!(
+        run_jan(&[], &fs::read(dir.0.join("data.jan")).unwrap()),
+        b"data"
+    );
+}
+
+#[test]
+fn test_test_mode() {
+    let dir = TempDir::new("test-mode");
+    let input: Vec<u8> = include_bytes!("../README.md").repeat(4);
+    let good = run_jan(&[], &input);
+    // The block format knows how long each block is supposed to be, so truncation is certain to
+    // be noticed.
+    let blocks = run_jan(&["--threads", "2", "--block-size", "4096"], &input);
+    fs::write(dir.0.join("good.jan"), &good).unwrap();
+    fs::write(dir.0.join("blocks.jan"), &blocks).unwrap();
+    fs::write(dir.0.join("truncated.jan"), &blocks[..blocks.len() / 2]).unwrap();
+    fs::write(dir.0.join("plain.txt"), &input).unwrap();
+
+    let output = run_jan_in(&dir, &["-t", "good.jan", "blocks.jan"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let output = run_jan_in(
+        &dir,
+        &[
+            "--test",
+            "good.jan",
+            "truncated.jan",
+            "plain.txt",
+            "blocks.jan",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("truncated.jan"));
+    assert!(stderr.contains("plain.txt: not a splaycompress file"));
+    assert!(!stderr.contains("good.jan"));
+    assert!(!stderr.contains("blocks.jan"));
+
+    // Nothing is created or deleted.
+    assert_eq!(
+        dir.entries(),
+        ["blocks.jan", "good.jan", "plain.txt", "truncated.jan"]
+    );
+    assert_eq!(run_jan(&["-t"], &good), b"");
+}
+
+/// Extracts the numbers from a `--verbose` line, i.e. ratio, uncompressed and compressed size.
+fn parse_verbose(line: &str) -> (f64, u64, u64) {
+    let numbers: Vec<&str> = line
+        .split(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
+        .filter(|s| !s.is_empty() && *s != "." && *s != "-" && *s != "--")
+        .collect();
+    // The file name contains no digits, so the first three numbers are the ones we want.
+    (
+        numbers[0].parse().unwrap(),
+        numbers[1].parse().unwrap(),
+        numbers[2].parse().unwrap(),
+    )
+}
+
+#[test]
+fn test_verbose() {
+    let dir = TempDir::new("verbose");
+    let input = include_bytes!("../README.md");
+    fs::write(dir.0.join("readme"), input).unwrap();
+
+    let output = run_jan_in(&dir, &["-v", "readme"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("readme:\t"), "{stderr}");
+    assert!(stderr.contains(" -- replaced with readme.jan "), "{stderr}");
+    let compressed_len = fs::metadata(dir.0.join("readme.jan")).unwrap().len();
+    let (ratio, plain, compressed) = parse_verbose(&stderr);
+    assert_eq!(plain, input.len() as u64);
+    assert_eq!(compressed, compressed_len);
+    let expected_ratio = 100.0 * (1.0 - compressed as f64 / plain as f64);
+    assert!((ratio - expected_ratio).abs() < 0.1, "{stderr}");
+
+    let output = run_jan_in(&dir, &["-v", "-d", "-k", "readme.jan"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(" -- created readme "), "{stderr}");
+    assert_eq!(parse_verbose(&stderr), (ratio, plain, compressed));
+
+    // Streams have no name, but the numbers are the same.
+    let output = jan_output(&["-v"], input);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(parse_verbose(&stderr), (ratio, plain, compressed));
+    assert_eq!(output.stdout.len() as u64, compressed);
+}
+
+/// Diagnostics must never end up in stdout, where they would corrupt the payload. So for each
+/// option that prints something, stdout must be exactly what the library produces, and the text
+/// must be on stderr. The progress bar only appears on a terminal, so it can't be tested here;
+/// `shows_progress` makes sure of that, and the bar goes through `note`, i.e. to stderr, anyway.
+#[test]
+fn test_diagnostics_on_stderr() {
+    let dir = TempDir::new("diagnostics");
+    // Of odd length, so that `--compare` always keeps bit8.
+    let input = &include_bytes!("../README.md")[..8191];
+    fs::write(dir.0.join("readme"), input).unwrap();
+    let mut single = Vec::new();
+    splaycompress::compress_with_magic(splaycompress::Flavor::Symbol8, input, &mut single).unwrap();
+    let mut blocks = Vec::new();
+    let options = splaycompress::BlockOptions {
+        block_size: 4 << 10,
+        threads: 1,
+        ..splaycompress::BlockOptions::default()
+    };
+    splaycompress::compress_blocks(splaycompress::Flavor::Symbol8, input, &mut blocks, &options)
+        .unwrap();
+
+    let compressing: [(&[&str], &[u8]); 6] = [
+        (&["-v"], &single),
+        (&["--stats"], &single),
+        (&["-v", "--stats"], &single),
+        (&["-v", "--stats", "--compare"], &single),
+        (&["-v", "--stats", "--flavor", "auto"], &single),
+        (
+            &["-v", "--stats", "--block-size", "4K", "--threads", "2"],
+            &blocks,
+        ),
+    ];
+    for (args, expected) in compressing {
+        // Both from stdin and from a file with -c, which print slightly different lines.
+        let output = jan_output(args, input);
+        assert!(output.status.success(), "{args:?}");
+        assert!(output.stdout == expected, "{args:?}");
+        assert!(!output.stderr.is_empty(), "{args:?}");
+        let args = [args, &["-c", "readme"]].concat();
+        let output = run_jan_in(&dir, &args);
+        assert!(output.status.success(), "{args:?}");
+        assert!(output.stdout == expected, "{args:?}");
+        assert!(!output.stderr.is_empty(), "{args:?}");
+    }
+
+    fs::write(dir.0.join("readme.jan"), &blocks).unwrap();
+    for args in [
+        &["-d", "-v"][..],
+        &["-d", "--stats"],
+        &["-d", "-v", "--stats"],
+    ] {
+        let output = jan_output(args, &single);
+        assert!(output.status.success(), "{args:?}");
+        assert!(output.stdout == input, "{args:?}");
+        assert!(!output.stderr.is_empty(), "{args:?}");
+        let args = [args, &["-c", "readme.jan"]].concat();
+        let output = run_jan_in(&dir, &args);
+        assert!(output.status.success(), "{args:?}");
+        assert!(output.stdout == input, "{args:?}");
+        assert!(!output.stderr.is_empty(), "{args:?}");
+    }
+
+    // Testing prints nothing at all to stdout, with or without -v.
+    let output = jan_output(&["-t", "-v", "--stats"], &single);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+    let output = run_jan_in(&dir, &["-t", "-v", "--stats", "readme.jan"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+
+    // Warnings, too.
+    let output = jan_output(&["-d", "--flavor", "bit16-le"], &single);
+    assert!(output.status.success());
+    assert!(output.stdout == input);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("warning"));
+}
+
+/// Extracts the `key=value` pairs from the `--stats` line, which must be the last line.
+fn parse_stats(stderr: &str) -> Vec<(String, f64)> {
+    let line = stderr.lines().last().unwrap();
+    let fields = line.strip_prefix("jan: stats: ").unwrap();
+    fields
+        .split(' ')
+        .map(|field| {
+            let (key, value) = field.split_once('=').unwrap();
+            (key.to_owned(), value.parse().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn test_stats_summary() {
+    let dir = TempDir::new("stats-summary");
+    let readme = include_bytes!("../README.md");
+    let inputs: [&[u8]; 3] = [readme, b"Hello, World!\n", b""];
+    let names = ["readme", "hello", "empty"];
+    for (name, input) in names.iter().zip(inputs) {
+        fs::write(dir.0.join(name), input).unwrap();
+    }
+
+    let mut args = vec!["--stats", "-v", "-j", "2"];
+    args.extend(names);
+    let output = run_jan_in(&dir, &args);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    // The summary comes after the per-file lines of `-v`.
+    assert_eq!(stderr.lines().count(), 4, "{stderr}");
+    let stats = parse_stats(&stderr);
+    let keys: Vec<&str> = stats.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(
+        keys,
+        [
+            "files",
+            "bytes_in",
+            "bytes_out",
+            "ratio",
+            "seconds",
+            "mb_per_s"
+        ]
+    );
+    let value = |key: &str| stats.iter().find(|(k, _)| k == key).unwrap().1;
+    let plain: usize = inputs.iter().map(|input| input.len()).sum();
+    let compressed: u64 = names
+        .iter()
+        .map(|name| {
+            fs::metadata(dir.0.join(format!("{name}.jan")))
+                .unwrap()
+                .len()
+        })
+        .sum();
+    assert_eq!(value("files"), 3.0);
+    assert_eq!(value("bytes_in"), plain as f64);
+    assert_eq!(value("bytes_out"), compressed as f64);
+    let ratio = compressed as f64 / plain as f64;
+    assert!((value("ratio") - ratio).abs() < 1e-4, "{stderr}");
+    let seconds = value("seconds");
+    assert!(seconds >= 0.0, "{stderr}");
+    if seconds > 0.0 {
+        // Both numbers are rounded in the output, so allow for that.
+        let throughput = plain as f64 / seconds / 1e6;
+        assert!(
+            (value("mb_per_s") - throughput).abs() <= throughput * 0.01 + 0.01,
+            "{stderr}"
+        );
+    }
+
+    // The same when decompressing, just the other way around.
+    let mut args = vec!["--stats", "-d"];
+    let compressed_names: Vec<String> = names.iter().map(|name| format!("{name}.jan")).collect();
+    args.extend(compressed_names.iter().map(String::as_str));
+    let output = run_jan_in(&dir, &args);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 1, "{stderr}");
+    let stats = parse_stats(&stderr);
+    assert_eq!(stats[0], ("files".to_owned(), 3.0));
+    assert_eq!(stats[1], ("bytes_in".to_owned(), compressed as f64));
+    assert_eq!(stats[2], ("bytes_out".to_owned(), plain as f64));
+
+    // Failed files don't count, but the summary is still printed.
+    let output = run_jan_in(&dir, &["--stats", "readme", "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stats = parse_stats(&String::from_utf8(output.stderr).unwrap());
+    assert_eq!(stats[0], ("files".to_owned(), 1.0));
+    assert_eq!(stats[1], ("bytes_in".to_owned(), readme.len() as f64));
+
+    // Without files, the summary covers stdin.
+    let output = jan_output(&["--stats"], readme);
+    assert!(output.status.success());
+    let stats = parse_stats(&String::from_utf8(output.stderr).unwrap());
+    assert_eq!(stats[1], ("bytes_in".to_owned(), readme.len() as f64));
+    assert_eq!(
+        stats[2],
+        ("bytes_out".to_owned(), output.stdout.len() as f64)
+    );
+}
+
+#[test]
+fn test_stdout_multiple_members() {
+    let dir = TempDir::new("members");
+    fs::write(dir.0.join("a.txt"), b"Hello, ").unwrap();
+    fs::write(dir.0.join("b.txt"), b"World!\n").unwrap();
+    let output = run_jan_in(&dir, &["-c", "a.txt", "b.txt"]);
+    assert!(output.status.success());
+    // -c implies --keep.
+    assert_eq!(dir.entries(), ["a.txt", "b.txt"]);
+    fs::write(dir.0.join("all.jan"), &output.stdout).unwrap();
+
+    let output = run_jan_in(&dir, &["-dc", "all.jan"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello, World!\n");
+    assert_eq!(
+        run_jan(&["-d"], &fs::read(dir.0.join("all.jan")).unwrap()),
+        b"Hello, World!\n"
+    );
+
+    // Decompressing several files to stdout concatenates them, too.
+    assert!(run_jan_in(&dir, &["a.txt", "b.txt"]).status.success());
+    let output = run_jan_in(&dir, &["-d", "-c", "a.txt.jan", "b.txt.jan"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello, World!\n");
+    assert_eq!(dir.entries(), ["a.txt.jan", "all.jan", "b.txt.jan"]);
+}
+
+#[test]
+fn test_stdout_force() {
+    let dir = TempDir::new("stdout-force");
+    let once = run_jan(&[], b"data");
+    fs::write(dir.0.join("once.jan"), &once).unwrap();
+    // Without --force, compressed input is decompressed, even with -c.
+    let output = run_jan_in(&dir, &["-c", "once.jan"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"data");
+    // With -f, it is compressed again.
+    let output = run_jan_in(&dir, &["-c", "-f", "once.jan"]);
+    assert!(output.status.success());
+    assert_eq!(run_jan(&[], &run_jan(&[], &output.stdout)), b"data");
+    assert_eq!(dir.entries(), ["once.jan"]);
+}
+
+#[test]
+fn test_stdout_multiple_no_magic() {
+    let dir = TempDir::new("members-no-magic");
+    fs::write(dir.0.join("a"), b"a").unwrap();
+    fs::write(dir.0.join("b"), b"b").unwrap();
+    let output = run_jan_in(&dir, &["-c", "--no-magic", "a", "b"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--no-magic"));
+}
+
+#[test]
+fn test_multiple_files() {
+    let dir = TempDir::new("multiple");
+    fs::write(dir.0.join("a.log"), b"first").unwrap();
+    fs::write(dir.0.join("b.jan"), b"not actually compressed").unwrap();
+    fs::write(dir.0.join("c.log"), b"third").unwrap();
+
+    // The file in the middle is skipped, but the others are still processed.
+    let output = run_jan_in(&dir, &["a.log", "b.jan", "c.log"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("b.jan: already has .jan suffix"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("a.log"), "{stderr}");
+    assert!(!stderr.contains("c.log"), "{stderr}");
+    assert_eq!(dir.entries(), ["a.log.jan", "b.jan", "c.log.jan"]);
+
+    // Without the suffix, there is no good name for the output.
+    fs::rename(dir.0.join("c.log.jan"), dir.0.join("c.compressed")).unwrap();
+    let output = run_jan_in(&dir, &["-d", "a.log.jan", "c.compressed"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("c.compressed: unknown suffix"), "{stderr}");
+    assert_eq!(dir.entries(), ["a.log", "b.jan", "c.compressed"]);
+    assert_eq!(fs::read(dir.0.join("a.log")).unwrap(), b"first");
+
+    // Unless it goes to stdout anyway.
+    let output = run_jan_in(&dir, &["-d", "-c", "c.compressed"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"third");
+}
+
+#[test]
+fn test_suffix() {
+    let dir = TempDir::new("suffix");
+    fs::write(dir.0.join("a.txt"), b"first").unwrap();
+    fs::write(dir.0.join("b.txt"), b"second").unwrap();
+    assert!(run_jan_in(&dir, &["-S", "spc", "a.txt", "b.txt"])
+        .status
+        .success());
+    assert_eq!(dir.entries(), ["a.txt.spc", "b.txt.spc"]);
+
+    // The default suffix isn't special anymore.
+    let output = run_jan_in(&dir, &["-d", "a.txt.spc"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected .jan"));
+
+    // A leading dot is fine, too.
+    assert!(run_jan_in(
+        &dir,
+        &["-d", "-k", "--suffix", ".spc", "a.txt.spc", "b.txt.spc"]
+    )
+    .status
+    .success());
+    assert_eq!(dir.entries(), ["a.txt", "a.txt.spc", "b.txt", "b.txt.spc"]);
+    assert_eq!(fs::read(dir.0.join("a.txt")).unwrap(), b"first");
+    assert_eq!(fs::read(dir.0.join("b.txt")).unwrap(), b"second");
+
+    let output = run_jan_in(&dir, &["--force", "-S", "txt", "a.txt"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already has .txt suffix"));
+}
+
+#[test]
+fn test_suffix_invalid() {
+    let dir = TempDir::new("suffix-invalid");
+    fs::write(dir.0.join("a"), b"data").unwrap();
+    for suffix in ["", ".", "x/y", "tar.jan"] {
+        let output = run_jan_in(&dir, &["-S", suffix, "a"]);
+        assert!(!output.status.success(), "{suffix:?}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("suffix"));
+        assert_eq!(dir.entries(), ["a"]);
+    }
+}
+
+#[test]
+fn test_dictionary() {
+    let dir = TempDir::new("dictionary");
+    fs::write(dir.0.join("s1"), b"2024-01-01 INFO request took 12ms\n").unwrap();
+    fs::write(dir.0.join("s2"), b"2024-01-02 WARN request took 7ms\n").unwrap();
+    fs::write(dir.0.join("log"), b"2024-01-03 INFO request took 9ms\n").unwrap();
+    let output = run_jan_in(&dir, &["train", "-o", "dict", "s1", "s2"]);
+    assert!(output.status.success());
+
+    assert!(run_jan_in(&dir, &["-k", "--dictionary", "dict", "log"])
+        .status
+        .success());
+    fs::remove_file(dir.0.join("log")).unwrap();
+
+    let output = run_jan_in(&dir, &["-d", "-k", "log.jan"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("dictionary"));
+    assert!(!dir.0.join("log").exists());
+
+    assert!(run_jan_in(&dir, &["-d", "--dictionary", "dict", "log.jan"])
+        .status
+        .success());
+    assert_eq!(
+        fs::read(dir.0.join("log")).unwrap(),
+        b"2024-01-03 INFO request took 9ms\n"
+    );
+
+    let output = run_jan_in(&dir, &["--dictionary", "dict", "--no-magic", "log"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_dump_tree() {
+    let dir = TempDir::new("dump-tree");
+    let input = include_bytes!("../README.md");
+    fs::write(dir.0.join("readme"), input).unwrap();
+
+    let output = run_jan_in(&dir, &["-k", "--dump-tree", "tree.bin", "readme"]);
+    assert!(output.status.success());
+    // The output is the same as without the dump.
+    assert_eq!(
+        fs::read(dir.0.join("readme.jan")).unwrap(),
+        run_jan(&[], input)
+    );
+    let dumped = fs::read(dir.0.join("tree.bin")).unwrap();
+    let tree = Dictionary::from_bytes(&dumped).unwrap();
+    assert_eq!(tree.to_bytes(), Dictionary::train([&input[..]]).to_bytes());
+    // It's a dictionary file, too.
+    let output = run_jan_in(&dir, &["-c", "--dictionary", "tree.bin", "readme"]);
+    assert!(output.status.success());
+
+    // Streams work, too.
+    let dot_path = dir.0.join("tree.dot");
+    let output = jan_output(
+        &[
+            "--dump-tree",
+            dot_path.to_str().unwrap(),
+            "--dump-tree-format",
+            "dot",
+        ],
+        input,
+    );
+    assert!(output.status.success());
+    let dot = fs::read_to_string(dot_path).unwrap();
+    let nodes = dot
+        .lines()
+        .filter(|line| line.contains("[label=") && !line.contains("->"));
+    assert_eq!(nodes.count(), 255 + 256);
+    assert_eq!(
+        dot.lines().filter(|line| line.contains(" -> ")).count(),
+        255 + 256 - 1
+    );
+
+    // Failing to write the dump leaves the output alone.
+    let output = run_jan_in(&dir, &["-c", "--dump-tree", "missing/tree.bin", "readme"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, run_jan(&[], input));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cannot dump the tree to missing/tree.bin"),
+        "{stderr}"
+    );
+
+    for args in [
+        &["--dump-tree", "t", "-d", "readme.jan"][..],
+        &["--dump-tree", "t", "--flavor", "bit16-le", "readme"],
+        &["--dump-tree", "t", "--no-magic", "readme"],
+        &["--dump-tree", "t", "--block-size", "4K", "readme"],
+        &["--dump-tree", "t", "readme", "readme.jan"],
+    ] {
+        assert_eq!(run_jan_in(&dir, args).status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn test_prime_tree() {
+    let dir = TempDir::new("prime-tree");
+    let first = b"2024-01-01 INFO request took 12ms\n2024-01-02 WARN request took 7ms\n";
+    fs::write(dir.0.join("first"), first.repeat(20)).unwrap();
+    fs::write(dir.0.join("log"), b"2024-01-03 INFO request took 9ms\n").unwrap();
+    let output = run_jan_in(&dir, &["-k", "--dump-tree", "tree.bin", "first"]);
+    assert!(output.status.success());
+
+    let output = run_jan_in(&dir, &["-c", "--prime-tree", "tree.bin", "log"]);
+    assert!(output.status.success());
+    let primed = output.stdout;
+    // Not counting the ID of the tree, which follows the filemagic.
+    assert!(primed.len() - 4 < run_jan_in(&dir, &["-c", "log"]).stdout.len());
+    fs::write(dir.0.join("log.jan"), &primed).unwrap();
+
+    let output = run_jan_in(&dir, &["-dc", "log.jan"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("dictionary"));
+    let output = run_jan_in(&dir, &["-dc", "--prime-tree", "tree.bin", "log.jan"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"2024-01-03 INFO request took 9ms\n");
+
+    // The tree has 8-bit symbols, so it doesn't fit any other flavor.
+    for args in [
+        &["--prime-tree", "tree.bin", "--flavor", "bit16-be", "log"][..],
+        &["--prime-tree", "tree.bin", "--flavor", "auto", "log"],
+        &["--prime-tree", "tree.bin", "--compare", "log"],
+        &[
+            "-d",
+            "--prime-tree",
+            "tree.bin",
+            "--flavor",
+            "i16-le",
+            "log.jan",
+        ],
+    ] {
+        let output = run_jan_in(&dir, args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("8-bit"), "{args:?}: {stderr}");
+    }
+    let output = run_jan_in(&dir, &["-c", "--prime-tree", "log", "first"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a splaycompress dictionary"));
+}
+
+/// Splits the rows of `--list` into their columns, without the header.
+fn parse_list(stdout: &str) -> Vec<Vec<&str>> {
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+        ["compressed", "uncompressed", "ratio", "flavor", "name"]
+    );
+    lines
+        .map(|line| line.split_whitespace().collect())
+        .collect()
+}
+
+#[test]
+fn test_list() {
+    let dir = TempDir::new("list");
+    let input = &include_bytes!("../README.md")[..1000];
+    fs::write(dir.0.join("a"), input).unwrap();
+    // The block format records the uncompressed size, but a single stream doesn't.
+    let blocks = run_jan(&["--block-size", "1K"], input);
+    let single = run_jan(&[], input);
+    fs::write(dir.0.join("blocks.jan"), &blocks).unwrap();
+    fs::write(dir.0.join("single.jan"), &single).unwrap();
+    let output = run_jan_in(&dir, &["-c", "a", "a"]);
+    let members = output.stdout;
+    fs::write(dir.0.join("members.jan"), &members).unwrap();
+
+    let output = run_jan_in(&dir, &["-l", "blocks.jan"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let blocks_len = blocks.len().to_string();
+    let ratio = format!("{:.1}%", 100.0 * (1.0 - blocks.len() as f64 / 1000.0));
+    assert_eq!(
+        parse_list(&stdout),
+        [[&blocks_len[..], "1000", &ratio, "bit8", "blocks"]],
+        "{stdout}"
+    );
+
+    let output = run_jan_in(&dir, &["--list", "single.jan"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let single_len = single.len().to_string();
+    assert_eq!(
+        parse_list(&stdout),
+        [[&single_len[..], "?", "?", "bit8", "single"]],
+        "{stdout}"
+    );
+
+    // A row per member, and the totals.
+    let output = run_jan_in(&dir, &["-l", "members.jan"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows = parse_list(&stdout);
+    assert_eq!(rows.len(), 3, "{stdout}");
+    assert_eq!(rows[0], rows[1]);
+    assert_eq!(rows[0][1], "1000");
+    assert_eq!(rows[0][4], "members");
+    let total = members.len().to_string();
+    assert_eq!(rows[2], [&total[..], "2000", rows[0][2], "(totals)"]);
+
+    // The totals are unknown as soon as a single member is, and problems don't stop the rest.
+    let output = run_jan_in(&dir, &["-l", "blocks.jan", "a", "single.jan"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("jan: a: "));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows = parse_list(&stdout);
+    let total = (blocks.len() + single.len()).to_string();
+    assert_eq!(rows[2], [&total[..], "?", "?", "(totals)"], "{stdout}");
+    // Nothing was decompressed.
+    assert!(!dir.0.join("blocks").exists());
+
+    for args in [&["-l"][..], &["-l", "-t", "single.jan"]] {
+        assert_eq!(run_jan_in(&dir, args).status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn test_checkpoint_interval() {
+    use std::io::{Seek, SeekFrom};
+    let dir = TempDir::new("checkpoint-interval");
+    let input: Vec<u8> = include_bytes!("../README.md")
+        .iter()
+        .cycle()
+        .take(50_000)
+        .copied()
+        .collect();
+    fs::write(dir.0.join("big"), &input).unwrap();
+    let output = run_jan_in(&dir, &["--checkpoint-interval", "4K", "-k", "big"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let compressed = fs::read(dir.0.join("big.jan")).unwrap();
+    assert_eq!(run_jan(&["-d"], &compressed), input);
+
+    let file = fs::File::open(dir.0.join("big.jan")).unwrap();
+    let mut seekable = splaycompress::SeekableDecompressor::new(file).unwrap();
+    assert_eq!(seekable.len(), 50_000);
+    assert_eq!(seekable.checkpoints(), 13);
+    seekable.seek(SeekFrom::Start(30_000)).unwrap();
+    let mut buf = [0; 5000];
+    seekable.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, input[30_000..35_000]);
+
+    let output = run_jan_in(&dir, &["-l", "big.jan"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("big (13 checkpoints)"), "{stdout}");
+
+    // A single checkpoint is still a valid index, but probably not what was meant.
+    fs::write(dir.0.join("small"), &input[..1000]).unwrap();
+    let output = run_jan_in(&dir, &["--checkpoint-interval", "4K", "small"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("only one checkpoint"), "{stderr}");
+    let output = run_jan_in(&dir, &["-l", "small.jan"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("small (1 checkpoint)"), "{stdout}");
+
+    for args in [
+        &["--checkpoint-interval", "0"][..],
+        &["--checkpoint-interval", "4K", "--block-size", "4K"],
+        &[
+            "--checkpoint-interval",
+            "4K",
+            "--format",
+            "raw",
+            "--flavor",
+            "bit8",
+        ],
+    ] {
+        assert_eq!(jan_output(args, b"").status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn test_recursive() {
+    let dir = TempDir::new("recursive");
+    fs::create_dir_all(dir.0.join("tree/a/c")).unwrap();
+    fs::write(dir.0.join("tree/top.txt"), b"top").unwrap();
+    fs::write(dir.0.join("tree/a/b.txt"), b"b").unwrap();
+    fs::write(dir.0.join("tree/a/c/d.txt"), b"d").unwrap();
+    let precompressed = run_jan(&[], b"pre");
+    fs::write(dir.0.join("tree/a/pre.txt.jan"), &precompressed).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("top.txt", dir.0.join("tree/link")).unwrap();
+
+    let output = run_jan_in(&dir, &["-r", "tree"]);
+    assert!(output.status.success());
+    #[cfg(unix)]
+    {
+        assert!(String::from_utf8_lossy(&output.stderr).contains("symbolic link"));
+        let link = dir.0.join("tree/link");
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("top.txt"));
+    }
+    assert!(dir.0.join("tree/top.txt.jan").exists());
+    assert!(dir.0.join("tree/a/b.txt.jan").exists());
+    assert!(dir.0.join("tree/a/c/d.txt.jan").exists());
+    assert!(!dir.0.join("tree/a/c/d.txt").exists());
+    assert_eq!(
+        fs::read(dir.0.join("tree/a/pre.txt.jan")).unwrap(),
+        precompressed
+    );
+
+    // The walk is sorted, so the members come in a predictable order.
+    let output = run_jan_in(&dir, &["-d", "-r", "-c", "tree"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"bdpretop");
+
+    assert!(run_jan_in(&dir, &["-d", "-r", "tree"]).status.success());
+    assert_eq!(fs::read(dir.0.join("tree/top.txt")).unwrap(), b"top");
+    assert_eq!(fs::read(dir.0.join("tree/a/c/d.txt")).unwrap(), b"d");
+    assert_eq!(fs::read(dir.0.join("tree/a/pre.txt")).unwrap(), b"pre");
+    assert!(!dir.0.join("tree/a/b.txt.jan").exists());
+}
+
+#[test]
+fn test_stats() {
+    let dir = TempDir::new("stats");
+    let input = b"Hello, World!\n".repeat(1000);
+    let blocks = run_jan(&["--block-size", "4K"], &input);
+    fs::write(dir.0.join("blocks.jan"), &blocks).unwrap();
+    let single = run_jan(&["--flavor", "bit16-le"], &input);
+    fs::write(dir.0.join("single.jan"), &single).unwrap();
+    fs::write(dir.0.join("plain"), b"plain text").unwrap();
+
+    let output = run_jan_in(&dir, &["stats", "blocks.jan", "single.jan", "plain"]);
+    assert!(output.status.success());
+    let saved = 100.0 * (1.0 - blocks.len() as f64 / input.len() as f64);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "blocks.jan: block format, bit8, 4 blocks, {} bytes compressed, {} bytes uncompressed, {saved:.1}% saved\n\
+             single.jan: single stream, bit16-le, {} bytes compressed, uncompressed size unknown\n\
+             plain: unknown format, 10 bytes\n",
+            blocks.len(),
+            input.len(),
+            single.len(),
+        )
+    );
+    // Nothing was decompressed or removed.
+    assert_eq!(dir.entries(), ["blocks.jan", "plain", "single.jan"]);
+
+    fs::write(dir.0.join("truncated.jan"), &blocks[..blocks.len() / 2]).unwrap();
+    let output = run_jan_in(
+        &dir,
+        &["stats", "truncated.jan", "missing.jan", "blocks.jan"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("truncated.jan: "), "{stderr}");
+    assert!(stderr.contains("missing.jan: "), "{stderr}");
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("blocks.jan: block format"));
+}
+
+#[test]
+fn test_jobs() {
+    let dir = TempDir::new("jobs");
+    fs::create_dir(dir.0.join("tree")).unwrap();
+    let contents = |i: usize| format!("file number {i}\n").repeat(i + 1);
+    for i in 0..12 {
+        fs::write(dir.0.join(format!("tree/{i:02}")), contents(i)).unwrap();
+    }
+
+    let output = run_jan_in(&dir, &["-r", "--jobs", "4", "-v", "tree"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // One whole line per file, in whatever order the jobs finished.
+    assert_eq!(stderr.lines().count(), 12, "{stderr}");
+    assert!(stderr.lines().all(|line| line.starts_with("tree/")
+        && line.contains("-- replaced with")
+        && line.ends_with("MB/s)")));
+    for i in 0..12 {
+        let compressed = fs::read(dir.0.join(format!("tree/{i:02}.jan"))).unwrap();
+        assert_eq!(compressed, run_jan(&[], contents(i).as_bytes()));
+        assert!(!dir.0.join(format!("tree/{i:02}")).exists());
+    }
+
+    let output = run_jan_in(&dir, &["-d", "-r", "-k", "-j", "4", "tree"]);
+    assert!(output.status.success());
+    for i in 0..12 {
+        let plain = fs::read(dir.0.join(format!("tree/{i:02}"))).unwrap();
+        assert_eq!(plain, contents(i).as_bytes());
+        assert!(dir.0.join(format!("tree/{i:02}.jan")).exists());
+    }
+
+    // A failure in one job neither stops the others, nor goes unnoticed.
+    fs::write(dir.0.join("tree/bogus.jan"), b"not compressed").unwrap();
+    for i in 0..12 {
+        fs::remove_file(dir.0.join(format!("tree/{i:02}"))).unwrap();
+    }
+    let output = run_jan_in(&dir, &["-d", "-r", "-j", "4", "tree"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bogus.jan"));
+    assert_eq!(fs::read_dir(dir.0.join("tree")).unwrap().count(), 13);
+    assert!(dir.0.join("tree/11").exists());
+
+    let output = run_jan_in(&dir, &["-c", "-j", "2", "tree/00", "tree/01"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_recursive_continues_after_failure() {
+    let dir = TempDir::new("recursive-failure");
+    fs::create_dir_all(dir.0.join("tree/a")).unwrap();
+    fs::write(dir.0.join("tree/a/b.txt"), b"b").unwrap();
+    fs::write(dir.0.join("tree/a/b.txt.jan"), b"in the way").unwrap();
+    fs::write(dir.0.join("tree/z.txt"), b"z").unwrap();
+
+    let output = run_jan_in(&dir, &["-r", "tree"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert_eq!(
+        fs::read(dir.0.join("tree/a/b.txt.jan")).unwrap(),
+        b"in the way"
+    );
+    assert!(dir.0.join("tree/z.txt.jan").exists());
+    assert!(!dir.0.join("tree/z.txt").exists());
+}
+
+/// Common failures end with a message and an exit code, not with a panic.
+#[test]
+fn test_exit_codes() {
+    let cases: [(&[&str], &[u8], i32); 4] = [
+        (&["-d"], b"not compressed", 1),
+        (&["--dictionary", "nonexistent"], b"", 1),
+        (&["-t"], b"not compressed", 1),
+        (&["--no-magic", "--dictionary", "dict"], b"", 2),
+    ];
+    for (args, input, code) in cases {
+        let output = jan_output(args, input);
+        assert_eq!(output.status.code(), Some(code), "{args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("jan: "), "{args:?}: {stderr}");
+        assert!(!stderr.contains("panicked"), "{args:?}: {stderr}");
+        assert!(!stderr.contains("backtrace"), "{args:?}: {stderr}");
+    }
+}
+
+/// Runs `program` with stdout going to a reader that reads `head` bytes and then goes away, like
+/// `jan -dc foo.jan | head -c 10`.
+fn run_into_closed_pipe(program: &str, args: &[&str], input: Vec<u8>, head: usize) -> Output {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let feeder = std::thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+    let mut stdout = child.stdout.take().unwrap();
+    let mut buf = vec![0xFF; head];
+    stdout.read_exact(&mut buf).unwrap();
+    drop(stdout);
+    let output = child.wait_with_output().unwrap();
+    feeder.join().unwrap();
+    output
+}
+
+#[test]
+fn test_broken_pipe() {
+    let plain = vec![0; 1 << 18];
+    let single = run_jan(&[], &plain);
+    let blocks = run_jan(&["--threads", "2"], &plain);
+    let noise: Vec<u8> = (0..1 << 18)
+        .map(|i: u32| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    let jan = env!("CARGO_BIN_EXE_jan");
+    let jancat = env!("CARGO_BIN_EXE_jancat");
+    let cases: [(&str, &[&str], &Vec<u8>); 4] = [
+        (jan, &["-d"], &single),
+        (jan, &["-d"], &blocks),
+        (jan, &[], &noise),
+        (jancat, &[], &blocks),
+    ];
+    for (program, args, input) in cases {
+        // Both with a reader that stops early, and one that never reads anything.
+        for head in [10, 0] {
+            let output = run_into_closed_pipe(program, args, input.clone(), head);
+            assert!(output.status.success(), "{program} {args:?} {head}");
+            assert!(
+                output.stderr.is_empty(),
+                "{program} {args:?} {head}: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_invoked_as_unjan() {
+    let dir = TempDir::new("unjan");
+    let compressed = run_jan(&[], b"data");
+    for name in ["unjan", "jandec"] {
+        let program = dir.0.join(name).with_extension(env::consts::EXE_EXTENSION);
+        fs::copy(env!("CARGO_BIN_EXE_jan"), &program).unwrap();
+
+        let output = program_output(&program, &[], &compressed);
+        assert!(output.status.success(), "{name}");
+        assert_eq!(output.stdout, b"data");
+        // Plain data isn't passed through, like `gunzip` doesn't.
+        let output = program_output(&program, &[], b"data");
+        assert!(!output.status.success(), "{name}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("not a splaycompress file"));
+
+        let output = program_output(&program, &["-z"], b"data");
+        assert!(output.status.success(), "{name}");
+        assert_eq!(output.stdout, compressed);
+    }
+}
+
+fn jancat_in(dir: &TempDir, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jancat"))
+        .args(args)
+        .current_dir(&dir.0)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_jancat() {
+    let dir = TempDir::new("jancat");
+    let first = run_jan(&[], b"first\n");
+    let second = run_jan(&["--flavor", "bit16-le", "--threads", "2"], b"second!\n");
+    fs::write(dir.0.join("a.jan"), &first).unwrap();
+    fs::write(dir.0.join("b.jan"), &second).unwrap();
+
+    let output = jancat_in(&dir, &["a.jan", "b.jan"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"first\nsecond!\n");
+    assert_eq!(dir.entries(), ["a.jan", "b.jan"]);
+    assert_eq!(fs::read(dir.0.join("a.jan")).unwrap(), first);
+
+    // From stdin, including a stream with several members.
+    fs::write(dir.0.join("c"), b"third\n").unwrap();
+    fs::write(dir.0.join("d"), b"fourth\n").unwrap();
+    let members = run_jan_in(&dir, &["-c", "c", "d"]).stdout;
+    let output = program_output(env!("CARGO_BIN_EXE_jancat").as_ref(), &[], &members);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"third\nfourth\n");
+    let output = program_output(env!("CARGO_BIN_EXE_jancat").as_ref(), &[], &first);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"first\n");
+}
+
+#[test]
+fn test_jancat_errors() {
+    let dir = TempDir::new("jancat-errors");
+    fs::write(dir.0.join("a.jan"), run_jan(&[], b"first\n")).unwrap();
+    fs::write(dir.0.join("plain"), b"plain\n").unwrap();
+    let output = jancat_in(&dir, &["missing.jan", "plain", "a.jan"]);
+    assert_eq!(output.status.code(), Some(1));
+    // The good file still comes through.
+    assert_eq!(output.stdout, b"first\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("jancat: missing.jan: No such file or directory"));
+    assert!(stderr.contains("jancat: plain: not a splaycompress file"));
+    assert_eq!(dir.entries(), ["a.jan", "plain"]);
+}
+
+#[test]
+fn test_max_output() {
+    // A decompression bomb, if a small one: Runs of a single byte take about a bit each.
+    let input = vec![0; 128 << 10];
+    let single = run_jan(&["--flavor", "bit8"], &input);
+    let blocks = run_jan(&["--flavor", "bit8", "--block-size", "32K"], &input);
+    let raw = run_jan(&["--flavor", "bit8", "--format", "raw"], &input);
+    assert!(single.len() < input.len() / 4, "{}", single.len());
+    let jancat = env!("CARGO_BIN_EXE_jancat");
+    let jan = env!("CARGO_BIN_EXE_jan");
+    let cases: [(&str, &[&str], &[u8]); 4] = [
+        (jan, &["-d"], &single),
+        (jan, &["-d"], &blocks),
+        (jan, &["-d", "--format", "raw", "--flavor", "bit8"], &raw),
+        (jancat, &[], &blocks),
+    ];
+    for (program, args, compressed) in cases {
+        let limited = [args, &["--max-output", "32K"]].concat();
+        let output = program_output(program.as_ref(), &limited, compressed);
+        assert_eq!(output.status.code(), Some(1), "{program} {args:?}");
+        // Streamed, so some of the output is already out, but never more than the limit.
+        assert!(output.stdout.len() <= 32 << 10, "{program} {args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("output limit exceeded"), "{stderr}");
+
+        let exact = [args, &["--max-output", "128K"]].concat();
+        let output = program_output(program.as_ref(), &exact, compressed);
+        assert!(output.status.success(), "{program} {args:?}");
+        assert!(output.stdout == input, "{program} {args:?}");
+    }
+}
+
+#[test]
+fn test_jan_inspect() {
+    let inspect = env!("CARGO_BIN_EXE_jan-inspect").as_ref();
+    let hello = &splaycompress::vectors::KNOWN_ANSWERS[0];
+    assert_eq!(hello.name, "hello-world bit8");
+    let mut input = splaycompress::MAGIC_FORMAT_SYMBOL8.to_vec();
+    input.extend_from_slice(hello.compressed);
+    let total_bits = format!("total bits: {}", 8 * hello.compressed.len());
+
+    let output = program_output(inspect, &[], &input);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .skip(1)
+        .take(hello.plaintext.len())
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    let symbols: Vec<u8> = lines.iter().map(|row| row[1].parse().unwrap()).collect();
+    assert_eq!(symbols, hello.plaintext);
+    let bits: usize = lines
+        .iter()
+        .map(|row| row[2].parse::<usize>().unwrap())
+        .sum();
+    let padding = format!("padding bits: {}", 8 * hello.compressed.len() - bits);
+    assert!(stdout.contains(&padding), "{stdout}");
+    assert!(stdout.ends_with(&format!("{total_bits}\n")), "{stdout}");
+
+    // "Hello, World!\n" has three 'l' and two 'o', so the counts add up to 14 over 11 rows.
+    let output = program_output(inspect, &["--summary"], &input);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.starts_with("symbols:"))
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(rows.len(), 11, "{stdout}");
+    let count: u64 = rows.iter().map(|row| row[1].parse::<u64>().unwrap()).sum();
+    assert_eq!(count, 14);
+    let summed: usize = rows
+        .iter()
+        .map(|row| row[2].parse::<usize>().unwrap())
+        .sum();
+    assert_eq!(summed, bits);
+    assert!(rows.contains(&vec!["108", "3", "18", "6.000"]), "{stdout}");
+    assert!(stdout.contains(&total_bits), "{stdout}");
+
+    let blocks = run_jan(&["--block-size", "1K"], b"blocks");
+    for data in [&b"not compressed"[..], &blocks] {
+        let output = program_output(inspect, &[], data);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("jan-inspect: "));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_files_from() {
+    let dir = TempDir::new("files-from");
+    let names = ["plain", "with space", "with\nnewline"];
+    for name in names {
+        fs::write(dir.0.join(name), format!("contents of {name}\n").repeat(20)).unwrap();
+    }
+    fs::write(
+        dir.0.join("list"),
+        b"plain\0with space\0missing\0with\nnewline\0",
+    )
+    .unwrap();
+
+    // A missing file is reported, but the others are still compressed, with all of the options.
+    let output = run_jan_in(&dir, &["-T", "list", "--null", "-k", "-S", "sz", "-j", "2"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing"), "{stderr}");
+    for name in names {
+        let original = fs::read(dir.0.join(name)).unwrap();
+        let compressed = fs::read(dir.0.join(format!("{name}.sz"))).unwrap();
+        assert_eq!(run_jan(&["-d"], &compressed), original);
+    }
+
+    // Without --null, the newline splits the name, so neither half is found.
+    fs::write(dir.0.join("list"), b"with\nnewline\n").unwrap();
+    let output = run_jan_in(&dir, &["-T", "list", "-k", "-f"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!dir.0.join("with\nnewline.jan").exists());
+
+    // An empty list does nothing, rather than reading stdin.
+    fs::write(dir.0.join("list"), b"").unwrap();
+    let output = run_jan_in(&dir, &["-T", "list"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = run_jan_in(&dir, &["-T", "list", "plain"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = run_jan_in(&dir, &["--null", "plain"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = run_jan_in(&dir, &["-T", "nonexistent"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_completions() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let script = String::from_utf8(run_jan(&["--completions", shell], b"")).unwrap();
+        assert!(script.contains("--flavor"), "{shell}: {script}");
+    }
+    assert_eq!(
+        jan_output(&["--completions", "tcsh"], b"").status.code(),
+        Some(2)
+    );
+    // Only the footer of --help mentions it, not the list of options.
+    let help = String::from_utf8(run_jan(&["--help"], b"")).unwrap();
+    assert!(!help.contains("--completions <SHELL>"));
+    assert!(help.contains("jan --completions bash"));
+}
+
+#[test]
+fn test_dump_manpage() {
+    let page = String::from_utf8(run_jan(&["--dump-manpage"], b"")).unwrap();
+    assert!(
+        page.starts_with(".ie") && page.contains(".TH jan 1"),
+        "{page}"
+    );
+    // Every option in --help is in the man page, where dashes are escaped.
+    let page = page.replace("\\-", "-");
+    let help = String::from_utf8(run_jan(&["--help"], b"")).unwrap();
+    assert!(help.contains("jan --dump-manpage > jan.1"));
+    let options: Vec<&str> = help
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .filter(|word| word.starts_with("--") && word.len() > 2)
+        .collect();
+    assert!(options.contains(&"--flavor") && options.contains(&"--files-from"));
+    for option in options {
+        if option == "--dump-manpage" || option == "--completions" {
+            continue;
+        }
+        assert!(page.contains(option), "{option}");
+    }
+}
+
+#[test]
+fn test_self_test() {
+    let output = jan_output(&["--self-test"], b"");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().count(),
+        splaycompress::vectors::KNOWN_ANSWERS.len()
+    );
+    assert!(
+        stdout.lines().all(|line| line.starts_with("PASS ")),
+        "{stdout}"
+    );
+    assert!(stdout.contains("PASS hello-world i16-le\n"), "{stdout}");
+}
+
+/// The rows of `--compare-only` as `(flavor, size)`, where the size is `None` for `n/a`.
+fn parse_ranking(table: &str) -> Vec<(String, Option<u64>)> {
+    let mut lines = table.lines();
+    assert!(lines.next().unwrap().starts_with("flavor"), "{table}");
+    lines
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let flavor = fields.next().unwrap().to_owned();
+            (flavor, fields.next().unwrap().parse().ok())
+        })
+        .collect()
+}
+
+#[test]
+fn test_compare_only() {
+    let input = "Hello, World!\n".repeat(50);
+    let output = jan_output(&["--compare-only"], input.as_bytes());
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let ranking = parse_ranking(&String::from_utf8(output.stderr).unwrap());
+    let mut flavors: Vec<&str> = ranking.iter().map(|(flavor, _)| flavor.as_str()).collect();
+    flavors.sort();
+    assert_eq!(flavors, ["bit16-be", "bit16-le", "bit8"]);
+    let sizes: Vec<u64> = ranking.iter().map(|(_, size)| size.unwrap()).collect();
+    assert!(sizes.is_sorted(), "{ranking:?}");
+    for (flavor, size) in &ranking {
+        let compressed = run_jan(&["--flavor", flavor], input.as_bytes());
+        assert_eq!(compressed.len() as u64, size.unwrap(), "{flavor}");
+    }
+
+    // The 16-bit flavors can't compress an odd length, which isn't an error.
+    let output = jan_output(&["--compare-only"], &input.as_bytes()[1..]);
+    assert!(output.status.success());
+    let ranking = parse_ranking(&String::from_utf8(output.stderr).unwrap());
+    assert_eq!(ranking[0].0, "bit8");
+    assert!(ranking[0].1.is_some());
+    assert_eq!(
+        &ranking[1..],
+        [("bit16-be".into(), None), ("bit16-le".into(), None)]
+    );
+
+    // Files are left alone, and each gets its own table.
+    let dir = TempDir::new("compare-only");
+    fs::write(dir.0.join("a"), &input).unwrap();
+    fs::write(dir.0.join("b"), &input[1..]).unwrap();
+    let output = run_jan_in(&dir, &["--compare-only", "a", "b", "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("a:\nflavor"), "{stderr}");
+    assert!(stderr.contains("\nb:\nflavor"), "{stderr}");
+    assert!(stderr.contains("bit16-be  n/a (odd length)"), "{stderr}");
+    assert!(stderr.contains("jan: missing: "), "{stderr}");
+    assert_eq!(dir.entries(), ["a", "b"]);
+
+    let output = jan_output(&["--compare-only", "-d"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    let output = jan_output(&["--compare-only", "--compare"], b"");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_quiet() {
+    let dir = TempDir::new("quiet");
+    fs::write(dir.0.join("a.jan"), b"already named like compressed").unwrap();
+    fs::write(dir.0.join("b"), b"plain").unwrap();
+    fs::create_dir(dir.0.join("tree")).unwrap();
+    fs::write(dir.0.join("tree/c"), b"plain").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("c", dir.0.join("tree/link")).unwrap();
+
+    // The file is skipped with a warning, which counts as a failure.
+    let output = run_jan_in(&dir, &["-k", "a.jan"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("jan: warning: a.jan: already has .jan suffix"),
+        "{stderr}"
+    );
+    // With -q, it's still a failure, but a silent one.
+    let output = run_jan_in(&dir, &["-q", "-k", "a.jan", "b"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stderr.is_empty(), "{:?}", output.stderr);
+    assert!(dir.0.join("b.jan").exists());
+    #[cfg(unix)]
+    {
+        let output = run_jan_in(&dir, &["-r", "-k", "tree"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("symbolic link"));
+        let output = run_jan_in(&dir, &["-q", "-r", "-k", "-f", "tree"]);
+        assert!(output.status.success());
+        assert!(output.stderr.is_empty());
+    }
+
+    // Errors are printed anyway.
+    let output = run_jan_in(&dir, &["-q", "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("jan: missing: "));
+
+    assert_eq!(jan_output(&["-q", "-v"], b"").status.code(), Some(2));
+}
+
+#[test]
+fn test_verbose_twice() {
+    let input = b"Hello, World!\n".repeat(10);
+    let output = jan_output(&["-v", "--block-size", "4K", "--threads", "2"], &input);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.lines().count(), 1, "{stderr}");
+
+    let output = jan_output(&["-vv", "--block-size", "4K", "--threads", "2"], &input);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 2, "{stderr}");
+    assert_eq!(lines[0], "bit8, blocks of 4096 bytes on 2 threads");
+    assert!(lines[1].contains("bytes compressed"), "{stderr}");
+}
@@ -0,0 +1,128 @@
+//! Cross-implementation conformance vectors.
+//!
+//! The files in `tests/conformance/` are the machine-readable spec of the compressed format: Each
+//! line pairs an input with the exact bytes it compresses to, see the header of `basic.txt` for
+//! the syntax. They are meant for other implementations as much as for this one, so they only use
+//! plain hex and the flavor names of `jan --flavor`. This test checks every vector of every
+//! `*.txt` file there, in both directions.
+
+use splaycompress::{compress, decompress, Flavor};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+struct Vector {
+    /// Where the vector comes from, e.g. `basic.txt:17`.
+    origin: String,
+    flavor: Flavor,
+    input: Vec<u8>,
+    compressed: Vec<u8>,
+}
+
+fn parse_hex(field: &str) -> Result<Vec<u8>, String> {
+    if field == "-" {
+        return Ok(Vec::new());
+    }
+    if !field.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in {field:?}"));
+    }
+    (0..field.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&field[i..i + 2], 16).map_err(|_| format!("not hex: {field:?}"))
+        })
+        .collect()
+}
+
+fn parse_vector(line: &str) -> Result<(Flavor, Vec<u8>, Vec<u8>), String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [flavor, input, compressed] = fields[..] else {
+        return Err(format!("expected 3 fields, got {}", fields.len()));
+    };
+    let flavor = flavor.parse().map_err(|e| format!("{flavor:?}: {e}"))?;
+    Ok((flavor, parse_hex(input)?, parse_hex(compressed)?))
+}
+
+fn load(path: &Path) -> Vec<Vector> {
+    let name = path.file_name().unwrap().to_string_lossy();
+    let contents = fs::read_to_string(path).unwrap();
+    let mut vectors = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let origin = format!("{name}:{}", i + 1);
+        let (flavor, input, compressed) =
+            parse_vector(line).unwrap_or_else(|e| panic!("{origin}: {e}"));
+        vectors.push(Vector {
+            origin,
+            flavor,
+            input,
+            compressed,
+        });
+    }
+    vectors
+}
+
+fn vector_files() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn test_parse_vector() {
+    let (flavor, input, compressed) = parse_vector("bit16-le 0a0B -").unwrap();
+    assert_eq!(flavor, Flavor::Symbol16LE);
+    assert_eq!(input, [0x0a, 0x0b]);
+    assert!(compressed.is_empty());
+    assert!(parse_vector("bit8 00").is_err());
+    assert!(parse_vector("bit8 00 00 00").is_err());
+    assert!(parse_vector("bit9 00 00").is_err());
+    assert!(parse_vector("bit8 0 00").is_err());
+    assert!(parse_vector("bit8 0g 00").is_err());
+}
+
+#[test]
+fn test_conformance() {
+    let vectors: Vec<Vector> = vector_files().iter().flat_map(|path| load(path)).collect();
+    assert!(!vectors.is_empty());
+    let mut failures = Vec::new();
+    for vector in &vectors {
+        let mut compressed = Vec::new();
+        compress(vector.flavor, vector.input.as_slice(), &mut compressed).unwrap();
+        if compressed != vector.compressed {
+            failures.push(format!(
+                "{}: compressing gave {}, expected {}",
+                vector.origin,
+                hex(&compressed),
+                hex(&vector.compressed)
+            ));
+        }
+        let mut decompressed = Vec::new();
+        decompress(
+            vector.flavor,
+            vector.compressed.as_slice(),
+            &mut decompressed,
+        )
+        .unwrap();
+        if decompressed != vector.input {
+            failures.push(format!(
+                "{}: decompressing gave {}, expected {}",
+                vector.origin,
+                hex(&decompressed),
+                hex(&vector.input)
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
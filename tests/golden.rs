@@ -0,0 +1,98 @@
+//! Golden-file comparison of the compressed format.
+//!
+//! The bytes produced by `compress` are the format contract of this crate: Any change to them
+//! (bit order, padding, tree initialization, …) silently breaks all existing `.jan` files. This
+//! test compresses a small fixed corpus with every flavor and compares the result against the
+//! files in `tests/golden/`.
+//!
+//! If you *intentionally* changed the format, regenerate the golden files with:
+//!
+//! ```console
+//! $ SPLAYCOMPRESS_BLESS=1 cargo test --test golden
+//! ```
+//!
+//! An empty value or `0` doesn't count.
+
+use splaycompress::{compress, decompress, Flavor};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const BLESS_VAR: &str = "SPLAYCOMPRESS_BLESS";
+
+fn corpus() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("empty", b"".to_vec()),
+        ("hello_world", b"Hello, World!\n".to_vec()),
+        ("short", b"short".to_vec()),
+        ("shorter", b"shorter".to_vec()),
+        (
+            "readme_snippet",
+            b"However, this compression scheme is actually surprisingly good for short snippets of data, often beating zstd, lz4, and others.\n".to_vec(),
+        ),
+        ("zeros", vec![0; 64]),
+        ("all_bytes", (0..=255).collect()),
+        ("all_bytes_reversed", (0..=255).rev().collect()),
+    ]
+}
+
+/// E.g. `tests/golden/zeros.symbol16be.jan` for `Flavor::Symbol16BE`.
+fn golden_path(name: &str, flavor: Flavor) -> PathBuf {
+    let flavor_name = format!("{flavor:?}").to_lowercase();
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("golden");
+    path.push(format!("{name}.{flavor_name}.jan"));
+    path
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn test_golden() {
+    // Only an explicit value blesses, so that e.g. `SPLAYCOMPRESS_BLESS=0` or an empty value
+    // left over in the environment doesn't overwrite the golden files.
+    let bless = env::var_os(BLESS_VAR).is_some_and(|v| !v.is_empty() && v != "0");
+    let mut failures = Vec::new();
+    for (name, plaintext) in corpus() {
+        for flavor in Flavor::ALL {
+            if flavor != Flavor::Symbol8 && plaintext.len() % 2 != 0 {
+                // Not representable as a sequence of 16-bit symbols.
+                continue;
+            }
+            let path = golden_path(name, flavor);
+            let mut actual = Vec::new();
+            compress(flavor, plaintext.as_slice(), &mut actual).unwrap();
+
+            if bless {
+                fs::write(&path, &actual).unwrap();
+            } else {
+                let expected = fs::read(&path).unwrap_or_else(|e| {
+                    panic!(
+                        "cannot read {}: {e} (run with {BLESS_VAR}=1?)",
+                        path.display()
+                    )
+                });
+                if expected != actual {
+                    failures.push(format!(
+                        "{}:\n  expected {}\n  actual   {}",
+                        path.display(),
+                        hex(&expected),
+                        hex(&actual)
+                    ));
+                }
+            }
+
+            let mut roundtrip = Vec::new();
+            decompress(flavor, actual.as_slice(), &mut roundtrip).unwrap();
+            assert_eq!(plaintext, roundtrip, "roundtrip of {name} with {flavor:?}");
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "compressed format changed! If this is intentional, re-run with {BLESS_VAR}=1.\n{}",
+        failures.join("\n")
+    );
+}
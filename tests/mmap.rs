@@ -0,0 +1,27 @@
+#![cfg(feature = "mmap")]
+
+use splaycompress::{compress, compress_mmap, decompress, Flavor};
+use std::env;
+use std::fs;
+
+#[test]
+fn test_compress_mmap() {
+    let path = env::temp_dir().join(format!("splaycompress-mmap-{}", std::process::id()));
+    let text = include_bytes!("corpus/english.txt");
+    for flavor in [Flavor::Symbol8, Flavor::Symbol16LE] {
+        for input in [&text[..], b""] {
+            fs::write(&path, input).unwrap();
+            let mut compressed = Vec::new();
+            compress_mmap(flavor, &path, &mut compressed).unwrap();
+            // The same as compressing the bytes directly.
+            let mut expected = Vec::new();
+            compress(flavor, input, &mut expected).unwrap();
+            assert_eq!(compressed, expected, "{flavor:?}");
+            let mut decompressed = Vec::new();
+            decompress(flavor, &compressed[..], &mut decompressed).unwrap();
+            assert_eq!(decompressed, input, "{flavor:?}");
+        }
+    }
+    fs::remove_file(&path).unwrap();
+    assert!(compress_mmap(Flavor::Symbol8, &path, Vec::new()).is_err());
+}
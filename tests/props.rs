@@ -0,0 +1,146 @@
+//! Property tests over random inputs: Everything must round-trip for every flavor, no matter how
+//! the reader chunks the input, and decompressing arbitrary bytes must fail cleanly instead of
+//! panicking.
+//!
+//! A failure is shrunk to a small input by proptest before it is reported. Its usual environment
+//! variables apply, e.g. `PROPTEST_CASES` for more inputs per property.
+
+use proptest::prelude::*;
+use splaycompress::{
+    compress, decompress_with_magic, decompress_with_options, DecompressOptions, Flavor,
+    MAGIC_FORMAT_BLOCKS,
+};
+use std::io::{self, Read};
+
+const FLAVORS_16: [Flavor; 4] = [
+    Flavor::Symbol16BE,
+    Flavor::Symbol16LE,
+    Flavor::SymbolI16BE,
+    Flavor::SymbolI16LE,
+];
+
+/// Number of inputs per property for the 16-bit flavors, much fewer than for bit8, since building
+/// their tree alone takes a noticeable fraction of a second in debug builds.
+const CASES_16: u32 = 24;
+
+/// Generous, but keeps arbitrary input from decoding to gigabytes.
+const MAX_OUTPUT: u64 = 1 << 16;
+
+/// Random bytes of a random kind, since uniform noise alone hardly exercises the tree.
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..300),
+        // A small alphabet, so that the tree adapts.
+        prop::collection::vec(b'a'..b'e', 0..300),
+        // Runs of a few different bytes.
+        prop::collection::vec((any::<u8>(), 1..40usize), 0..20).prop_map(|runs| {
+            runs.into_iter()
+                .flat_map(|(byte, run)| std::iter::repeat_n(byte, run))
+                .collect()
+        }),
+        // Extremes, which sit at the edges of the tree.
+        prop::collection::vec(
+            prop::sample::select(&[0, 1, 0x7F, 0x80, 0xFE, 0xFF][..]),
+            0..300
+        ),
+    ]
+}
+
+/// Sizes of the chunks that `ChunkedReader` hands out, one after another.
+fn chunks() -> impl Strategy<Value = Vec<usize>> {
+    prop::collection::vec(1..=17usize, 1..16)
+}
+
+/// Hands out the data in chunks of the given sizes, like a pipe or a socket would, and starts over
+/// with the first size once all of them have been used.
+struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunks: std::iter::Cycle<std::slice::Iter<'a, usize>>,
+}
+
+impl<'a> ChunkedReader<'a> {
+    fn new(data: &'a [u8], chunks: &'a [usize]) -> Self {
+        Self {
+            data,
+            chunks: chunks.iter().cycle(),
+        }
+    }
+}
+
+impl Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (*self.chunks.next().unwrap())
+            .min(buf.len())
+            .min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+fn check_roundtrip(flavor: Flavor, input: &[u8], chunks: &[usize]) -> Result<(), TestCaseError> {
+    // The 16-bit flavors only accept whole symbols.
+    let input = match flavor {
+        Flavor::Symbol8 => input,
+        _ => &input[..input.len() & !1],
+    };
+    let mut compressed = Vec::new();
+    compress(flavor, ChunkedReader::new(input, chunks), &mut compressed)?;
+    let mut decompressed = Vec::new();
+    let r = ChunkedReader::new(&compressed, chunks);
+    decompress_with_options(flavor, r, &mut decompressed, &Default::default())?;
+    prop_assert_eq!(decompressed, input);
+    Ok(())
+}
+
+fn check_decompress_arbitrary(flavor: Flavor, input: &[u8]) {
+    let options = DecompressOptions {
+        max_output_bytes: Some(MAX_OUTPUT),
+        ..Default::default()
+    };
+    // Decoding noise is slow for the 16-bit flavors, since every symbol is a deep leaf of a huge
+    // tree, so keep it short there.
+    let input = match flavor {
+        Flavor::Symbol8 => input,
+        _ => &input[..input.len().min(32)],
+    };
+    // Errors are fine, only panics are not.
+    let _ = decompress_with_options(flavor, input, io::sink(), &options);
+    // The same behind each filemagic, so that the container code gets its share.
+    let mut with_magic = flavor.magic().to_vec();
+    with_magic.extend_from_slice(input);
+    let _ = decompress_with_magic(&with_magic[..], io::sink(), &options);
+    let mut blocks = MAGIC_FORMAT_BLOCKS.to_vec();
+    blocks.extend_from_slice(input);
+    let _ = decompress_with_magic(&blocks[..], io::sink(), &options);
+}
+
+proptest! {
+    #[test]
+    fn test_roundtrip_8(input in bytes(), chunks in chunks()) {
+        check_roundtrip(Flavor::Symbol8, &input, &chunks)?;
+    }
+
+    #[test]
+    fn test_decompress_arbitrary_8(input in bytes()) {
+        check_decompress_arbitrary(Flavor::Symbol8, &input);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES_16))]
+
+    #[test]
+    fn test_roundtrip_16(
+        flavor in prop::sample::select(&FLAVORS_16[..]),
+        input in bytes(),
+        chunks in chunks(),
+    ) {
+        check_roundtrip(flavor, &input, &chunks)?;
+    }
+
+    #[test]
+    fn test_decompress_arbitrary_16(flavor in prop::sample::select(&FLAVORS_16[..]), input in bytes()) {
+        check_decompress_arbitrary(flavor, &input);
+    }
+}
@@ -0,0 +1,70 @@
+//! Compression-ratio regression test.
+//!
+//! Unlike `tests/golden.rs`, this doesn't pin the exact bytes, only how many of them there are:
+//! A change to the encoding that keeps the ratio is fine, but a change that makes the algorithm
+//! compress worse is caught here even if every round-trip still works.
+//!
+//! The baselines were taken from the implementation at the time. If a change *improves* the
+//! ratio, lower them accordingly.
+
+use splaycompress::{compress, Flavor};
+
+const FLAVORS: [(Flavor, &str); 3] = [
+    (Flavor::Symbol8, "symbol8"),
+    (Flavor::Symbol16BE, "symbol16be"),
+    (Flavor::Symbol16LE, "symbol16le"),
+];
+
+fn corpus() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("english", include_bytes!("corpus/english.txt").to_vec()),
+        ("source", include_bytes!("corpus/source.rs.txt").to_vec()),
+        // Incompressible-looking bytes, committed so that they stay the same.
+        ("random", include_bytes!("corpus/random.bin").to_vec()),
+    ]
+}
+
+/// Maximum permitted compressed size, in bytes, for each corpus entry and flavor.
+const BASELINES: &[(&str, &str, usize)] = &[
+    ("english", "symbol8", 913),
+    ("english", "symbol16be", 869),
+    ("english", "symbol16le", 865),
+    ("source", "symbol8", 1456),
+    ("source", "symbol16be", 1343),
+    ("source", "symbol16le", 1334),
+    ("random", "symbol8", 2752),
+    ("random", "symbol16be", 2580),
+    ("random", "symbol16le", 2601),
+];
+
+fn baseline(name: &str, flavor_name: &str) -> usize {
+    BASELINES
+        .iter()
+        .find(|(n, f, _)| *n == name && *f == flavor_name)
+        .unwrap_or_else(|| panic!("no baseline for {name}.{flavor_name}"))
+        .2
+}
+
+#[test]
+fn test_ratio() {
+    let mut failures = Vec::new();
+    for (name, plaintext) in corpus() {
+        for (flavor, flavor_name) in FLAVORS {
+            let mut compressed = Vec::new();
+            compress(flavor, &plaintext[..], &mut compressed).unwrap();
+            let limit = baseline(name, flavor_name);
+            if compressed.len() > limit {
+                failures.push(format!(
+                    "{name}.{flavor_name}: {} bytes (from {}), baseline is {limit}",
+                    compressed.len(),
+                    plaintext.len()
+                ));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "compression got worse:\n{}",
+        failures.join("\n")
+    );
+}
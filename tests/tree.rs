@@ -0,0 +1,119 @@
+//! The tree as seen from outside the crate: Walking it through `NodeArena::splayable_mut`.
+
+use core::fmt::Debug;
+use splaycompress::{
+    Arena16, Arena8, Direction, InconsistencyError, Node, NodeArena, NodeRef, WalkError,
+};
+
+#[test]
+fn test_try_go() {
+    let mut arena = Arena8::new_uniform();
+    let mut walker = arena.splayable_mut();
+    assert!(walker.is_root());
+    // The uniform tree is 8 levels deep, with symbol 0 at the far left.
+    for _ in 0..8 {
+        walker.try_go(Direction::Left).unwrap();
+    }
+    assert!(walker.is_leaf());
+    assert_eq!(walker.current_value(), 0);
+    assert_eq!(walker.depth(), 8);
+    assert_eq!(
+        walker.try_go(Direction::Left),
+        Err(WalkError::DescendOnLeaf)
+    );
+    // Unchanged by the error.
+    assert_eq!(walker.current_value(), 0);
+    assert_eq!(walker.depth(), 8);
+
+    walker.try_splay_parent_of_leaf().unwrap();
+    assert!(walker.is_root());
+    assert_eq!(walker.current_value(), 0);
+    assert_eq!(walker.try_splay_parent_of_leaf(), Err(WalkError::NotOnLeaf));
+    assert!(arena.is_consistent());
+    assert_ne!(arena, Arena8::new_uniform());
+}
+
+#[test]
+fn test_splay_symbol() {
+    let mut warmed = Arena8::new_uniform();
+    for symbol in b"abracadabra" {
+        warmed.splayable_mut().splay_symbol(*symbol);
+    }
+    assert!(warmed.is_consistent());
+
+    // The same by hand.
+    let mut by_hand = Arena8::new_uniform();
+    for &symbol in b"abracadabra" {
+        let mut walker = by_hand.splayable_mut();
+        while !walker.is_leaf() {
+            let dir = Direction::from_bit(symbol > walker.current_value());
+            walker.try_go(dir).unwrap();
+        }
+        assert_eq!(walker.current_value(), symbol);
+        walker.try_splay_parent_of_leaf().unwrap();
+    }
+    assert_eq!(warmed, by_hand);
+}
+
+/// The degenerate chain, in which internal node `i` has leaf `i` on its left and internal node
+/// `i + 1` on its right, up to the last one, which has the two largest leaves.
+fn chain<T: Copy + Debug + Eq>(ids: impl Iterator<Item = (T, T)>, max: T) -> Vec<Node<T>> {
+    ids.map(|(id, next)| Node {
+        left: NodeRef::Leaf(id),
+        right: if next == max {
+            NodeRef::Leaf(max)
+        } else {
+            NodeRef::Internal(next)
+        },
+    })
+    .collect()
+}
+
+#[test]
+fn test_arena8_from_nodes() {
+    let nodes = chain((0..u8::MAX).map(|id| (id, id + 1)), u8::MAX);
+    let mut arena = Arena8::from_nodes(nodes.clone().try_into().unwrap(), 0).unwrap();
+    assert_eq!(arena.root_idx(), NodeRef::Internal(0));
+    // Symbol 0 is right at the top, and 255 at the very bottom.
+    let mut walker = arena.splayable_mut();
+    walker.try_go(Direction::Left).unwrap();
+    assert_eq!(walker.current_value(), 0);
+    assert_eq!(walker.depth(), 1);
+
+    // The right arm of the root doesn't cover 128.
+    let mut bad = nodes;
+    bad[0].right = NodeRef::Internal(128);
+    assert!(Arena8::from_nodes(bad.try_into().unwrap(), 0).is_err());
+}
+
+#[test]
+fn test_arena16_from_nodes() {
+    let nodes = chain((0..u16::MAX).map(|id| (id, id + 1)), u16::MAX);
+    let arena = Arena16::from_nodes(nodes.clone(), 0).unwrap();
+    assert_eq!(arena.root_idx(), NodeRef::Internal(0));
+    assert_ne!(arena, Arena16::new_uniform());
+
+    let mut bad = nodes;
+    bad[u16::MAX as usize - 1].left = NodeRef::Leaf(7);
+    assert!(Arena16::from_nodes(bad, 0).is_err());
+}
+
+#[test]
+fn test_validate() {
+    let mut arena = Arena8::new_uniform();
+    assert_eq!(arena.validate(), Ok(()));
+    // Internal node 0 separates the leaves 0 and 1.
+    arena.node_mut(0).right = NodeRef::Leaf(5);
+    let e = arena.validate().unwrap_err();
+    assert_eq!(
+        e,
+        InconsistencyError::Leaf {
+            parent: 0,
+            leaf: 5,
+            cover_min: 1,
+            cover_max_incl: 1,
+        }
+    );
+    assert!(e.to_string().contains("internal node 0"), "{e}");
+    assert!(!arena.is_consistent());
+}